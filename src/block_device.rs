@@ -0,0 +1,89 @@
+use std::{
+    fs,
+    io::{self, BufReader, Read, Seek, SeekFrom},
+    path::Path,
+};
+
+/// A reader over a block device (e.g. `/dev/sdb1`) or an image file, which knows its real length.
+///
+/// `SeekFrom::End` isn't reliable on block devices and their metadata reports a length of 0,
+/// so on Linux the size is queried with the `BLKGETSIZE64` ioctl.
+pub struct BlockDevice {
+    reader: BufReader<fs::File>,
+    len: u64,
+    pos: u64,
+}
+
+impl BlockDevice {
+    /// Open a block device or a regular file in read-only mode.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let file = fs::File::open(path)?;
+        let len = device_size(&file)?;
+
+        Ok(Self {
+            reader: BufReader::new(file),
+            len,
+            pos: 0,
+        })
+    }
+
+    /// Get the size of the device in bytes.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn device_size(file: &fs::File) -> io::Result<u64> {
+    use std::os::unix::{fs::FileTypeExt, io::AsRawFd};
+
+    // _IOR(0x12, 114, size_t)
+    const BLKGETSIZE64: libc::c_ulong = (2 << 30)
+        | ((std::mem::size_of::<libc::size_t>() as libc::c_ulong) << 16)
+        | (0x12 << 8)
+        | 114;
+
+    let metadata = file.metadata()?;
+    if !metadata.file_type().is_block_device() {
+        return Ok(metadata.len());
+    }
+
+    let mut size: u64 = 0;
+    // SAFETY: BLKGETSIZE64 writes a u64 to the given pointer.
+    let ret = unsafe { libc::ioctl(file.as_raw_fd(), BLKGETSIZE64 as _, &mut size) };
+    if ret != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(size)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn device_size(file: &fs::File) -> io::Result<u64> {
+    Ok(file.metadata()?.len())
+}
+
+impl Read for BlockDevice {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.reader.read(buf)?;
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl Seek for BlockDevice {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let pos = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => self.len.checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.pos.checked_add_signed(offset),
+        }
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Invalid seek position"))?;
+
+        self.pos = self.reader.seek(SeekFrom::Start(pos))?;
+        Ok(self.pos)
+    }
+}
@@ -0,0 +1,207 @@
+// https://www.kernel.org/doc/html/latest/filesystems/ext4/overview.html#checksums
+
+const CRC32C_POLY: u32 = 0x82F6_3B78;
+
+const CRC32C_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut j = 0;
+        while j < 8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ CRC32C_POLY
+            } else {
+                crc >> 1
+            };
+            j += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+/// crc16 with the reflected polynomial 0x8005, see lib/crc16.c of Linux.
+const CRC16_TABLE: [u16; 256] = {
+    let mut table = [0u16; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u16;
+        let mut j = 0;
+        while j < 8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xA001
+            } else {
+                crc >> 1
+            };
+            j += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+/// The algorithm of metadata checksums, selected by the feature flags of the super block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChecksumKind {
+    /// Neither gdt_csum nor metadata_csum, nothing is checksummed.
+    None,
+    /// gdt_csum (also known as uninit_bg), only block group descriptors are checksummed.
+    Crc16,
+    /// metadata_csum, all metadata but the super block is checksummed with a seeded crc32c.
+    Crc32c,
+}
+
+/// Offset of the checksum in the super block.
+const SUPER_BLOCK_CHECKSUM_OFFSET: usize = 0x3FC;
+/// Offset of `bg_checksum` in the block group descriptor.
+const DESCRIPTOR_CHECKSUM_OFFSET: usize = 0x1E;
+/// Offset of `i_generation` in the inode.
+const INODE_GENERATION_OFFSET: usize = 0x64;
+/// Offset of `l_i_checksum_lo` (in `osd2`) in the inode.
+const INODE_CHECKSUM_LO_OFFSET: usize = 0x7C;
+/// Offset of `i_extra_isize` in the inode.
+const INODE_EXTRA_ISIZE_OFFSET: usize = 0x80;
+/// Offset of `i_checksum_hi` in the inode.
+const INODE_CHECKSUM_HI_OFFSET: usize = 0x82;
+/// Size of `ext4_dir_entry_tail`.
+const DIR_ENTRY_TAIL_SIZE: usize = 12;
+
+/// crc32c as used by ext4, without the pre and post inversion.
+pub fn crc32c(mut crc: u32, data: &[u8]) -> u32 {
+    for &b in data {
+        crc = CRC32C_TABLE[((crc ^ b as u32) & 0xFF) as usize] ^ (crc >> 8);
+    }
+    crc
+}
+
+/// crc16 as used by gdt_csum, without the pre and post inversion.
+pub fn crc16(mut crc: u16, data: &[u8]) -> u16 {
+    for &b in data {
+        crc = (crc >> 8) ^ CRC16_TABLE[((crc ^ b as u16) & 0xFF) as usize];
+    }
+    crc
+}
+
+fn read_u16(buf: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([buf[offset], buf[offset + 1]])
+}
+
+fn read_u32(buf: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([
+        buf[offset],
+        buf[offset + 1],
+        buf[offset + 2],
+        buf[offset + 3],
+    ])
+}
+
+/// Check the checksum of the raw 1024 bytes super block.
+pub fn verify_super_block(raw: &[u8]) -> bool {
+    let expected = read_u32(raw, SUPER_BLOCK_CHECKSUM_OFFSET);
+    crc32c(!0, &raw[..SUPER_BLOCK_CHECKSUM_OFFSET]) == expected
+}
+
+/// Get the seed of all metadata checksums from the uuid of file system.
+pub fn fs_seed(uuid: &[u8]) -> u32 {
+    crc32c(!0, uuid)
+}
+
+/// Get the seed of the checksums of an inode, its extent tree and directory blocks.
+pub fn inode_seed(fs_seed: u32, ino: u64, generation: u32) -> u32 {
+    let crc = crc32c(fs_seed, &(ino as u32).to_le_bytes());
+    crc32c(crc, &generation.to_le_bytes())
+}
+
+/// Check the checksum of a raw block group descriptor.
+pub fn verify_descriptor(fs_seed: u32, group: u64, raw: &[u8]) -> bool {
+    let expected = read_u16(raw, DESCRIPTOR_CHECKSUM_OFFSET);
+
+    let mut crc = crc32c(fs_seed, &(group as u32).to_le_bytes());
+    crc = crc32c(crc, &raw[..DESCRIPTOR_CHECKSUM_OFFSET]);
+    crc = crc32c(crc, &[0, 0]);
+    crc = crc32c(crc, &raw[DESCRIPTOR_CHECKSUM_OFFSET + 2..]);
+
+    crc as u16 == expected
+}
+
+/// Check the crc16 checksum of a raw block group descriptor on a gdt_csum file system.
+pub fn verify_descriptor_crc16(uuid: &[u8], group: u64, raw: &[u8]) -> bool {
+    let expected = read_u16(raw, DESCRIPTOR_CHECKSUM_OFFSET);
+
+    let mut crc = crc16(!0, uuid);
+    crc = crc16(crc, &(group as u32).to_le_bytes());
+    crc = crc16(crc, &raw[..DESCRIPTOR_CHECKSUM_OFFSET]);
+    crc = crc16(crc, &raw[DESCRIPTOR_CHECKSUM_OFFSET + 2..]);
+
+    crc == expected
+}
+
+/// Check the checksum of a raw inode of `inode_size` bytes.
+pub fn verify_inode(fs_seed: u32, ino: u64, raw: &[u8]) -> bool {
+    let mut raw = raw.to_vec();
+    // checksum_hi only exists if it's covered by extra_isize
+    let has_hi = raw.len() > INODE_EXTRA_ISIZE_OFFSET
+        && INODE_EXTRA_ISIZE_OFFSET + read_u16(&raw, INODE_EXTRA_ISIZE_OFFSET) as usize
+            >= INODE_CHECKSUM_HI_OFFSET + 2;
+
+    let mut expected = read_u16(&raw, INODE_CHECKSUM_LO_OFFSET) as u32;
+    raw[INODE_CHECKSUM_LO_OFFSET..INODE_CHECKSUM_LO_OFFSET + 2].fill(0);
+    if has_hi {
+        expected |= (read_u16(&raw, INODE_CHECKSUM_HI_OFFSET) as u32) << 16;
+        raw[INODE_CHECKSUM_HI_OFFSET..INODE_CHECKSUM_HI_OFFSET + 2].fill(0);
+    }
+
+    let generation = read_u32(&raw, INODE_GENERATION_OFFSET);
+    let crc = crc32c(inode_seed(fs_seed, ino, generation), &raw);
+    if has_hi {
+        crc == expected
+    } else {
+        crc & 0xFFFF == expected
+    }
+}
+
+/// Check the checksum of an extent tree block, whose `ext4_extent_tail` follows `max` entries.
+pub fn verify_extent_block(inode_seed: u32, max: u16, block: &[u8]) -> bool {
+    let tail = 12 + 12 * max as usize;
+    if tail + 4 > block.len() {
+        return false;
+    }
+    crc32c(inode_seed, &block[..tail]) == read_u32(block, tail)
+}
+
+/// Check the checksum of a directory leaf block.
+///
+/// Returns `None` if the block doesn't end with a `ext4_dir_entry_tail`, e.g. htree nodes.
+pub fn verify_dir_block(inode_seed: u32, block: &[u8]) -> Option<bool> {
+    let tail = block.len().checked_sub(DIR_ENTRY_TAIL_SIZE)?;
+    let is_tail = read_u32(block, tail) == 0
+        && read_u16(block, tail + 4) == DIR_ENTRY_TAIL_SIZE as u16
+        && block[tail + 6] == 0
+        && block[tail + 7] == 0xDE;
+    if !is_tail {
+        return None;
+    }
+    Some(crc32c(inode_seed, &block[..tail]) == read_u32(block, tail + 8))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{crc16, crc32c};
+
+    #[test]
+    fn test_crc32c() {
+        // standard check value of CRC-32C
+        assert_eq!(!crc32c(!0, b"123456789"), 0xE306_9283);
+        assert_eq!(crc32c(!0, b""), !0);
+    }
+
+    #[test]
+    fn test_crc16() {
+        // check values of CRC-16/ARC and CRC-16/MODBUS
+        assert_eq!(crc16(0, b"123456789"), 0xBB3D);
+        assert_eq!(crc16(!0, b"123456789"), 0x4B37);
+    }
+}
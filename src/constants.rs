@@ -1,19 +1,81 @@
 // https://www.kernel.org/doc/html/latest/filesystems/ext4/overview.html#special-inodes
+/// Bad blocks list.
+pub const INO_BAD_BLOCKS: u64 = 1;
 /// Root directory.
 pub const INO_ROOT: u64 = 2;
+/// User quota.
+pub const INO_USR_QUOTA: u64 = 3;
+/// Group quota.
+pub const INO_GRP_QUOTA: u64 = 4;
+/// Boot loader.
+pub const INO_BOOT_LOADER: u64 = 5;
+/// Undelete directory.
+pub const INO_UNDEL_DIR: u64 = 6;
+/// Reserved group descriptors for online resizing.
+pub const INO_RESIZE: u64 = 7;
+/// Journal.
+pub const INO_JOURNAL: u64 = 8;
+/// First inode that isn't reserved in revision 0 file systems, `lost+found` by default.
+pub const GOOD_OLD_FIRST_INO: u64 = 11;
 
 pub const ZERO_PADDING_SIZE: u64 = 1024;
 
+/// Has a journal.
+pub const FEATURE_COMPAT_HAS_JOURNAL: u32 = 0x4;
+/// Backup super blocks are only in the groups listed by `s_backup_bgs`.
+pub const FEATURE_COMPAT_SPARSE_SUPER2: u32 = 0x200;
+
+/// Backup super blocks are only in groups 0, 1 and powers of 3, 5 and 7.
+pub const FEATURE_RO_COMPAT_SPARSE_SUPER: u32 = 0x1;
+
+/// Files can be larger than 2 GiB.
+pub const FEATURE_RO_COMPAT_LARGE_FILE: u32 = 0x2;
+/// Unused, reserved for btree directories.
+pub const FEATURE_RO_COMPAT_BTREE_DIR: u32 = 0x4;
+/// Group descriptors have checksums.
+pub const FEATURE_RO_COMPAT_GDT_CSUM: u32 = 0x10;
+/// Filesystem supports metadata checksumming.
+pub const FEATURE_RO_COMPAT_METADATA_CSUM: u32 = 0x400;
+/// Blocks are allocated in clusters of `2^s_log_cluster_size` KiB.
+pub const FEATURE_RO_COMPAT_BIGALLOC: u32 = 0x200;
+
+/// `s_checksum_type` of crc32c, the only algorithm of metadata_csum.
+pub const CHECKSUM_TYPE_CRC32C: u8 = 1;
+
 /// Directory entries record the file type.
 pub const FEATURE_INCOMPAT_FILETYPE: u32 = 0x2;
 /// Filesystem needs recovery.
 pub const FEATURE_INCOMPAT_RECOVER: u32 = 0x4;
+/// Block group descriptors are stored in the first groups of each meta group.
+pub const FEATURE_INCOMPAT_META_BG: u32 = 0x10;
 /// Files in this filesystem use extents.
 pub const FEATURE_INCOMPAT_EXTENTS: u32 = 0x40;
 /// Enable a filesystem size of 2^64 blocks (INCOMPAT_64BIT).
 pub const FEATURE_INCOMPAT_64BIT: u32 = 0x80;
 /// Flexible block groups.
 pub const FEATURE_INCOMPAT_FLEX_BG: u32 = 0x200;
+/// Metadata checksum seed is stored in the superblock.
+pub const FEATURE_INCOMPAT_CSUM_SEED: u32 = 0x2000;
+/// Directories larger than 2 GiB and htrees of 3 levels.
+pub const FEATURE_INCOMPAT_LARGEDIR: u32 = 0x4000;
+
+/// Size of the super block.
+pub const SUPER_BLOCK_SIZE: usize = 1024;
+
+/// Size of block group descriptor without the 64bit feature.
+pub const DESC_SIZE_32BIT: usize = 32;
+/// Size of block group descriptor with the 64bit feature.
+pub const DESC_SIZE_64BIT: usize = 64;
+
+/// The inode table and bitmap of the block group aren't initialized.
+pub const BG_INODE_UNINIT: u16 = 0x1;
+
+/// Size of inode in the original ext2 format, without any extra fields.
+pub const INODE_SIZE_GOOD_OLD: usize = 128;
+/// Size of inode including all extra fields known by this crate.
+pub const INODE_SIZE_FULL: usize = 160;
+/// Offset of `i_block` in the inode.
+pub const INODE_BLOCK_OFFSET: usize = 0x28;
 
 /// FIFO
 pub const INODE_MODE_FIFO: u16 = 0x1000;
@@ -30,14 +92,72 @@ pub const INODE_MODE_LNK: u16 = 0xA000;
 /// Socket
 pub const INODE_MODE_SOCK: u16 = 0xC000;
 
+// https://www.kernel.org/doc/html/latest/filesystems/ext4/globals.html#super-block
+/// Print debugging info upon (re)mount.
+pub const DEFM_DEBUG: u32 = 0x0001;
+/// New files take the gid of the containing directory.
+pub const DEFM_BSDGROUPS: u32 = 0x0002;
+/// Support userspace-provided extended attributes.
+pub const DEFM_XATTR_USER: u32 = 0x0004;
+/// Support POSIX access control lists.
+pub const DEFM_ACL: u32 = 0x0008;
+/// Do not support 32-bit UIDs.
+pub const DEFM_UID16: u32 = 0x0010;
+/// Mask of the journal mode bits.
+pub const DEFM_JMODE: u32 = 0x0060;
+/// All data and metadata are committed to the journal.
+pub const DEFM_JMODE_DATA: u32 = 0x0020;
+/// All data are flushed to the disk before metadata are committed to the journal.
+pub const DEFM_JMODE_ORDERED: u32 = 0x0040;
+/// Data ordering is not preserved.
+pub const DEFM_JMODE_WBACK: u32 = 0x0060;
+/// Disable write flushes.
+pub const DEFM_NOBARRIER: u32 = 0x0100;
+/// Track which blocks in a filesystem are metadata.
+pub const DEFM_BLOCK_VALIDITY: u32 = 0x0200;
+/// Enable DISCARD support.
+pub const DEFM_DISCARD: u32 = 0x0400;
+/// Disable delayed allocation.
+pub const DEFM_NODELALLOC: u32 = 0x0800;
+
 pub const SUPER_BLOCK_MAGIC: u16 = 0xEF53;
 pub const EXTENT_HEADER_MAGIC: u16 = 0xF30A;
+/// Maximum depth of an extent tree, `EXT4_MAX_EXTENT_DEPTH`.
+pub const EXTENT_MAX_DEPTH: u16 = 5;
+/// Maximum length of an initialized extent, a larger `ee_len` marks an uninitialized extent.
+pub const EXTENT_INIT_MAX_LEN: u16 = 32768;
 
 // https://www.kernel.org/doc/html/latest/filesystems/ext4/dynamic.html#i-flags
+/// Encrypted inode.
+pub const INODE_FLAG_ENCRYPT: u32 = 0x800;
+/// Top of directory hierarchies.
+pub const INODE_FLAG_TOPDIR: u32 = 0x2_0000;
+/// The file is huge, `i_blocks` is in units of file system blocks.
+pub const INODE_FLAG_HUGE_FILE: u32 = 0x4_0000;
+/// Verity protected inode.
+pub const INODE_FLAG_VERITY: u32 = 0x10_0000;
+/// Directory with case-insensitive names.
+pub const INODE_FLAG_CASEFOLD: u32 = 0x4000_0000;
 /// Inode uses extents.
 pub const INODE_FLAG_EXTENTS: u32 = 0x8_0000;
 /// Directory has hashed indexes
 pub const INODE_FLAG_INDEX: u32 = 0x1000;
+/// Inode has inline data.
+pub const INODE_FLAG_INLINE_DATA: u32 = 0x1000_0000;
+
+// https://www.kernel.org/doc/html/latest/filesystems/ext4/dynamic.html#hash-tree-directories
+pub const DX_HASH_LEGACY: u8 = 0x0;
+pub const DX_HASH_HALF_MD4: u8 = 0x1;
+pub const DX_HASH_TEA: u8 = 0x2;
+pub const DX_HASH_LEGACY_UNSIGNED: u8 = 0x3;
+pub const DX_HASH_HALF_MD4_UNSIGNED: u8 = 0x4;
+pub const DX_HASH_TEA_UNSIGNED: u8 = 0x5;
+
+/// `s_flags`: the default directory hash treats names as `unsigned char`.
+pub const FLAGS_UNSIGNED_HASH: u32 = 0x2;
+
+/// Maximum number of symlinks followed while resolving a path, like `MAXSYMLINKS` of Linux.
+pub const MAX_SYMLINK_FOLLOWS: u32 = 40;
 
 pub const DOT_DIR_NAME: &[u8] = b".";
 pub const DOTDOT_DIR_NAME: &[u8] = b"..";
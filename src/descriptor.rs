@@ -1,9 +1,60 @@
-use std::io::Read;
+use std::{cmp, io::Read};
 
 use bincode::Options;
 use serde::Deserialize;
 
-use super::{errors::ExtfsError, utils::compute_u64};
+use super::{
+    constants::{DESC_SIZE_32BIT, DESC_SIZE_64BIT},
+    errors::ExtfsError,
+    utils::compute_u64,
+};
+
+/// Free blocks counted by the super block and by the block group descriptors, see
+/// `FileSystem::free_blocks_verified`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FreeBlocksCheck {
+    /// `s_free_blocks_count` of the super block.
+    pub super_block: u64,
+    /// Sum of `bg_free_blocks_count` of all groups, converted from clusters with bigalloc.
+    pub groups: u64,
+    /// Whether both counts agree, they usually don't after an unclean unmount.
+    pub consistent: bool,
+}
+
+/// Inode usage like `df -i`, see `FileSystem::inode_usage`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InodeUsage {
+    /// `s_inodes_count` of the super block.
+    pub total: u64,
+    /// `s_free_inodes_count` of the super block.
+    pub free: u64,
+    /// Inodes in use, `total - free`.
+    pub used: u64,
+    /// Sum of `bg_free_inodes_count` of all groups.
+    pub groups_free: u64,
+    /// Whether the super block and the groups agree on the free inodes.
+    pub consistent: bool,
+}
+
+/// Layout and usage of a block group, see `FileSystem::block_groups`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockGroupInfo {
+    pub group: u64,
+    /// First block of the group, groups start at `s_first_data_block`.
+    pub first_block: u64,
+    pub block_bitmap: u64,
+    pub inode_bitmap: u64,
+    /// First block of the inode table.
+    pub inode_table: u64,
+    /// Free blocks, free clusters with bigalloc.
+    pub free_blocks: u64,
+    pub free_inodes: u64,
+    pub used_dirs: u64,
+    /// Inodes at the end of the inode table which were never used.
+    pub itable_unused: u64,
+    /// `bg_flags`, e.g. `INODE_UNINIT` (0x1), `BLOCK_UNINIT` (0x2) and `ITABLE_ZEROED` (0x4).
+    pub flags: u16,
+}
 
 #[derive(Deserialize, Debug, Default)]
 #[allow(dead_code)]
@@ -59,15 +110,68 @@ impl BlockGroupDescriptor {
         compute_u64(self.descriptor32.inode_table_lo, self.inode_table_hi)
     }
 
-    pub fn from_reader(mut reader: impl Read, is_64bit: bool) -> Result<Self, ExtfsError> {
+    /// Check whether the bitmaps and the inode table are all at block 0, like in a descriptor
+    /// table that was wiped.
+    pub fn is_zeroed(&self) -> bool {
+        self.get_block_bitmap_loc() == 0
+            && self.get_inode_bitmap_loc() == 0
+            && self.get_inode_table_loc() == 0
+    }
+
+    /// Get the number of free blocks in the group.
+    pub fn get_free_blocks_count(&self) -> u64 {
+        (self.free_blocks_count_hi as u64) << 16 | self.descriptor32.free_blocks_count_lo as u64
+    }
+
+    /// Get the number of free inodes in the group.
+    pub fn get_free_inodes_count(&self) -> u64 {
+        (self.free_inodes_count_hi as u64) << 16 | self.descriptor32.free_inodes_count_lo as u64
+    }
+
+    /// Get the number of directories in the group.
+    pub fn get_used_dirs_count(&self) -> u64 {
+        (self.used_dirs_count_hi as u64) << 16 | self.descriptor32.used_dirs_count_lo as u64
+    }
+
+    /// Get the number of never used inodes at the end of the inode table.
+    pub fn get_itable_unused(&self) -> u64 {
+        (self.itable_unused_hi as u64) << 16 | self.descriptor32.itable_unused_lo as u64
+    }
+
+    pub fn get_flags(&self) -> u16 {
+        self.descriptor32.flags
+    }
+
+    /// Summarize the descriptor of `group`, which starts at `first_block`.
+    pub fn info(&self, group: u64, first_block: u64) -> BlockGroupInfo {
+        BlockGroupInfo {
+            group,
+            first_block,
+            block_bitmap: self.get_block_bitmap_loc(),
+            inode_bitmap: self.get_inode_bitmap_loc(),
+            inode_table: self.get_inode_table_loc(),
+            free_blocks: self.get_free_blocks_count(),
+            free_inodes: self.get_free_inodes_count(),
+            used_dirs: self.get_used_dirs_count(),
+            itable_unused: self.get_itable_unused(),
+            flags: self.get_flags(),
+        }
+    }
+
+    /// Read a descriptor of `desc_size` bytes, the 64bit fields are only present when `desc_size > 32`.
+    pub fn from_reader(mut reader: impl Read, desc_size: u16) -> Result<Self, ExtfsError> {
+        let desc_size = desc_size as usize;
+        let mut buf = vec![0; cmp::max(desc_size, DESC_SIZE_64BIT)];
+        reader.read_exact(&mut buf[..desc_size])?;
+
         let codec = bincode::options()
             .with_little_endian()
             .with_fixint_encoding()
             .allow_trailing_bytes();
-        let bgd: BlockGroupDescriptor = if is_64bit {
-            codec.deserialize_from(&mut reader)?
+        let bgd: BlockGroupDescriptor = if desc_size > DESC_SIZE_32BIT {
+            codec.deserialize(&buf)?
         } else {
-            let bgd32: BlockGroupDescriptor32 = codec.deserialize_from(&mut reader)?;
+            let bgd32: BlockGroupDescriptor32 = codec.deserialize(&buf)?;
             BlockGroupDescriptor {
                 descriptor32: bgd32,
                 ..Default::default()
@@ -88,8 +192,7 @@ mod tests {
     fn test_block_group_descriptor() {
         let mut file = File::open("testdata/test.ext4").unwrap();
         file.seek(std::io::SeekFrom::Start(1024 + 1024)).unwrap();
-        let is_64bit = true;
-        let first_bgd = BlockGroupDescriptor::from_reader(file, is_64bit).unwrap();
+        let first_bgd = BlockGroupDescriptor::from_reader(file, 64).unwrap();
         println!("1st block group descriptor {:?}", first_bgd);
         println!(
             "block_bitmap_loc={} inode_bitmap_loc={} inode_table_loc={}",
@@ -98,4 +201,18 @@ mod tests {
             first_bgd.get_inode_table_loc()
         );
     }
+
+    #[test]
+    fn test_block_group_descriptor_32() {
+        // 64bit feature with 32 bytes descriptors, the 2nd must not be misaligned.
+        let mut table = vec![0u8; 64];
+        table[8..12].copy_from_slice(&100u32.to_le_bytes());
+        table[32 + 8..32 + 12].copy_from_slice(&200u32.to_le_bytes());
+
+        let mut reader = table.as_slice();
+        let first = BlockGroupDescriptor::from_reader(&mut reader, 32).unwrap();
+        let second = BlockGroupDescriptor::from_reader(&mut reader, 32).unwrap();
+        assert_eq!(first.get_inode_table_loc(), 100);
+        assert_eq!(second.get_inode_table_loc(), 200);
+    }
 }
@@ -0,0 +1,234 @@
+// Hash of file names in htree directories, ported from fs/ext4/hash.c
+
+use super::{
+    constants::{
+        DX_HASH_HALF_MD4, DX_HASH_HALF_MD4_UNSIGNED, DX_HASH_LEGACY, DX_HASH_LEGACY_UNSIGNED,
+        DX_HASH_TEA, DX_HASH_TEA_UNSIGNED,
+    },
+    errors::ExtfsError,
+};
+
+/// Hash value marking the end of a 32bit htree directory.
+const HTREE_EOF_32BIT: u32 = 0x7FFF_FFFF;
+
+const TEA_DELTA: u32 = 0x9E37_79B9;
+
+const MD4_K2: u32 = 0o13240474631;
+const MD4_K3: u32 = 0o15666365641;
+
+fn tea_transform(buf: &mut [u32; 4], input: &[u32]) {
+    let mut sum = 0u32;
+    let (mut b0, mut b1) = (buf[0], buf[1]);
+    let (a, b, c, d) = (input[0], input[1], input[2], input[3]);
+
+    for _ in 0..16 {
+        sum = sum.wrapping_add(TEA_DELTA);
+        b0 = b0.wrapping_add(
+            (b1 << 4).wrapping_add(a) ^ b1.wrapping_add(sum) ^ (b1 >> 5).wrapping_add(b),
+        );
+        b1 = b1.wrapping_add(
+            (b0 << 4).wrapping_add(c) ^ b0.wrapping_add(sum) ^ (b0 >> 5).wrapping_add(d),
+        );
+    }
+
+    buf[0] = buf[0].wrapping_add(b0);
+    buf[1] = buf[1].wrapping_add(b1);
+}
+
+fn md4_f(x: u32, y: u32, z: u32) -> u32 {
+    z ^ (x & (y ^ z))
+}
+
+fn md4_g(x: u32, y: u32, z: u32) -> u32 {
+    (x & y).wrapping_add((x ^ y) & z)
+}
+
+fn md4_h(x: u32, y: u32, z: u32) -> u32 {
+    x ^ y ^ z
+}
+
+fn half_md4_transform(buf: &mut [u32; 4], input: &[u32]) {
+    let [mut a, mut b, mut c, mut d] = *buf;
+
+    macro_rules! round {
+        ($f:ident, $a:ident, $b:ident, $c:ident, $d:ident, $x:expr, $s:expr) => {
+            $a = $a
+                .wrapping_add($f($b, $c, $d))
+                .wrapping_add($x)
+                .rotate_left($s);
+        };
+    }
+
+    // Round 1
+    round!(md4_f, a, b, c, d, input[0], 3);
+    round!(md4_f, d, a, b, c, input[1], 7);
+    round!(md4_f, c, d, a, b, input[2], 11);
+    round!(md4_f, b, c, d, a, input[3], 19);
+    round!(md4_f, a, b, c, d, input[4], 3);
+    round!(md4_f, d, a, b, c, input[5], 7);
+    round!(md4_f, c, d, a, b, input[6], 11);
+    round!(md4_f, b, c, d, a, input[7], 19);
+
+    // Round 2
+    round!(md4_g, a, b, c, d, input[1].wrapping_add(MD4_K2), 3);
+    round!(md4_g, d, a, b, c, input[3].wrapping_add(MD4_K2), 5);
+    round!(md4_g, c, d, a, b, input[5].wrapping_add(MD4_K2), 9);
+    round!(md4_g, b, c, d, a, input[7].wrapping_add(MD4_K2), 13);
+    round!(md4_g, a, b, c, d, input[0].wrapping_add(MD4_K2), 3);
+    round!(md4_g, d, a, b, c, input[2].wrapping_add(MD4_K2), 5);
+    round!(md4_g, c, d, a, b, input[4].wrapping_add(MD4_K2), 9);
+    round!(md4_g, b, c, d, a, input[6].wrapping_add(MD4_K2), 13);
+
+    // Round 3
+    round!(md4_h, a, b, c, d, input[3].wrapping_add(MD4_K3), 3);
+    round!(md4_h, d, a, b, c, input[7].wrapping_add(MD4_K3), 9);
+    round!(md4_h, c, d, a, b, input[2].wrapping_add(MD4_K3), 11);
+    round!(md4_h, b, c, d, a, input[6].wrapping_add(MD4_K3), 15);
+    round!(md4_h, a, b, c, d, input[1].wrapping_add(MD4_K3), 3);
+    round!(md4_h, d, a, b, c, input[5].wrapping_add(MD4_K3), 9);
+    round!(md4_h, c, d, a, b, input[0].wrapping_add(MD4_K3), 11);
+    round!(md4_h, b, c, d, a, input[4].wrapping_add(MD4_K3), 15);
+
+    buf[0] = buf[0].wrapping_add(a);
+    buf[1] = buf[1].wrapping_add(b);
+    buf[2] = buf[2].wrapping_add(c);
+    buf[3] = buf[3].wrapping_add(d);
+}
+
+/// Convert a name byte to int, as `signed char` or `unsigned char` in C.
+fn char_value(b: u8, signed: bool) -> u32 {
+    if signed {
+        b as i8 as i32 as u32
+    } else {
+        b as u32
+    }
+}
+
+fn dx_hack_hash(name: &[u8], signed: bool) -> u32 {
+    let (mut hash0, mut hash1) = (0x12A3_FE2Du32, 0x37AB_E8F9u32);
+
+    for &b in name {
+        let mut hash = hash1.wrapping_add(hash0 ^ char_value(b, signed).wrapping_mul(7152373));
+        if hash & 0x8000_0000 != 0 {
+            hash = hash.wrapping_sub(0x7FFF_FFFF);
+        }
+        hash1 = hash0;
+        hash0 = hash;
+    }
+    hash0 << 1
+}
+
+/// Pack up to `num * 4` bytes of the name into `num` words, padded with the name length.
+fn str2hashbuf(msg: &[u8], num: usize, signed: bool) -> Vec<u32> {
+    let len = msg.len() as u32;
+    let mut pad = len | (len << 8);
+    pad |= pad << 16;
+
+    let mut buf = Vec::with_capacity(num);
+    let mut val = pad;
+    for (i, &b) in msg.iter().take(num * 4).enumerate() {
+        val = char_value(b, signed).wrapping_add(val << 8);
+        if i % 4 == 3 {
+            buf.push(val);
+            val = pad;
+        }
+    }
+    if buf.len() < num {
+        buf.push(val);
+    }
+    buf.resize(num, pad);
+    buf
+}
+
+/// Compute `(hash, minor_hash)` of a name in an htree directory.
+///
+/// `version` is one of the `DX_HASH_*` versions, the unsigned variants treat name bytes as
+/// `unsigned char`. An all-zero `seed` is replaced by the default MD4 seed, like the kernel does.
+pub fn name_hash(name: &[u8], version: u8, seed: [u32; 4]) -> Result<(u32, u32), ExtfsError> {
+    let mut buf = [0x6745_2301, 0xEFCD_AB89, 0x98BA_DCFE, 0x1032_5476];
+    if seed.iter().any(|&x| x != 0) {
+        buf = seed;
+    }
+
+    let (hash, minor_hash) = match version {
+        DX_HASH_LEGACY | DX_HASH_LEGACY_UNSIGNED => {
+            (dx_hack_hash(name, version == DX_HASH_LEGACY), 0)
+        }
+        DX_HASH_HALF_MD4 | DX_HASH_HALF_MD4_UNSIGNED => {
+            let signed = version == DX_HASH_HALF_MD4;
+            for chunk in name.chunks(32) {
+                half_md4_transform(&mut buf, &str2hashbuf(chunk, 8, signed));
+            }
+            (buf[1], buf[2])
+        }
+        DX_HASH_TEA | DX_HASH_TEA_UNSIGNED => {
+            let signed = version == DX_HASH_TEA;
+            for chunk in name.chunks(16) {
+                tea_transform(&mut buf, &str2hashbuf(chunk, 4, signed));
+            }
+            (buf[0], buf[1])
+        }
+        _ => return Err(ExtfsError::UnsupportedHashVersion(version)),
+    };
+
+    let mut hash = hash & !1;
+    if hash == HTREE_EOF_32BIT << 1 {
+        hash = (HTREE_EOF_32BIT - 1) << 1;
+    }
+    Ok((hash, minor_hash))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::name_hash;
+
+    // Test vectors from `debugfs -R "dx_hash -h <version> -s <seed> <name>"`
+    const SEED: [u32; 4] = [0x0003_b3ff, 0xe748_acb2, 0xf25d_2bb9, 0x31ce_c279];
+    const LONG_NAME: &[u8] = b"a_much_longer_file_name_that_spans_more_than_32_bytes.txt";
+    const CAFE: &[u8] = "café".as_bytes();
+
+    #[test]
+    fn test_legacy() {
+        assert_eq!(name_hash(b"hello.txt", 0, SEED).unwrap(), (0x65a05776, 0));
+        assert_eq!(name_hash(LONG_NAME, 0, SEED).unwrap(), (0x8eefe8c6, 0));
+        assert_eq!(name_hash(CAFE, 0, SEED).unwrap(), (0x96ca5a2c, 0));
+        assert_eq!(name_hash(CAFE, 3, SEED).unwrap(), (0x6dde4230, 0));
+    }
+
+    #[test]
+    fn test_half_md4() {
+        assert_eq!(
+            name_hash(b"hello.txt", 1, SEED).unwrap(),
+            (0x446f63e2, 0xfd487fcf)
+        );
+        assert_eq!(
+            name_hash(LONG_NAME, 1, SEED).unwrap(),
+            (0x81b93416, 0x46b35ab9)
+        );
+        assert_eq!(name_hash(CAFE, 1, SEED).unwrap(), (0x367aa246, 0xb7c5e2c8));
+        assert_eq!(name_hash(CAFE, 4, SEED).unwrap(), (0x1e573b34, 0x0c462611));
+        assert_eq!(
+            name_hash(b"hello.txt", 1, [0; 4]).unwrap(),
+            (0xa26e1d86, 0x133b3f98)
+        );
+    }
+
+    #[test]
+    fn test_tea() {
+        assert_eq!(
+            name_hash(b"hello.txt", 2, SEED).unwrap(),
+            (0x0ecc6ee4, 0x9d912c61)
+        );
+        assert_eq!(
+            name_hash(LONG_NAME, 2, SEED).unwrap(),
+            (0x0afc6ad4, 0xbb145a2b)
+        );
+        assert_eq!(name_hash(CAFE, 2, SEED).unwrap(), (0x915b8b70, 0xc2953872));
+        assert_eq!(name_hash(CAFE, 5, SEED).unwrap(), (0xe0b6ef8c, 0xfd09d2d5));
+    }
+
+    #[test]
+    fn test_unsupported() {
+        assert!(name_hash(b"hello.txt", 6, SEED).is_err());
+    }
+}
@@ -2,14 +2,17 @@ use std::io::{Error, ErrorKind, Read};
 
 use byteorder::{LittleEndian, ReadBytesExt};
 
-use super::constants::{DOTDOT_DIR_NAME, DOT_DIR_NAME};
+use super::{
+    constants::{DOTDOT_DIR_NAME, DOT_DIR_NAME},
+    file_type::FileType,
+};
 
 const EXT4_NAME_LEN: usize = 255;
 
 /// Linear (Classic) Directories (old style)
 ///
 /// https://www.kernel.org/doc/html/latest/filesystems/ext4/dynamic.html#directory-entries
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct DirEntry {
     /// Number of the inode that this directory entry points to.
     inode: u32,
@@ -32,7 +35,7 @@ impl DirEntry {
 /// https://www.kernel.org/doc/html/latest/filesystems/ext4/dynamic.html#directory-entries
 ///
 /// Compared to the `DirEntry`, the new directory entry format shortens the name_len field and uses the space for a file type flag
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct DirEntry2 {
     /// Number of the inode that this directory entry points to.
     inode: u32,
@@ -60,7 +63,7 @@ impl DirEntry2 {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct DirEntryTail {
     /// Inode number, which must be zero.
     reserved_zero1: u32,
@@ -74,7 +77,7 @@ pub struct DirEntryTail {
     checksum: u32,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum DirEntryEnum {
     DirEntry(DirEntry),
     DirEntry2(DirEntry2),
@@ -98,6 +101,37 @@ impl DirEntryEnum {
         }
     }
 
+    /// Build a `.` or `..` entry for a directory that doesn't store it, like an inline one.
+    pub(crate) fn dot_entry(ino: u32, name: &[u8], feature_incompat_filetype: bool) -> Self {
+        // the size of an entry with a name of up to 4 bytes
+        let rec_len = 12;
+        if feature_incompat_filetype {
+            DirEntryEnum::DirEntry2(DirEntry2 {
+                inode: ino,
+                rec_len,
+                name_len: name.len() as u8,
+                // directory
+                file_type: 0x2,
+                name: name.to_vec(),
+            })
+        } else {
+            DirEntryEnum::DirEntry(DirEntry {
+                inode: ino,
+                rec_len,
+                name_len: name.len() as u16,
+                name: name.to_vec(),
+            })
+        }
+    }
+
+    /// Get the file type, only available when the filetype feature is enabled.
+    pub fn get_file_type(&self) -> Option<FileType> {
+        match self {
+            DirEntryEnum::DirEntry2(e) => FileType::from_dir_entry_code(e.file_type),
+            _ => None,
+        }
+    }
+
     pub fn get_name_str(&self) -> String {
         let name = match self {
             DirEntryEnum::DirEntry(e) => e.name.clone(),
@@ -125,6 +159,36 @@ impl DirEntryEnum {
         }
     }
 
+    /// Check whether the entry is a deleted or empty slot.
+    pub fn is_unused(&self) -> bool {
+        self.get_ino() == Some(0)
+    }
+
+    /// Read the entry at `offset` of a directory block.
+    ///
+    /// Entries never cross a block boundary, one whose `rec_len` runs past the end of the block
+    /// is corrupt and fails with `InvalidData`, so the damage stays within the block.
+    pub fn from_block(
+        block: &[u8],
+        offset: usize,
+        feature_incompat_filetype: bool,
+    ) -> Result<Self, std::io::Error> {
+        let rest = block.get(offset..).unwrap_or_default();
+        if let Some(&[lo, hi]) = rest.get(4..6) {
+            let rec_len = u16::from_le_bytes([lo, hi]);
+            if rec_len as usize > rest.len() {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!(
+                        "Invalid dir entry: rec_len={} crosses the block boundary",
+                        rec_len
+                    ),
+                ));
+            }
+        }
+        Self::from_reader(rest, feature_incompat_filetype)
+    }
+
     /// Read `DirEntry` | `DirEntry2` | `DirEntryTail` from a reader.
     pub fn from_reader(
         mut reader: impl Read,
@@ -132,37 +196,34 @@ impl DirEntryEnum {
     ) -> Result<Self, std::io::Error> {
         let inode = reader.read_u32::<LittleEndian>()?;
         let rec_len = reader.read_u16::<LittleEndian>()?;
+        let name_len_lo = reader.read_u8()?;
+        let name_len_hi_or_file_type = reader.read_u8()?;
 
-        // Treat as DirEntryTail
-        if inode == 0 && rec_len == 12 {
-            let reserved_zero2 = reader.read_u8()?;
-            let reserved_ft = reader.read_u8()?;
-
-            if reserved_zero2 != 0 || reserved_ft != 0xDE {
-                return Err(Error::new(
-                    ErrorKind::Other,
-                    format!(
-                        "Invalid dir entry tail: reserved_zero2={} reserved_ft={}",
-                        reserved_zero2, reserved_ft
-                    ),
-                ));
-            }
+        // a zero-filled block would never advance past such an entry
+        if rec_len < 8 {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("Invalid dir entry: rec_len={}", rec_len),
+            ));
+        }
 
+        // Treat as DirEntryTail, an unused entry of the same size is just padding.
+        if inode == 0 && rec_len == 12 && name_len_lo == 0 && name_len_hi_or_file_type == 0xDE {
             let checksum = reader.read_u32::<LittleEndian>()?;
 
             return Ok(Self::DirEntryTail(DirEntryTail {
                 reserved_zero1: inode,
                 rec_len,
-                reserved_zero2,
-                reserved_ft,
+                reserved_zero2: name_len_lo,
+                reserved_ft: name_len_hi_or_file_type,
                 checksum,
             }));
         }
 
         // Treat as DirEntry2
         let (entry, name_len) = if feature_incompat_filetype {
-            let name_len = reader.read_u8()?;
-            let file_type = reader.read_u8()?;
+            let name_len = name_len_lo;
+            let file_type = name_len_hi_or_file_type;
 
             let mut name = vec![0; name_len as usize];
             reader.read_exact(&mut name)?;
@@ -178,7 +239,7 @@ impl DirEntryEnum {
             )
         // Treat as DirEntry
         } else {
-            let name_len = reader.read_u16::<LittleEndian>()?;
+            let name_len = u16::from_le_bytes([name_len_lo, name_len_hi_or_file_type]);
 
             let mut name = vec![0; name_len as usize];
             reader.read_exact(&mut name)?;
@@ -194,8 +255,16 @@ impl DirEntryEnum {
         };
 
         // Discard the aligned bytes.
-        let real_len = name_len as u16 + 8;
-        let align = rec_len - real_len;
+        let real_len = name_len + 8;
+        let align = rec_len.checked_sub(real_len).ok_or_else(|| {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "Invalid dir entry: rec_len={} name_len={}",
+                    rec_len, name_len
+                ),
+            )
+        })?;
         let mut discard = vec![0; align as usize];
         reader.read_exact(&mut discard)?;
 
@@ -203,7 +272,47 @@ impl DirEntryEnum {
     }
 }
 
+/// Name, inode number and type of a directory entry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirEntryInfo {
+    pub name: String,
+    pub ino: u64,
+    /// `None` if the filetype feature is disabled or the type is unknown.
+    pub file_type: Option<FileType>,
+}
+
+impl DirEntryInfo {
+    /// Build from a `DirEntry` or `DirEntry2`, `None` for a `DirEntryTail`.
+    pub fn from_entry(entry: &DirEntryEnum) -> Option<Self> {
+        Some(Self {
+            name: entry.get_name_str(),
+            ino: entry.get_ino()? as u64,
+            file_type: entry.get_file_type(),
+        })
+    }
+}
+
 /// Hash Tree Directories
 ///
 /// The root of Hash Tree
 pub struct DxRoot {}
+
+#[cfg(test)]
+mod tests {
+    use super::DirEntryEnum;
+
+    #[test]
+    fn test_dir_entry_tail() {
+        let mut b = vec![0, 0, 0, 0, 12, 0, 0, 0xDE];
+        b.extend(0x1234_5678u32.to_le_bytes());
+        let e = DirEntryEnum::from_reader(b.as_slice(), true).unwrap();
+        assert!(matches!(e, DirEntryEnum::DirEntryTail(_)));
+
+        // A freed slot that happens to be 12 bytes long isn't a tail.
+        let b = vec![0, 0, 0, 0, 12, 0, 3, 1, b'a', b'b', b'c', 0];
+        let e = DirEntryEnum::from_reader(b.as_slice(), true).unwrap();
+        assert!(matches!(e, DirEntryEnum::DirEntry2(_)));
+        assert_eq!(e.get_ino(), Some(0));
+        assert_eq!(e.get_rec_len(), 12);
+    }
+}
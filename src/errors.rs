@@ -2,7 +2,7 @@ use std::{io, path::PathBuf};
 
 use thiserror::Error;
 
-use super::entry::DirEntryEnum;
+use super::{entry::DirEntryEnum, file_type::FileType};
 
 #[derive(Error, Debug)]
 pub enum ExtfsError {
@@ -12,24 +12,101 @@ pub enum ExtfsError {
     #[error(transparent)]
     Io(#[from] io::Error),
 
+    #[error("IO error while reading {context} at offset {offset}: {source}")]
+    IoAt {
+        source: io::Error,
+        offset: u64,
+        context: &'static str,
+    },
+
+    #[error("Truncated image: failed to read {reading} at offset {offset}")]
+    TruncatedImage { reading: &'static str, offset: u64 },
+
+    #[error("Arithmetic overflow while computing {0}")]
+    ArithmeticOverflow(&'static str),
+
+    #[error("{0} bytes can't be held in memory on this platform")]
+    FileTooLargeForPlatform(u64),
+
+    #[error("Corrupt directory entry at offset {offset}: {reason}")]
+    CorruptDirEntry { offset: u64, reason: String },
+
     #[error("Invalid super block magic: {0}")]
     InvalidSuperBlockMagic(u16),
 
+    #[error("The file system has no journal inode")]
+    NoJournalInode,
+
+    #[error("Block {block} of inode {ino} isn't mapped by its extents")]
+    UnmappedBlock { ino: u64, block: u64 },
+
+    #[error("Invalid journal magic: {0:#x}")]
+    InvalidJournalMagic(u32),
+
     #[error("Block group count mismatch: from_blocks={blocks} from_inodes={inodes}")]
     BlockGroupCountMismatch { blocks: u64, inodes: u64 },
 
     #[error("Block group descriptor {0} not found")]
     BlockGroupDescriptorNotFound(u64),
 
+    #[error("Block group descriptor {0} is zeroed, the location of its inode table is lost")]
+    ZeroedBlockGroupDescriptor(u64),
+
+    #[error("Inode table of group {group} at block {block} lies outside the file system")]
+    InodeTableOutOfBounds { group: u64, block: u64 },
+
     #[error("Invalid extent header magic: {0}")]
     InvalidExtentHeaderMagic(u16),
 
+    #[error("Extents overlap at logical block {0}")]
+    OverlappingExtents(u64),
+
+    #[error("Extent tree depth {0} exceeds the maximum of 5")]
+    ExtentTreeTooDeep(u16),
+
+    #[error("Extent tree block {block} has depth {found}, expected {expected}")]
+    InvalidExtentDepth {
+        block: u64,
+        expected: u16,
+        found: u16,
+    },
+
+    #[error("Unsupported checksum type: {0}")]
+    UnsupportedChecksumType(u8),
+
+    #[error("Super block checksum mismatch")]
+    SuperBlockChecksumMismatch,
+
+    #[error("Block group descriptor {group} checksum mismatch")]
+    DescriptorChecksumMismatch { group: u64 },
+
+    #[error("Inode {0} checksum mismatch")]
+    InodeChecksumMismatch(u64),
+
+    #[error("Extent tree block {0} checksum mismatch")]
+    ExtentBlockChecksumMismatch(u64),
+
+    #[error("Directory block {block} of inode {ino} checksum mismatch")]
+    DirBlockChecksumMismatch { ino: u64, block: u64 },
+
+    #[error("Unsupported feature: {name} ({bit:#x})")]
+    UnsupportedFeature { name: &'static str, bit: u32 },
+
+    #[error("Unsupported directory hash version: {0}")]
+    UnsupportedHashVersion(u8),
+
+    #[error("Invalid htree index of directory {ino}: {reason}")]
+    InvalidHtree { ino: u64, reason: &'static str },
+
     #[error("Require absolute path, got {0}")]
     RequireAbsolutePath(PathBuf),
 
     #[error("Unexpected parent dir in the path: {0}")]
     UnexpectedParentDir(PathBuf),
 
+    #[error("Too many levels of symbolic links: {0}")]
+    TooManySymlinks(PathBuf),
+
     #[error("Invalid path: {0}")]
     InvalidPath(PathBuf),
 
@@ -39,12 +116,18 @@ pub enum ExtfsError {
     #[error("{0} is not directory")]
     IsNotDirecotry(PathBuf),
 
+    #[error("Inode {0} is not directory")]
+    InodeIsNotDirectory(u64),
+
     #[error("{0} is not symlink")]
     IsNotSymlink(PathBuf),
 
     #[error("{0} is not regular file")]
     IsNotRegular(PathBuf),
 
+    #[error("Inode of type {0:?} is not regular file")]
+    InodeIsNotRegular(FileType),
+
     #[error("Unexpected dir entry: {0:?}")]
     UnexpectedDirEntry(DirEntryEnum),
 
@@ -1,10 +1,16 @@
-use std::io::{Read, Seek};
+use std::{
+    cmp,
+    io::{Read, Seek},
+};
 
 use serde::Deserialize;
 
 use super::{
-    codec::Decoder, constants::EXTENT_HEADER_MAGIC, entry::DirEntryEnum, errors::ExtfsError,
-    utils::compute_u64,
+    codec::Decoder,
+    constants::{EXTENT_HEADER_MAGIC, EXTENT_INIT_MAX_LEN},
+    entry::DirEntryEnum,
+    errors::ExtfsError,
+    utils::{block_pos, compute_u64, read_exact_at},
 };
 
 /// The extent tree header
@@ -13,7 +19,7 @@ use super::{
 pub struct ExtentHeader {
     magic: u16,
     pub(crate) entries: u16,
-    max: u16,
+    pub(crate) max: u16,
     pub(crate) depth: u16,
     generation: u32,
 }
@@ -48,12 +54,12 @@ impl ExtentIdx {
 /// Leaf nodes of the extent tree
 ///
 /// https://www.kernel.org/doc/html/latest/filesystems/ext4/dynamic.html#extent-tree
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Clone)]
 pub struct Extent {
     /// First file block number that this extent covers.
     block: u32,
-    /// Number of blocks covered by extent.
-    pub(crate) len: u16,
+    /// Number of blocks covered by extent, above 32768 the extent is uninitialized.
+    len: u16,
     /// Upper 16-bits of the block number to which this extent points.
     start_hi: u16,
     /// Lower 32-bits of the block number to which this extent points.
@@ -61,30 +67,92 @@ pub struct Extent {
 }
 
 impl Extent {
-    // Get location of blocks referenced by the extent.
+    /// Build an extent of `len` blocks from logical `block` to physical `start`, `len` has the
+    /// uninitialized bit like on disk.
+    #[cfg(test)]
+    pub(crate) fn new(block: u32, len: u16, start: u64) -> Self {
+        Self {
+            block,
+            len,
+            start_hi: (start >> 32) as u16,
+            start_lo: start as u32,
+        }
+    }
+
+    /// Get location of blocks referenced by the extent.
     pub fn get_block_loc(&self) -> u64 {
         compute_u64(self.start_lo, self.start_hi as u32)
     }
 
+    /// Get the first file block number that this extent covers.
+    pub fn get_logical_block(&self) -> u64 {
+        self.block as u64
+    }
+
+    /// Get number of blocks covered by the extent.
+    pub fn get_len(&self) -> u16 {
+        if self.len > EXTENT_INIT_MAX_LEN {
+            self.len - EXTENT_INIT_MAX_LEN
+        } else {
+            self.len
+        }
+    }
+
+    /// Check whether the extent is preallocated but not written yet, it reads as zeros.
+    pub fn is_uninit(&self) -> bool {
+        self.len > EXTENT_INIT_MAX_LEN
+    }
+
     // Read raw bytes from the extent.
     pub fn read_bytes(
         &self,
         block_size: u64,
-        mut reader: impl Read + Seek,
+        reader: impl Read + Seek,
         start: u64,
         max: u64,
     ) -> Result<Vec<u8>, std::io::Error> {
-        let pos = self.get_block_loc() * block_size;
-        let mut size = self.len as u64 * block_size;
-        if max < size {
-            size = max;
+        let size = (self.get_len() as u64 * block_size).saturating_sub(start);
+        let len = cmp::min(size, max);
+        let len = usize::try_from(len).map_err(|_| {
+            std::io::Error::new(
+                std::io::ErrorKind::OutOfMemory,
+                ExtfsError::FileTooLargeForPlatform(len),
+            )
+        })?;
+        let mut buf = vec![0; len];
+        self.read_into(block_size, reader, start, &mut buf)?;
+
+        Ok(buf)
+    }
+
+    /// Read bytes from the extent directly into `buf`, starting at byte `start` of the extent.
+    ///
+    /// Returns the number of bytes read, which is only less than `buf.len()` at the end of the extent.
+    pub fn read_into(
+        &self,
+        block_size: u64,
+        mut reader: impl Read + Seek,
+        start: u64,
+        buf: &mut [u8],
+    ) -> Result<usize, std::io::Error> {
+        let size = self.get_len() as u64 * block_size;
+        if start >= size {
+            return Ok(0);
         }
-        reader.seek(std::io::SeekFrom::Start(pos + start))?;
+        let n = cmp::min(buf.len() as u64, size - start) as usize;
 
-        let mut buf = vec![0; size as usize];
-        reader.read_exact(&mut buf)?;
+        let into_io = |e| match e {
+            ExtfsError::Io(e) => e,
+            ExtfsError::IoAt { ref source, .. } => std::io::Error::new(source.kind(), e),
+            e @ ExtfsError::TruncatedImage { .. } => {
+                std::io::Error::new(std::io::ErrorKind::UnexpectedEof, e)
+            }
+            e => std::io::Error::new(std::io::ErrorKind::InvalidData, e),
+        };
+        let offset = block_pos(self.get_block_loc(), block_size, start).map_err(into_io)?;
+        read_exact_at(&mut reader, offset, &mut buf[..n], "file data").map_err(into_io)?;
 
-        Ok(buf)
+        Ok(n)
     }
 
     /// Read `DirEntryEnum` list from the extent.
@@ -94,75 +162,236 @@ impl Extent {
         feature_incompat_filetype: bool,
         mut reader: impl Read + Seek,
     ) -> Result<Vec<DirEntryEnum>, ExtfsError> {
-        let pos = self.get_block_loc() * block_size;
-        let size = self.len as u64 * block_size;
-
-        reader.seek(std::io::SeekFrom::Start(pos))?;
-        let mut limit_reader = reader.take(size);
-
         let mut entries = Vec::new();
-        loop {
-            match DirEntryEnum::from_reader(&mut limit_reader, feature_incompat_filetype) {
-                Ok(DirEntryEnum::DirEntryTail(_)) => {
-                    return Ok(entries);
-                }
-                Ok(e) => {
-                    // ignore dot and dotdot
-                    if e.is_dot() || e.is_dotdot() {
-                        continue;
-                    }
-                    entries.push(e);
-                }
-                Err(e) => {
-                    if e.kind() == std::io::ErrorKind::UnexpectedEof {
-                        break;
-                    }
-                    return Err(ExtfsError::Io(e));
-                }
-            }
+        let mut offset = 0;
+        // each block is read once for all its entries
+        let mut cache = None;
+        while let Some((e, next)) = self.read_entry_cached(
+            block_size,
+            feature_incompat_filetype,
+            &mut reader,
+            offset,
+            &mut cache,
+        )? {
+            entries.push(e);
+            offset = next;
         }
-
         Ok(entries)
     }
 
     /// Read a `DirEntryEnum` from the extent
+    ///
+    /// Entries are read within their block, the rest of a block too short for an entry is
+    /// skipped. `.` and `..` are returned like any entry, `ReadDir` skips them by default.
     pub fn read_entry(
+        &self,
+        block_size: u64,
+        feature_incompat_filetype: bool,
+        reader: impl Read + Seek,
+        offset: u64,
+    ) -> Result<Option<(DirEntryEnum, u64)>, ExtfsError> {
+        self.read_entry_cached(
+            block_size,
+            feature_incompat_filetype,
+            reader,
+            offset,
+            &mut None,
+        )
+    }
+
+    /// Like `read_entry`, reusing the block in `cache` if it's the one at `offset`, along with
+    /// its start in the extent.
+    fn read_entry_cached(
         &self,
         block_size: u64,
         feature_incompat_filetype: bool,
         mut reader: impl Read + Seek,
         mut offset: u64,
+        cache: &mut Option<(u64, Vec<u8>)>,
     ) -> Result<Option<(DirEntryEnum, u64)>, ExtfsError> {
-        let pos = self.get_block_loc() * block_size;
-        let size = self.len as u64 * block_size;
+        let pos = block_pos(self.get_block_loc(), block_size, 0)?;
+        let size = self.get_len() as u64 * block_size;
 
-        if offset >= size {
-            return Ok(None);
-        }
-
-        reader.seek(std::io::SeekFrom::Start(pos + offset))?;
-        let mut limit_reader = reader.take(size - offset);
-        loop {
-            match DirEntryEnum::from_reader(&mut limit_reader, feature_incompat_filetype) {
-                Ok(DirEntryEnum::DirEntryTail(_)) => {
-                    return Ok(None);
+        while offset < size {
+            let block_start = offset - offset % block_size;
+            let block = match cache {
+                Some((start, block)) if *start == block_start => block,
+                _ => {
+                    let mut block = vec![0; block_size as usize];
+                    read_exact_at(
+                        &mut reader,
+                        pos + block_start,
+                        &mut block,
+                        "directory block",
+                    )?;
+                    &mut cache.insert((block_start, block)).1
                 }
-                Ok(e) => {
-                    offset += e.get_rec_len() as u64;
-                    // ignore dot and dotdot
-                    if e.is_dot() || e.is_dotdot() {
-                        continue;
+            };
+
+            let mut in_block = (offset - block_start) as usize;
+            while in_block < block.len() {
+                match DirEntryEnum::from_block(block, in_block, feature_incompat_filetype) {
+                    Ok(e) => {
+                        in_block += e.get_rec_len() as usize;
+                        offset = block_start + in_block as u64;
+                        // ignore the tail and deleted entries
+                        if matches!(e, DirEntryEnum::DirEntryTail(_)) || e.is_unused() {
+                            continue;
+                        }
+                        return Ok(Some((e, offset)));
                     }
-                    return Ok(Some((e, offset)));
+                    Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+                    Err(e) => return Err(dir_entry_error(e, pos + block_start + in_block as u64)),
                 }
-                Err(e) => {
-                    if e.kind() == std::io::ErrorKind::UnexpectedEof {
-                        return Ok(None);
-                    }
-                    return Err(ExtfsError::Io(e));
+            }
+            offset = block_start + block_size;
+        }
+        Ok(None)
+    }
+}
+
+/// Convert an error of `DirEntryEnum::from_reader` for the entry at `offset`.
+pub(crate) fn dir_entry_error(e: std::io::Error, offset: u64) -> ExtfsError {
+    if e.kind() == std::io::ErrorKind::InvalidData {
+        return ExtfsError::CorruptDirEntry {
+            offset,
+            reason: e.to_string(),
+        };
+    }
+    ExtfsError::IoAt {
+        source: e,
+        offset,
+        context: "directory entry",
+    }
+}
+
+/// A contiguous run of a file, see `FileSystem::data_location`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DataRun {
+    /// Byte offset in the file.
+    pub logical: u64,
+    /// Byte offset in the image, `None` for a hole or an unwritten extent, which read as zeros.
+    pub physical: Option<u64>,
+    /// Length in bytes.
+    pub len: u64,
+}
+
+/// Describe the first `size` bytes of a file covered by `extents` as data runs and holes.
+pub(crate) fn data_runs(
+    extents: &[Extent],
+    block_size: u64,
+    size: u64,
+) -> Result<Vec<DataRun>, ExtfsError> {
+    let mut extents: Vec<&Extent> = extents.iter().collect();
+    extents.sort_by_key(|e| e.block);
+
+    let mut runs = Vec::new();
+    let mut logical = 0;
+    for e in extents {
+        let start = block_pos(e.get_logical_block(), block_size, 0)?;
+        if start >= size {
+            break;
+        }
+        // a byte can't live at two places
+        if start < logical {
+            return Err(ExtfsError::OverlappingExtents(e.get_logical_block()));
+        }
+        if start > logical {
+            runs.push(DataRun {
+                logical,
+                physical: None,
+                len: start - logical,
+            });
+        }
+        let len = cmp::min(e.get_len() as u64 * block_size, size - start);
+        let physical = if e.is_uninit() {
+            None
+        } else {
+            Some(block_pos(e.get_block_loc(), block_size, 0)?)
+        };
+        runs.push(DataRun {
+            logical: start,
+            physical,
+            len,
+        });
+        logical = start + len;
+    }
+    if size > logical {
+        runs.push(DataRun {
+            logical,
+            physical: None,
+            len: size - logical,
+        });
+    }
+    Ok(runs)
+}
+
+/// Map a logical block of a file to its physical block, `None` for a hole or an unwritten
+/// extent.
+pub(crate) fn physical_block(extents: &[Extent], logical: u64) -> Option<u64> {
+    extents
+        .iter()
+        .find(|e| {
+            let start = e.get_logical_block();
+            (start..start + e.get_len() as u64).contains(&logical)
+        })
+        .filter(|e| !e.is_uninit())
+        .map(|e| e.get_block_loc() + (logical - e.get_logical_block()))
+}
+
+/// List the `(logical, physical)` block pairs mapped by `extents`, sorted by logical block.
+///
+/// Unwritten extents aren't listed, like holes.
+pub(crate) fn block_map(extents: &[Extent]) -> Vec<(u64, u64)> {
+    let mut map: Vec<(u64, u64)> = extents
+        .iter()
+        .filter(|e| !e.is_uninit())
+        .flat_map(|e| {
+            let (logical, physical) = (e.get_logical_block(), e.get_block_loc());
+            (0..e.get_len() as u64).map(move |i| (logical + i, physical + i))
+        })
+        .collect();
+    map.sort_unstable();
+    map
+}
+
+/// Fragmentation summary of a file, see `FileSystem::fragmentation`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FragStats {
+    /// Number of extents mapping the file.
+    pub extents: usize,
+    /// Number of places where an extent doesn't start right after the previous one on disk.
+    pub gaps: usize,
+    /// Length in blocks of the largest physically contiguous run.
+    pub largest_run: u64,
+}
+
+impl FragStats {
+    /// Summarize extents in file order, like `filefrag` does.
+    pub(crate) fn from_extents(extents: &[Extent]) -> Self {
+        let mut extents: Vec<&Extent> = extents.iter().collect();
+        extents.sort_by_key(|e| e.block);
+
+        let mut stats = Self {
+            extents: extents.len(),
+            ..Self::default()
+        };
+        let mut run = 0;
+        let mut next_block = None;
+        for e in extents {
+            let len = e.get_len() as u64;
+            if next_block == Some(e.get_block_loc()) {
+                run += len;
+            } else {
+                if next_block.is_some() {
+                    stats.gaps += 1;
                 }
+                run = len;
             }
+            stats.largest_run = stats.largest_run.max(run);
+            next_block = Some(e.get_block_loc() + len);
         }
+        stats
     }
 }
 
@@ -175,3 +404,197 @@ pub enum ExtentOrIdx {
     Extent(Extent),
     Idx(ExtentIdx),
 }
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Read, Seek, SeekFrom};
+
+    use super::{block_map, data_runs, physical_block, Extent, FragStats};
+    use crate::{errors::ExtfsError, read_dir::ReadDir, testutil::dir_entry};
+
+    #[test]
+    fn test_read_entries_skip_unused() {
+        let block_size = 1024;
+        let mut block = Vec::new();
+        block.extend(dir_entry(2, 12, b"."));
+        block.extend(dir_entry(2, 12, b".."));
+        block.extend(dir_entry(12, 12, b"a"));
+        block.extend(dir_entry(0, 12, b"b"));
+        block.extend(dir_entry(13, 1024 - 48, b"c"));
+
+        let extent = Extent::new(0, 1, 0);
+
+        let entries = extent
+            .read_entries(block_size, true, Cursor::new(&block))
+            .unwrap();
+        let names: Vec<_> = entries.iter().map(|e| e.get_name_str()).collect();
+        assert_eq!(names, [".", "..", "a", "c"]);
+
+        // the block is read once for all its entries
+        struct CountReads(Cursor<Vec<u8>>, usize);
+        impl Read for CountReads {
+            fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+                self.1 += 1;
+                self.0.read(buf)
+            }
+        }
+        impl Seek for CountReads {
+            fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+                self.0.seek(pos)
+            }
+        }
+        let mut reader = CountReads(Cursor::new(block.clone()), 0);
+        assert_eq!(
+            extent
+                .read_entries(block_size, true, &mut reader)
+                .unwrap()
+                .len(),
+            4
+        );
+        assert_eq!(reader.1, 1);
+
+        let mut names = Vec::new();
+        let mut offset = 0;
+        while let Some((e, next)) = extent
+            .read_entry(block_size, true, Cursor::new(&block), offset)
+            .unwrap()
+        {
+            names.push(e.get_name_str());
+            offset = next;
+        }
+        assert_eq!(names, [".", "..", "a", "c"]);
+
+        // ReadDir leaves the dots out unless asked for
+        let names = |rd: ReadDir<_>| -> Vec<_> { rd.map(|e| e.unwrap().get_name_str()).collect() };
+        let rd = ReadDir::new(Cursor::new(&block), vec![extent.clone()], block_size, true);
+        assert_eq!(names(rd), ["a", "c"]);
+        let rd = ReadDir::new(Cursor::new(&block), vec![extent], block_size, true).with_dots(true);
+        assert_eq!(names(rd), [".", "..", "a", "c"]);
+    }
+
+    #[test]
+    fn test_zero_rec_len() {
+        let block_size = 1024;
+        // a zero-filled block followed by a valid one
+        let mut blocks = vec![0; 1024];
+        blocks.extend(dir_entry(12, 12, b"a"));
+        blocks.extend(dir_entry(13, 1024 - 12, b"b"));
+
+        let extent = Extent::new(0, 2, 0);
+
+        let err = extent
+            .read_entries(block_size, true, Cursor::new(&blocks))
+            .err()
+            .unwrap();
+        assert!(matches!(err, ExtfsError::CorruptDirEntry { offset: 0, .. }));
+        let err = extent
+            .read_entry(block_size, true, Cursor::new(&blocks), 0)
+            .err()
+            .unwrap();
+        assert!(matches!(err, ExtfsError::CorruptDirEntry { offset: 0, .. }));
+
+        // ReadDir reports the corrupt block once and goes on with the next one
+        let rd = ReadDir::new(Cursor::new(&blocks), vec![extent], block_size, true);
+        let results: Vec<_> = rd.collect();
+        assert_eq!(results.len(), 3);
+        assert!(matches!(
+            results[0],
+            Err(ExtfsError::CorruptDirEntry { .. })
+        ));
+        let names: Vec<_> = results[1..]
+            .iter()
+            .map(|e| e.as_ref().unwrap().get_name_str())
+            .collect();
+        assert_eq!(names, ["a", "b"]);
+    }
+
+    #[test]
+    fn test_rec_len_crossing_block() {
+        let block_size = 1024;
+        // "a" claims to run into the second block
+        let mut blocks = Vec::new();
+        blocks.extend(dir_entry(12, 12, b"a"));
+        blocks.extend(dir_entry(13, 12, b"b"));
+        blocks[16..18].copy_from_slice(&1024u16.to_le_bytes());
+        blocks.resize(1024, 0);
+        blocks.extend(dir_entry(14, 1024, b"c"));
+
+        let extent = Extent::new(0, 2, 0);
+        let err = extent
+            .read_entries(block_size, true, Cursor::new(&blocks))
+            .err()
+            .unwrap();
+        assert!(matches!(
+            err,
+            ExtfsError::CorruptDirEntry { offset: 12, .. }
+        ));
+
+        // the damage stays within the first block
+        let rd = ReadDir::new(Cursor::new(&blocks), vec![extent], block_size, true);
+        let results: Vec<_> = rd.collect();
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0].as_ref().unwrap().get_name_str(), "a");
+        assert!(matches!(
+            results[1],
+            Err(ExtfsError::CorruptDirEntry { offset: 12, .. })
+        ));
+        assert_eq!(results[2].as_ref().unwrap().get_name_str(), "c");
+    }
+
+    #[test]
+    fn test_frag_stats() {
+        // the second extent continues the first one on disk, out of order in the tree
+        let extents = [
+            Extent::new(5, 2, 200),
+            Extent::new(0, 3, 100),
+            Extent::new(3, 2, 103),
+            // uninitialized, 4 blocks
+            Extent::new(7, 32768 + 4, 202),
+        ];
+        assert_eq!(
+            FragStats::from_extents(&extents),
+            FragStats {
+                extents: 4,
+                gaps: 1,
+                largest_run: 6,
+            }
+        );
+        assert_eq!(FragStats::from_extents(&[]), FragStats::default());
+    }
+
+    #[test]
+    fn test_physical_block() {
+        let extents = [
+            Extent::new(4, 2, 500),
+            Extent::new(0, 3, 100),
+            // uninitialized
+            Extent::new(6, 32768 + 1, 700),
+        ];
+        assert_eq!(physical_block(&extents, 0), Some(100));
+        assert_eq!(physical_block(&extents, 2), Some(102));
+        assert_eq!(physical_block(&extents, 5), Some(501));
+        // a hole
+        assert_eq!(physical_block(&extents, 3), None);
+        assert_eq!(physical_block(&extents, 6), None);
+        assert_eq!(physical_block(&extents, 7), None);
+
+        assert_eq!(
+            block_map(&extents),
+            [(0, 100), (1, 101), (2, 102), (4, 500), (5, 501)]
+        );
+    }
+
+    #[test]
+    fn test_data_runs_overlap() {
+        let extents = [Extent::new(0, 2, 100), Extent::new(3, 1, 200)];
+        let runs = data_runs(&extents, 1024, 4096).unwrap();
+        assert_eq!(runs.len(), 3);
+        assert_eq!(runs[1].physical, None);
+
+        let extents = [Extent::new(0, 2, 100), Extent::new(1, 2, 200)];
+        assert!(matches!(
+            data_runs(&extents, 1024, 4096),
+            Err(ExtfsError::OverlappingExtents(1))
+        ));
+    }
+}
@@ -0,0 +1,312 @@
+// Unpack a whole file system to a host directory.
+
+use std::{
+    collections::{HashMap, HashSet},
+    ffi::CString,
+    fs,
+    io::{self, Read, Seek},
+    os::unix::{
+        ffi::OsStrExt,
+        fs::{fchown, lchown, symlink, PermissionsExt},
+    },
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use super::{
+    errors::ExtfsError, fs::FileSystem, inode::Inode, metadata::Metadata, utils::path_from_bytes,
+};
+
+/// Ownership can only be changed by root, ignore the failure like `tar` does for other users.
+fn ignore_permission_denied(result: io::Result<()>) -> io::Result<()> {
+    match result {
+        Err(e) if e.kind() == io::ErrorKind::PermissionDenied => Ok(()),
+        x => x,
+    }
+}
+
+/// Set the modification time, owner and mode of an extracted file or directory through an open
+/// handle, the mode comes last as a restrictive one wouldn't let it be opened again.
+fn apply_metadata(file: &fs::File, inode: &Inode) -> io::Result<()> {
+    let metadata = Metadata::from(inode);
+    file.set_modified(metadata.modified()?)?;
+    ignore_permission_denied(fchown(file, Some(metadata.uid32()), Some(metadata.gid32())))?;
+    // setuid, setgid and sticky bits too
+    let mode = inode.mode as u32 & 0o7777;
+    file.set_permissions(fs::Permissions::from_mode(mode))
+}
+
+/// Set the owner and modification time of an extracted symlink itself, not of its target.
+fn apply_symlink_metadata(path: &Path, inode: &Inode) -> io::Result<()> {
+    let metadata = Metadata::from(inode);
+    ignore_permission_denied(lchown(path, Some(metadata.uid32()), Some(metadata.gid32())))?;
+    set_symlink_modified(path, metadata.modified()?)
+}
+
+/// Set the modification time of a symlink, std only has `File::set_modified` which follows it.
+fn set_symlink_modified(path: &Path, modified: SystemTime) -> io::Result<()> {
+    let path = CString::new(path.as_os_str().as_bytes())?;
+    let since_epoch = modified
+        .duration_since(UNIX_EPOCH)
+        .map_err(io::Error::other)?;
+    let times = [
+        // the access time is left alone, like for other files
+        libc::timespec {
+            tv_sec: 0,
+            tv_nsec: libc::UTIME_OMIT,
+        },
+        libc::timespec {
+            tv_sec: since_epoch.as_secs() as libc::time_t,
+            tv_nsec: since_epoch.subsec_nanos() as _,
+        },
+    ];
+    // SAFETY: the path is NUL terminated and `times` holds the two entries utimensat reads.
+    let ret = unsafe {
+        libc::utimensat(
+            libc::AT_FDCWD,
+            path.as_ptr(),
+            times.as_ptr(),
+            libc::AT_SYMLINK_NOFOLLOW,
+        )
+    };
+    if ret != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Check that a directory entry name can't escape the destination.
+fn check_name(dir: &Path, name: &str) -> Result<(), ExtfsError> {
+    if name.is_empty() || name == "." || name == ".." || name.contains('/') {
+        return Err(ExtfsError::InvalidPath(dir.join(name)));
+    }
+    Ok(())
+}
+
+impl<R: Read + Seek> FileSystem<R> {
+    /// Extract the whole file system, from its root set with `set_root`, under `dest`, which is
+    /// created if needed.
+    ///
+    /// Regular files, directories and symlinks are recreated with their mode, owner and
+    /// modification time where the host permits it, hard links are recreated as links. Device
+    /// files, fifos and sockets are skipped.
+    ///
+    /// Existing paths under `dest` are never overwritten, and symlinks are only created once
+    /// everything else is, so an image can't make the extraction write through them.
+    pub fn extract_to<P: AsRef<Path>>(&self, dest: P) -> Result<(), ExtfsError> {
+        let dest = dest.as_ref();
+        fs::create_dir_all(dest)?;
+
+        // first extracted path of inodes with several links
+        let mut linked: HashMap<u64, PathBuf> = HashMap::new();
+        // directories get their metadata last, so adding entries doesn't change their mtime
+        // and a read-only mode doesn't prevent it
+        let mut dirs = Vec::new();
+        let mut symlinks = Vec::new();
+        // a corrupted entry pointing to a visited directory would loop forever
+        let mut visited = HashSet::from([self.root()]);
+
+        let mut stack = vec![(self.inode(self.root())?, dest.to_path_buf())];
+        while let Some((inode, dir)) = stack.pop() {
+            let infos = self
+                .read_dir_ref(&inode)?
+                .infos()
+                .collect::<Result<Vec<_>, _>>()?;
+
+            for info in infos {
+                check_name(&dir, &info.name)?;
+                let path = dir.join(&info.name);
+                let child = self.inode(info.ino)?;
+
+                if child.is_dir() {
+                    if !visited.insert(info.ino) {
+                        continue;
+                    }
+                    fs::create_dir(&path)?;
+                    stack.push((child, path));
+                } else if child.is_regular() {
+                    if child.links_count() > 1 {
+                        if let Some(first) = linked.get(&info.ino) {
+                            fs::hard_link(first, &path)?;
+                            continue;
+                        }
+                        linked.insert(info.ino, path.clone());
+                    }
+                    let mut f = fs::File::create_new(&path)?;
+                    io::copy(
+                        &mut child.read_file(self.block_size(), self.cursor())?,
+                        &mut f,
+                    )?;
+                    apply_metadata(&f, &child)?;
+                } else if child.is_symlink() {
+                    symlinks.push((path, child));
+                }
+            }
+            dirs.push((dir, inode));
+        }
+
+        for (path, inode) in symlinks {
+            let target = inode.read_link(self.block_size(), self.cursor())?;
+            symlink(path_from_bytes(target), &path)?;
+            apply_symlink_metadata(&path, &inode)?;
+        }
+
+        // the destination itself keeps its metadata
+        for (dir, inode) in dirs.iter().rev() {
+            if dir != dest {
+                apply_metadata(&fs::File::open(dir)?, inode)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs, io,
+        os::unix::fs::{MetadataExt, PermissionsExt},
+        path::PathBuf,
+    };
+
+    use crate::{ExtfsError, FileSystem};
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("ext4fs-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn test_extract_to() {
+        let dest = temp_dir("extract");
+        let fs = FileSystem::from_path("testdata/test.ext4").unwrap();
+        fs.extract_to(&dest).unwrap();
+
+        assert_eq!(fs::read(dest.join("hello.txt")).unwrap(), b"hello\n");
+        assert_eq!(fs::read(dest.join("dir1/world.txt")).unwrap(), b"world\n");
+        assert!(dest.join("dir1/dir12").is_dir());
+        assert!(dest.join("dir2").is_dir());
+
+        let meta = fs.metadata("/dir1").unwrap();
+        let host = fs::metadata(dest.join("dir1")).unwrap();
+        assert_eq!(
+            host.permissions().mode() & 0o7777,
+            meta.permissions() as u32
+        );
+        assert_eq!(host.modified().unwrap(), meta.modified().unwrap());
+        fs::remove_dir_all(&dest).unwrap();
+
+        // symlinks are kept as they are
+        let dest = temp_dir("extract-symlink");
+        let fs = FileSystem::from_path("testdata/symlink.ext4").unwrap();
+        fs.extract_to(&dest).unwrap();
+        assert_eq!(
+            fs::read_link(dest.join("link_abs")).unwrap(),
+            PathBuf::from("/a/b")
+        );
+        assert_eq!(
+            fs::read_link(dest.join("a/b/up")).unwrap(),
+            PathBuf::from("../c")
+        );
+        let meta = fs.metadata("/link_abs").unwrap();
+        assert!(meta.is_symlink());
+        let host = fs::symlink_metadata(dest.join("link_abs")).unwrap();
+        assert_eq!(host.modified().unwrap(), meta.modified().unwrap());
+        fs::remove_dir_all(&dest).unwrap();
+    }
+
+    #[test]
+    fn test_extract_inline() {
+        let dest = temp_dir("extract-inline");
+        let fs = FileSystem::from_path("testdata/inline.ext4").unwrap();
+        fs.extract_to(&dest).unwrap();
+
+        for path in [
+            "small.txt",
+            "spill.txt",
+            "big.txt",
+            "smalldir/a",
+            "spilldir/entry_1",
+        ] {
+            assert_eq!(
+                fs::read(dest.join(path)).unwrap(),
+                fs.read(format!("/{}", path)).unwrap(),
+                "{}",
+                path
+            );
+        }
+        assert_eq!(fs::read(dest.join("small.txt")).unwrap().len(), 7);
+        fs::remove_dir_all(&dest).unwrap();
+    }
+
+    #[test]
+    fn test_extract_read_only_file() {
+        // mode 0 on /hello.txt, the 12th inode of the table at block 50
+        let mut data = fs::read("testdata/test.ext4").unwrap();
+        let pos = 50 * 1024 + 11 * 128;
+        data[pos..pos + 2].copy_from_slice(&0o100000u16.to_le_bytes());
+
+        let dest = temp_dir("extract-mode0");
+        let fs = FileSystem::from_bytes(&data).unwrap();
+        fs.extract_to(&dest).unwrap();
+
+        let host = fs::metadata(dest.join("hello.txt")).unwrap();
+        assert_eq!(host.permissions().mode() & 0o7777, 0);
+        assert_eq!(
+            host.modified().unwrap(),
+            fs.metadata("/hello.txt").unwrap().modified().unwrap()
+        );
+        fs::set_permissions(dest.join("hello.txt"), fs::Permissions::from_mode(0o644)).unwrap();
+        assert_eq!(fs::read(dest.join("hello.txt")).unwrap(), b"hello\n");
+        fs::remove_dir_all(&dest).unwrap();
+    }
+
+    #[test]
+    fn test_extract_symlink_collision() {
+        let mut data = fs::read("testdata/symlink.ext4").unwrap();
+        let fs = FileSystem::from_bytes(&data).unwrap();
+        let dir_ino = fs.path_to_ino("/a").unwrap() as u32;
+        let link_ino = fs.path_to_ino("/link_abs").unwrap() as u32;
+        let link = fs.raw_inode(link_ino as u64).unwrap();
+        let link_pos = data.windows(link.len()).position(|x| x == link).unwrap();
+
+        // the root lists the symlink /link_abs as "a" before the directory, now also named
+        // "a", and the symlink points to "zzzz" instead of "/a/b"
+        let a = data.windows(3).position(|x| x == [1, 2, b'a']).unwrap() - 6;
+        data[a..a + 4].copy_from_slice(&link_ino.to_le_bytes());
+        data[a + 7] = 7;
+        let l = data.windows(8).position(|x| x == b"link_abs").unwrap() - 8;
+        data[l..l + 4].copy_from_slice(&dir_ino.to_le_bytes());
+        data[l + 6..l + 9].copy_from_slice(&[1, 2, b'a']);
+        data[link_pos + 0x28..link_pos + 0x2C].copy_from_slice(b"zzzz");
+
+        let dest = temp_dir("extract-collision");
+        let fs = FileSystem::from_bytes(&data).unwrap();
+        assert!(matches!(
+            fs.extract_to(&dest),
+            Err(ExtfsError::Io(e)) if e.kind() == io::ErrorKind::AlreadyExists
+        ));
+        // the files went to the directory, not through the symlink
+        assert!(fs::symlink_metadata(dest.join("a")).unwrap().is_dir());
+        assert_eq!(fs::read(dest.join("a/b/y")).unwrap().len(), 2);
+        assert!(!dest.join("zzzz").exists());
+        fs::remove_dir_all(&dest).unwrap();
+    }
+
+    #[test]
+    fn test_extract_hard_links() {
+        let dest = temp_dir("extract-links");
+        let fs = FileSystem::from_path("testdata/htree.ext4").unwrap();
+        fs.extract_to(&dest).unwrap();
+
+        let target = fs::metadata(dest.join("big/target")).unwrap();
+        let link = fs::metadata(
+            dest.join("big/a_hard_link_with_a_rather_long_name_to_fill_blocks_quickly_0001"),
+        )
+        .unwrap();
+        assert_eq!(target.ino(), link.ino());
+        assert_eq!(target.nlink(), 4001);
+        fs::remove_dir_all(&dest).unwrap();
+    }
+}
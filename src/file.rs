@@ -1,6 +1,6 @@
 use std::{
     cmp,
-    io::{Error, ErrorKind, Read, Seek},
+    io::{self, Error, IoSliceMut, Read, Seek},
 };
 
 use super::extent::Extent;
@@ -12,6 +12,8 @@ pub struct File<R> {
     current: u64,
 
     block_size: u64,
+    /// The data stored in the inode with EXT4_INLINE_DATA_FL, read instead of the extents.
+    inline: Option<Vec<u8>>,
 }
 
 impl<R: Read + Seek> File<R> {
@@ -22,46 +24,131 @@ impl<R: Read + Seek> File<R> {
             len,
             current: 0,
             block_size,
+            inline: None,
         }
     }
-}
 
-impl<R: Read + Seek> Read for File<R> {
-    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
-        if buf.is_empty() || self.current >= self.len {
+    /// Create a file whose data is stored in the inode, `data` is `i_block` followed by the
+    /// value of the `system.data` attribute.
+    pub(crate) fn new_inline(reader: R, mut data: Vec<u8>, len: u64, block_size: u64) -> Self {
+        data.truncate(usize::try_from(len).unwrap_or(usize::MAX));
+        Self {
+            inline: Some(data),
+            ..Self::new(reader, Vec::new(), len, block_size)
+        }
+    }
+
+    /// Get the size of the file in bytes.
+    pub(crate) fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Get the extents of the file, sorted by logical block.
+    pub fn extents(&self) -> &[Extent] {
+        &self.extents
+    }
+
+    /// Unwraps this `File`, returning the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    /// Read bytes at `offset` into `buf`, returning the number of bytes read, 0 at the end.
+    ///
+    /// The position used by `Read` and `Seek` isn't changed.
+    pub fn read_at(&mut self, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+        // extents can cover blocks past i_size, e.g. with EXT4_EOFBLOCKS_FL, they aren't data
+        if buf.is_empty() || offset >= self.len {
             return Ok(0);
         }
 
+        let n = cmp::min(buf.len() as u64, self.len - offset) as usize;
+        if let Some(data) = &self.inline {
+            // a size past the inline data reads as zeros, like a hole
+            let start = cmp::min(offset, data.len() as u64) as usize;
+            let copied = cmp::min(n, data.len() - start);
+            buf[..copied].copy_from_slice(&data[start..start + copied]);
+            buf[copied..n].fill(0);
+            return Ok(n);
+        }
+
+        let block_size = self.block_size;
         let mut buf_pos = 0;
 
-        let mut offset = 0;
-        for e in &self.extents {
-            let extent_size = e.len as u64 * self.block_size;
-            if self.current >= offset + extent_size {
-                offset = offset + extent_size;
-                continue;
-            }
+        while buf_pos < n {
+            let pos = offset + buf_pos as u64;
+            let chunk = &mut buf[buf_pos..n];
 
-            let file_remain_len = self.len - self.current;
-            let buf_remain_len = (buf.len() - buf_pos) as u64;
-
-            let temp = e.read_bytes(
-                self.block_size,
-                &mut self.reader,
-                self.current - offset,
-                cmp::min(file_remain_len, buf_remain_len),
-            )?;
-            buf[buf_pos..buf_pos + temp.len()].copy_from_slice(&temp);
-            buf_pos += temp.len();
-            self.current += temp.len() as u64;
-
-            if buf_pos >= buf.len() {
-                return Ok(buf_pos);
-            }
+            // extents are sorted by logical block, find the first one not ending before pos
+            let idx = self.extents.partition_point(|e| {
+                (e.get_logical_block() + e.get_len() as u64) * block_size <= pos
+            });
+            let read = match self.extents.get(idx) {
+                Some(e) => {
+                    let start = e.get_logical_block() * block_size;
+                    let end = start + e.get_len() as u64 * block_size;
+                    if pos < start {
+                        // a hole before the extent
+                        fill_zero(chunk, start - pos)
+                    } else if e.is_uninit() {
+                        fill_zero(chunk, end - pos)
+                    } else {
+                        e.read_into(block_size, &mut self.reader, pos - start, chunk)?
+                    }
+                }
+                // a hole at the end of file
+                None => fill_zero(chunk, u64::MAX),
+            };
+
+            buf_pos += read;
         }
 
         Ok(buf_pos)
     }
+
+    /// Read exactly `buf.len()` bytes at `offset`, failing with `UnexpectedEof` if the file
+    /// ends before.
+    ///
+    /// The position used by `Read` and `Seek` isn't changed.
+    pub fn read_exact_at(&mut self, buf: &mut [u8], offset: u64) -> io::Result<()> {
+        let n = self.read_at(buf, offset)?;
+        if n < buf.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "failed to fill whole buffer",
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl<R: Read + Seek> Read for File<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.read_at(buf, self.current)?;
+        self.current += n as u64;
+        Ok(n)
+    }
+
+    /// Fill the buffers in order from consecutive bytes of the file, stopping at the end.
+    fn read_vectored(&mut self, bufs: &mut [IoSliceMut<'_>]) -> io::Result<usize> {
+        let mut total = 0;
+        for buf in bufs {
+            let n = self.read_at(buf, self.current)?;
+            self.current += n as u64;
+            total += n;
+            if n < buf.len() {
+                break;
+            }
+        }
+        Ok(total)
+    }
+}
+
+/// Fill at most `max` zeros into `buf`, returning the count.
+fn fill_zero(buf: &mut [u8], max: u64) -> usize {
+    let n = cmp::min(buf.len() as u64, max) as usize;
+    buf[..n].fill(0);
+    n
 }
 
 impl<R: Read + Seek> Seek for File<R> {
@@ -70,10 +157,7 @@ impl<R: Read + Seek> Seek for File<R> {
             std::io::SeekFrom::Start(offset) => offset,
             std::io::SeekFrom::End(offset) => {
                 if !offset.is_negative() {
-                    return Err(Error::new(
-                        ErrorKind::Other,
-                        format!("Expect negative offset"),
-                    ));
+                    return Err(Error::other("Expect negative offset"));
                 }
                 self.len - offset.wrapping_abs() as u64
             }
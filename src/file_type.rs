@@ -0,0 +1,112 @@
+use super::constants::{
+    INODE_MODE_BLK, INODE_MODE_CHR, INODE_MODE_DIR, INODE_MODE_FIFO, INODE_MODE_LNK,
+    INODE_MODE_REG, INODE_MODE_SOCK,
+};
+
+/// Type of an inode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FileType {
+    Regular,
+    Directory,
+    CharacterDevice,
+    BlockDevice,
+    Fifo,
+    Socket,
+    Symlink,
+    /// Unrecognized type bits of the mode, e.g. in a corrupted inode.
+    Unknown(u16),
+}
+
+impl FileType {
+    /// Convert the type bits of an inode mode.
+    pub fn from_mode(mode: u16) -> Self {
+        match mode & 0xF000 {
+            INODE_MODE_REG => Self::Regular,
+            INODE_MODE_DIR => Self::Directory,
+            INODE_MODE_CHR => Self::CharacterDevice,
+            INODE_MODE_BLK => Self::BlockDevice,
+            INODE_MODE_FIFO => Self::Fifo,
+            INODE_MODE_SOCK => Self::Socket,
+            INODE_MODE_LNK => Self::Symlink,
+            bits => Self::Unknown(bits),
+        }
+    }
+
+    /// Convert the file type code stored in a directory entry, 0 (unknown) and invalid codes give `None`.
+    pub fn from_dir_entry_code(code: u8) -> Option<Self> {
+        match code {
+            0x1 => Some(Self::Regular),
+            0x2 => Some(Self::Directory),
+            0x3 => Some(Self::CharacterDevice),
+            0x4 => Some(Self::BlockDevice),
+            0x5 => Some(Self::Fifo),
+            0x6 => Some(Self::Socket),
+            0x7 => Some(Self::Symlink),
+            _ => None,
+        }
+    }
+
+    /// Get the type bits of an inode mode, the inverse of `from_mode`.
+    pub fn to_mode_bits(&self) -> u16 {
+        match self {
+            Self::Regular => INODE_MODE_REG,
+            Self::Directory => INODE_MODE_DIR,
+            Self::CharacterDevice => INODE_MODE_CHR,
+            Self::BlockDevice => INODE_MODE_BLK,
+            Self::Fifo => INODE_MODE_FIFO,
+            Self::Socket => INODE_MODE_SOCK,
+            Self::Symlink => INODE_MODE_LNK,
+            Self::Unknown(bits) => bits & 0xF000,
+        }
+    }
+
+    /// Get the file type code of a directory entry, the inverse of `from_dir_entry_code`.
+    /// `Unknown` gives 0.
+    pub fn to_dir_entry_code(&self) -> u8 {
+        match self {
+            Self::Regular => 0x1,
+            Self::Directory => 0x2,
+            Self::CharacterDevice => 0x3,
+            Self::BlockDevice => 0x4,
+            Self::Fifo => 0x5,
+            Self::Socket => 0x6,
+            Self::Symlink => 0x7,
+            Self::Unknown(_) => 0,
+        }
+    }
+
+    pub fn is_dir(&self) -> bool {
+        *self == Self::Directory
+    }
+
+    pub fn is_file(&self) -> bool {
+        *self == Self::Regular
+    }
+
+    pub fn is_symlink(&self) -> bool {
+        *self == Self::Symlink
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FileType;
+
+    #[test]
+    fn test_conversions() {
+        for code in 1..=7 {
+            let t = FileType::from_dir_entry_code(code).unwrap();
+            assert_eq!(t.to_dir_entry_code(), code);
+            assert_eq!(FileType::from_mode(t.to_mode_bits() | 0o644), t);
+        }
+        assert_eq!(FileType::from_dir_entry_code(0), None);
+        assert_eq!(FileType::from_dir_entry_code(8), None);
+
+        assert_eq!(FileType::Symlink.to_mode_bits(), 0o120000);
+        assert_eq!(FileType::from_mode(0o040755).to_dir_entry_code(), 2);
+        let unknown = FileType::from_mode(0o030644);
+        assert_eq!(unknown, FileType::Unknown(0o030000));
+        assert_eq!(unknown.to_mode_bits(), 0o030000);
+        assert_eq!(unknown.to_dir_entry_code(), 0);
+    }
+}
@@ -1,21 +1,67 @@
 use std::{
-    io::{Read, Seek},
-    path::{Path, PathBuf},
+    collections::{HashMap, HashSet, VecDeque},
+    fs,
+    io::{BufReader, Cursor, Read, Seek, Write},
+    path::{Component, Path, PathBuf},
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+    time::SystemTime,
 };
 
-use crate::constants::INO_ROOT;
+use crate::constants::{INODE_FLAG_CASEFOLD, INODE_FLAG_ENCRYPT, INO_ROOT, MAX_SYMLINK_FOLLOWS};
 
 use super::{
-    codec::Decoder, constants::ZERO_PADDING_SIZE, descriptor::BlockGroupDescriptor,
-    errors::ExtfsError, file::File, inode::Inode, metadata::Metadata, read_dir::ReadDir,
-    superblock::SuperBlock,
+    block_device::BlockDevice,
+    checksum::{self, ChecksumKind},
+    constants::{
+        BG_INODE_UNINIT, CHECKSUM_TYPE_CRC32C, FEATURE_COMPAT_HAS_JOURNAL,
+        FEATURE_INCOMPAT_EXTENTS, SUPER_BLOCK_SIZE, ZERO_PADDING_SIZE,
+    },
+    descriptor::{BlockGroupDescriptor, BlockGroupInfo, FreeBlocksCheck, InodeUsage},
+    dir_hash::name_hash,
+    entry::DirEntryEnum,
+    entry::DirEntryInfo,
+    errors::ExtfsError,
+    extent::{block_map, data_runs, physical_block, DataRun, Extent, FragStats},
+    file::File,
+    handle::Entry,
+    htree::{dx_entries, find_entry, find_in_leaf, DxRoot, DX_NODE_ENTRIES_OFFSET},
+    inode::Inode,
+    inode_cache::InodeCache,
+    journal::{JournalSuperBlock, JournalTransaction},
+    metadata::Metadata,
+    mount_options::MountOptions,
+    options::FileSystemOptions,
+    partition::Partition,
+    read_at::{ReadAt, ReadAtCursor},
+    read_dir::ReadDir,
+    special_inode::SpecialInode,
+    superblock::{BlkidInfo, CreatorOs, Provenance, SuperBlock, SuperBlockView},
+    utils::{block_pos, path_from_bytes, read_exact_at, to_usize},
+    walk_dir::WalkDir,
+    xattr::{self, XattrEntry},
 };
 
+/// An ext4 file system read from `R`.
+///
+/// Reads take `&self` and go through `ReadAt`, the owned reader is locked for each positioned
+/// read. `read_dir`, `open` and `open_from_inode` hand the reader itself to the returned handle,
+/// by value or borrowed mutably. The file system is `Send` and `Sync` whenever `R` is `Send`,
+/// e.g. to read one image from several threads or to scan many images from a thread pool.
 #[derive(Debug)]
 pub struct FileSystem<R> {
     super_block: SuperBlock,
     block_group_descriptors: Vec<BlockGroupDescriptor>,
-    reader: R,
+    /// Locked for each positioned read, see `ReadAt`.
+    reader: Mutex<R>,
+    /// Verify checksums of all metadata as it's read.
+    strict: bool,
+    /// Directory that paths are resolved from, `INO_ROOT` unless set with `set_root`.
+    root: u64,
+    inode_cache: Mutex<InodeCache>,
+    /// Inodes whose blocks were verified in strict mode, so it's done once per inode.
+    verified: Mutex<HashSet<u64>>,
+    /// Skip corrupted directory entries, see `FileSystemOptions::tolerate_corruption`.
+    tolerate_corruption: bool,
     // reserved_gdt_blocks: Vec<u8>,
     // data_block_bitmaps: Vec<Bitmap>,
     // inode_bitmaps: Vec<Bitmap>,
@@ -23,276 +69,3585 @@ pub struct FileSystem<R> {
     // data_blocks: Vec<u8>,
 }
 
+impl<'a> FileSystem<Cursor<&'a [u8]>> {
+    /// Read an ext4 image that is already in memory.
+    ///
+    /// File reads are copied straight from the slice into the caller's buffer.
+    pub fn from_bytes(bytes: &'a [u8]) -> Result<Self, ExtfsError> {
+        Self::from_reader(Cursor::new(bytes))
+    }
+}
+
+impl FileSystem<BufReader<fs::File>> {
+    /// Open an image file read-only.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self, ExtfsError> {
+        Self::from_reader(BufReader::new(fs::File::open(path)?))
+    }
+}
+
+impl TryFrom<fs::File> for FileSystem<BufReader<fs::File>> {
+    type Error = ExtfsError;
+
+    fn try_from(file: fs::File) -> Result<Self, Self::Error> {
+        Self::from_reader(BufReader::new(file))
+    }
+}
+
+impl TryFrom<&Path> for FileSystem<BufReader<fs::File>> {
+    type Error = ExtfsError;
+
+    fn try_from(path: &Path) -> Result<Self, Self::Error> {
+        Self::from_path(path)
+    }
+}
+
+impl FileSystem<BlockDevice> {
+    /// Open the file system on a block device like `/dev/sdb1`, or an image file, read-only.
+    pub fn from_block_device<P: AsRef<Path>>(path: P) -> Result<Self, ExtfsError> {
+        Self::from_reader(BlockDevice::open(path)?)
+    }
+}
+
+impl<T: ReadAt> FileSystem<ReadAtCursor<Arc<T>>> {
+    /// Read a file system from a source of positioned reads that isn't `Seek`, through a
+    /// `ReadAtCursor` tracking the position. The source is kept behind an `Arc` shared with the
+    /// handles of `open_shared`.
+    pub fn from_read_at(source: T) -> Result<Self, ExtfsError> {
+        Self::from_reader(ReadAtCursor::new(Arc::new(source)))
+    }
+
+    /// Open a file whose handle reads the source with a cursor of its own, e.g. for the threads
+    /// of a server.
+    ///
+    /// The handle doesn't borrow the file system, and its reads don't serialize with the other
+    /// handles on the lock of the file system.
+    pub fn open_shared<P: AsRef<Path>>(
+        &self,
+        path: P,
+    ) -> Result<File<ReadAtCursor<Arc<T>>>, ExtfsError> {
+        let i = self.get_inode_by_path(path.as_ref())?;
+        if !i.is_regular() {
+            return Err(ExtfsError::IsNotRegular(path.as_ref().to_path_buf()));
+        }
+        let block_size = self.super_block.get_block_size();
+        let source = Arc::clone(
+            self.reader
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .get_ref(),
+        );
+
+        i.read_file(block_size, ReadAtCursor::new(source))
+    }
+}
+
+impl<R: Read + Seek> FileSystem<Partition<R>> {
+    /// Read a file system starting at byte `partition_offset` of `reader`, e.g. a partition of
+    /// a whole disk image, without slicing it out first.
+    pub fn from_reader_at(reader: R, partition_offset: u64) -> Result<Self, ExtfsError> {
+        FileSystemOptions::new()
+            .offset(partition_offset)
+            .open(reader)
+    }
+}
+
 impl<R: Read + Seek> FileSystem<R> {
-    pub fn from_reader(mut reader: R) -> Result<Self, ExtfsError> {
-        reader.seek(std::io::SeekFrom::Start(ZERO_PADDING_SIZE))?;
+    pub fn from_reader(reader: R) -> Result<Self, ExtfsError> {
+        FileSystemOptions::new().open_unwrapped(reader)
+    }
+
+    /// Like `from_reader`, but verify checksums of the super block, block group descriptors,
+    /// inodes, extent tree blocks and directory blocks as they are read, failing on the first
+    /// mismatch. Only block group descriptors are verified with gdt_csum, and nothing without
+    /// either feature, see `checksum_kind`.
+    pub fn from_reader_strict(reader: R) -> Result<Self, ExtfsError> {
+        FileSystemOptions::new().strict(true).open_unwrapped(reader)
+    }
+
+    pub(crate) fn new(mut reader: R, options: &FileSystemOptions) -> Result<Self, ExtfsError> {
+        let strict = options.strict;
+        let mut raw = vec![0; SUPER_BLOCK_SIZE];
+        read_exact_at(&mut reader, ZERO_PADDING_SIZE, &mut raw, "super block")?;
+        let super_block = SuperBlock::from_reader(raw.as_slice())?;
+        if !super_block.feature_incompat_extents() {
+            return Err(ExtfsError::UnsupportedFeature {
+                name: "extents",
+                bit: FEATURE_INCOMPAT_EXTENTS,
+            });
+        }
+        if strict && super_block.checksum_kind() == ChecksumKind::Crc32c {
+            if super_block.checksum_type() != CHECKSUM_TYPE_CRC32C {
+                return Err(ExtfsError::UnsupportedChecksumType(
+                    super_block.checksum_type(),
+                ));
+            }
+            if !checksum::verify_super_block(&raw) {
+                return Err(ExtfsError::SuperBlockChecksumMismatch);
+            }
+        }
+
+        let block_group_descriptors =
+            Self::read_block_group_descriptors(&mut reader, &super_block, strict)?;
+
+        Ok(Self {
+            super_block,
+            block_group_descriptors,
+            reader: Mutex::new(reader),
+            strict,
+            root: INO_ROOT,
+            inode_cache: Mutex::new(InodeCache::new(options.inode_cache)),
+            verified: Mutex::new(HashSet::new()),
+            tolerate_corruption: options.tolerate_corruption,
+        })
+    }
+
+    /// Read the block group descriptor table, verifying checksums if `verify` is set.
+    fn read_block_group_descriptors(
+        mut reader: impl Read + Seek,
+        super_block: &SuperBlock,
+        verify: bool,
+    ) -> Result<Vec<BlockGroupDescriptor>, ExtfsError> {
+        let desc_size = super_block.get_desc_size();
+        let mut block_group_descriptors = Vec::new();
+        for group in 0..super_block.get_block_group_count() as u64 {
+            let mut raw = vec![0; desc_size as usize];
+            let pos = super_block.descriptor_pos(group)?;
+            read_exact_at(&mut reader, pos, &mut raw, "block group descriptor")?;
+            let valid = match (super_block.checksum_kind(), super_block.get_checksum_seed()) {
+                _ if !verify => true,
+                (ChecksumKind::Crc32c, Some(seed)) => {
+                    checksum::verify_descriptor(seed, group, &raw)
+                }
+                (ChecksumKind::Crc16, _) => {
+                    checksum::verify_descriptor_crc16(super_block.uuid(), group, &raw)
+                }
+                _ => true,
+            };
+            if !valid {
+                return Err(ExtfsError::DescriptorChecksumMismatch { group });
+            }
+            let bgd = BlockGroupDescriptor::from_reader(raw.as_slice(), desc_size)?;
+            block_group_descriptors.push(bgd);
+        }
+
+        Ok(block_group_descriptors)
+    }
+
+    /// Verify checksums of all block group descriptors, failing with `DescriptorChecksumMismatch`
+    /// on the first corrupted one. It's a no-op if neither gdt_csum nor metadata_csum is enabled.
+    pub fn verify_descriptors(&self) -> Result<(), ExtfsError> {
+        Self::read_block_group_descriptors(self.cursor(), &self.super_block, true)?;
+        Ok(())
+    }
+
+    /// Compare the free blocks count of the super block with the sum of the block group
+    /// descriptors, a cheap hint of whether the file system was cleanly unmounted.
+    pub fn free_blocks_verified(&self) -> FreeBlocksCheck {
+        let super_block = self.super_block.get_free_blocks_count();
+        // groups count free clusters with bigalloc
+        let groups = self
+            .block_group_descriptors
+            .iter()
+            .map(|bgd| bgd.get_free_blocks_count())
+            .sum::<u64>()
+            * self.super_block.get_cluster_ratio();
+        FreeBlocksCheck {
+            super_block,
+            groups,
+            consistent: super_block == groups,
+        }
+    }
+
+    /// List the block groups with the location of their bitmaps and inode table, and their
+    /// free counts, like `dumpe2fs`.
+    pub fn block_groups(&self) -> Vec<BlockGroupInfo> {
+        self.block_group_descriptors
+            .iter()
+            .enumerate()
+            .map(|(group, bgd)| {
+                let group = group as u64;
+                bgd.info(group, self.super_block.group_first_block(group))
+            })
+            .collect()
+    }
+
+    /// Report the total, free and used inodes like `df -i`, cross-checking the free inodes of
+    /// the super block with the groups like `free_blocks_verified`.
+    pub fn inode_usage(&self) -> InodeUsage {
+        let total = self.super_block.get_inodes_count();
+        let free = self.super_block.get_free_inodes_count();
+        let groups_free = self
+            .block_group_descriptors
+            .iter()
+            .map(|bgd| bgd.get_free_inodes_count())
+            .sum();
+        InodeUsage {
+            total,
+            free,
+            used: total.saturating_sub(free),
+            groups_free,
+            consistent: free == groups_free,
+        }
+    }
+
+    /// Get the OS that created the file system.
+    pub fn creator_os(&self) -> CreatorOs {
+        self.super_block.creator_os()
+    }
+
+    /// Get the revision level, 0 for the original format and 1 for dynamic inode sizes and
+    /// feature flags, with the minor revision level.
+    pub fn revision(&self) -> (u32, u16) {
+        self.super_block.revision()
+    }
+
+    /// Get the time the file system was created, the epoch if `mkfs` didn't record it.
+    pub fn created_at(&self) -> SystemTime {
+        self.provenance().created_at
+    }
+
+    /// Get the UUID, label and type of the file system formatted like `blkid` does.
+    pub fn blkid_info(&self) -> BlkidInfo {
+        self.super_block.blkid_info()
+    }
+
+    /// Get the creation time, creator OS and revision level together.
+    pub fn provenance(&self) -> Provenance {
+        self.super_block.provenance()
+    }
+
+    /// Get the algorithm of the metadata checksums.
+    pub fn checksum_kind(&self) -> ChecksumKind {
+        self.super_block.checksum_kind()
+    }
+
+    /// Get the size of an on-disk inode in bytes.
+    pub fn inode_size(&self) -> u16 {
+        self.super_block.inode_size
+    }
+
+    /// Get the number of bytes beyond 128 that all inodes have, so the extra fields like the
+    /// nanoseconds of timestamps are valid in every inode.
+    pub fn min_extra_isize(&self) -> u16 {
+        self.super_block.min_extra_isize
+    }
+
+    /// Get the number of bytes beyond 128 that new inodes should have.
+    pub fn want_extra_isize(&self) -> u16 {
+        self.super_block.want_extra_isize
+    }
+
+    /// Get the number of blocks in each block group, the last group may have fewer.
+    pub fn blocks_per_group(&self) -> u32 {
+        self.super_block.blocks_per_group
+    }
+
+    /// Get the number of inodes in each block group, inode `n` is in group
+    /// `(n - 1) / inodes_per_group`.
+    pub fn inodes_per_group(&self) -> u32 {
+        self.super_block.inodes_per_group
+    }
+
+    /// Get the first block of group 0, 1 for 1 KiB blocks whose block 0 holds the super block,
+    /// 0 otherwise.
+    pub fn first_data_block(&self) -> u64 {
+        self.super_block.get_first_data_block()
+    }
+
+    /// Get the size of an allocation cluster in bytes, larger than the block size with bigalloc.
+    pub fn cluster_size(&self) -> u64 {
+        self.super_block.get_cluster_size()
+    }
+
+    /// Get the number of block groups packed together with flex_bg, whose bitmaps and inode
+    /// tables are stored in the first group of each flex group. `None` without flex_bg.
+    pub fn flex_bg_size(&self) -> Option<u32> {
+        self.super_block.flex_bg_size()
+    }
+
+    /// Get a copy of the common fields of the super block.
+    pub fn superblock(&self) -> SuperBlockView {
+        self.super_block.view()
+    }
+
+    /// Get the number of KiB written to the file system over its lifetime.
+    pub fn kbytes_written(&self) -> u64 {
+        self.super_block.kbytes_written
+    }
+
+    /// Get the seed used to hash names in htree directories.
+    pub fn dir_hash_seed(&self) -> [u32; 4] {
+        self.super_block.get_hash_seed()
+    }
+
+    /// Get the default hash version of htree directories (0=legacy, 1=half MD4, 2=tea, ...).
+    pub fn dir_hash_version(&self) -> u8 {
+        self.super_block.get_def_hash_version()
+    }
+
+    /// Get the default mount options of the file system.
+    pub fn default_mount_options(&self) -> MountOptions {
+        self.super_block.default_mount_options()
+    }
+
+    /// Get the mount options string stored in the super block.
+    pub fn mount_opts_string(&self) -> String {
+        self.super_block.mount_opts_string()
+    }
+
+    pub(crate) fn feature_incompat_filetype(&self) -> bool {
+        self.super_block.feature_incompat_filetype()
+    }
+
+    pub(crate) fn tolerates_corruption(&self) -> bool {
+        self.tolerate_corruption
+    }
+
+    pub(crate) fn block_size(&self) -> u64 {
+        self.super_block.get_block_size()
+    }
+
+    pub(crate) fn reader_mut(&mut self) -> &mut R {
+        self.reader
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn into_reader(self) -> R {
+        self.reader
+            .into_inner()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Get a `Read + Seek` cursor of its own over the reader, each read is positioned through
+    /// `ReadAt` so the file system is read from `&self`.
+    pub(crate) fn cursor(&self) -> ReadAtCursor<&Mutex<R>> {
+        ReadAtCursor::new(&self.reader)
+    }
+
+    pub(crate) fn extents_of_inode(&self, inode: &Inode) -> Result<Vec<Extent>, ExtfsError> {
+        let block_size = self.super_block.get_block_size();
+        inode.extents(block_size, self.cursor())
+    }
+
+    fn get_inode(&self, ino: u64) -> Result<Inode, ExtfsError> {
+        let cached = self.lock_inode_cache().get(ino);
+        if let Some(inode) = cached {
+            // it may have been decoded before strict mode was turned on by `read_checked`
+            self.check_inode(&inode)?;
+            return Ok(inode);
+        }
+        let raw = self.raw_inode(ino)?;
+        let inode = self.decode_inode(ino, &raw)?;
+        self.lock_inode_cache().insert(ino, inode.clone());
+        Ok(inode)
+    }
+
+    fn lock_inode_cache(&self) -> MutexGuard<'_, InodeCache> {
+        self.inode_cache
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Decode an inode read from the inode table, verifying it like `get_inode`.
+    fn decode_inode(&self, ino: u64, raw: &[u8]) -> Result<Inode, ExtfsError> {
+        let mut inode = Inode::from_reader(raw, self.super_block.inode_size)?;
+        inode.ino = ino;
+        inode.largedir = self.super_block.feature_incompat_largedir();
+        if !self.super_block.feature_incompat_64bit() {
+            inode.clear_file_acl_high();
+        }
+        // osd2 is decoded with the Linux layout
+        inode.clear_foreign_osd2(self.super_block.creator_os());
+
+        // always recorded, only fatal in strict mode
+        inode.checksum_valid = self
+            .super_block
+            .get_checksum_seed()
+            .map(|seed| checksum::verify_inode(seed, ino, raw));
+        self.check_inode(&inode)?;
+
+        Ok(inode)
+    }
+
+    /// Fail on a bad checksum of the inode or of its blocks in strict mode.
+    ///
+    /// The blocks of an inode are only verified the first time, as they're read again by every
+    /// lookup going through it.
+    fn check_inode(&self, inode: &Inode) -> Result<(), ExtfsError> {
+        if let Some(seed) = self.strict_checksum_seed() {
+            if inode.checksum_valid == Some(false) {
+                return Err(ExtfsError::InodeChecksumMismatch(inode.ino));
+            }
+            if !self.lock_verified().contains(&inode.ino) {
+                self.verify_inode_blocks(seed, inode.ino, inode)?;
+                self.lock_verified().insert(inode.ino);
+            }
+        }
+        Ok(())
+    }
+
+    fn lock_verified(&self) -> MutexGuard<'_, HashSet<u64>> {
+        self.verified.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Get the first block of the inode table of `group`, checking that the whole table lies
+    /// after the first data block and before the end of the file system.
+    ///
+    /// A corrupt descriptor or `inodes_per_group` would otherwise read data blocks as inodes.
+    fn inode_table_loc(&self, group: u64) -> Result<u64, ExtfsError> {
+        let bgd = self
+            .block_group_descriptors
+            .get(group as usize)
+            .ok_or(ExtfsError::BlockGroupDescriptorNotFound(group))?;
+        // all the locations of this descriptor are 0, it was wiped rather than pointing at a bad
+        // inode table
+        if bgd.is_zeroed() {
+            return Err(ExtfsError::ZeroedBlockGroupDescriptor(group));
+        }
+        let block = bgd.get_inode_table_loc();
+
+        let block_size = self.super_block.get_block_size();
+        let table_blocks = (self.super_block.inodes_per_group as u64)
+            .checked_mul(self.super_block.inode_size as u64)
+            .ok_or(ExtfsError::ArithmeticOverflow("inode table size"))?
+            .div_ceil(block_size);
+        let in_bounds = block > self.super_block.get_first_data_block()
+            && block
+                .checked_add(table_blocks)
+                .is_some_and(|end| end <= self.super_block.get_block_count());
+        if !in_bounds {
+            return Err(ExtfsError::InodeTableOutOfBounds { group, block });
+        }
+        Ok(block)
+    }
+
+    /// Read the `inode_size` bytes of an inode as they are on disk, without verifying them.
+    pub fn raw_inode(&self, ino: u64) -> Result<Vec<u8>, ExtfsError> {
+        let pos = self.inode_pos(ino)?;
+        let mut raw = vec![0; self.super_block.inode_size as usize];
+        read_exact_at(self.cursor(), pos, &mut raw, "inode table")?;
+        Ok(raw)
+    }
+
+    /// Get the byte position of an inode in the image.
+    fn inode_pos(&self, ino: u64) -> Result<u64, ExtfsError> {
+        let index = ino
+            .checked_sub(1)
+            .ok_or(ExtfsError::ArithmeticOverflow("inode number"))?;
+        let inodes_per_group = self.super_block.inodes_per_group as u64;
+        let bgd_num = index
+            .checked_div(inodes_per_group)
+            .ok_or(ExtfsError::ArithmeticOverflow("inode number"))?;
+        let inode_table_loc = self.inode_table_loc(bgd_num)?;
+
+        let inode_table_index = index % inodes_per_group;
+
+        block_pos(
+            inode_table_loc,
+            self.super_block.get_block_size(),
+            inode_table_index * self.super_block.inode_size as u64,
+        )
+    }
+
+    /// Read all the inodes of a block group with their numbers, unused ones included.
+    ///
+    /// The inode table of the group is read in one IO instead of seeking to each inode, which
+    /// is much faster to scan every inode. Inodes the group descriptor marks as never
+    /// initialized, with `INODE_UNINIT` or past `bg_itable_unused`, aren't read and are returned
+    /// zeroed, still with their own number.
+    pub fn inodes_in_group(
+        &self,
+        group: u64,
+    ) -> Result<impl Iterator<Item = (u64, Inode)>, ExtfsError> {
+        let inode_table_loc = self.inode_table_loc(group)?;
+        let inodes_per_group = self.super_block.inodes_per_group as u64;
+        let inode_size = self.super_block.inode_size as u64;
+
+        // the flag and the count are only maintained with group descriptor checksums
+        let bgd = &self.block_group_descriptors[group as usize];
+        let initialized = if self.super_block.checksum_kind() == ChecksumKind::None {
+            inodes_per_group
+        } else if bgd.get_flags() & BG_INODE_UNINIT != 0 {
+            0
+        } else {
+            inodes_per_group.saturating_sub(bgd.get_itable_unused())
+        };
+
+        let pos = block_pos(inode_table_loc, self.super_block.get_block_size(), 0)?;
+        let len = initialized
+            .checked_mul(inode_size)
+            .ok_or(ExtfsError::ArithmeticOverflow("inode table size"))?;
+        let mut table = vec![0; to_usize(len)?];
+        read_exact_at(self.cursor(), pos, &mut table, "inode table")?;
+
+        let first_ino = group * inodes_per_group + 1;
+        let empty = Inode::from_reader(
+            &vec![0; inode_size as usize][..],
+            self.super_block.inode_size,
+        )?;
+        // never used inodes are left zeroed, without checksum, they keep their number
+        let zeroed = |ino| {
+            let mut inode = empty.clone();
+            inode.ino = ino;
+            inode
+        };
+        let mut inodes = Vec::with_capacity(inodes_per_group as usize);
+        for (i, raw) in table.chunks_exact(inode_size as usize).enumerate() {
+            let ino = first_ino + i as u64;
+            let inode = if raw.iter().all(|&b| b == 0) {
+                zeroed(ino)
+            } else {
+                self.decode_inode(ino, raw)?
+            };
+            inodes.push((ino, inode));
+        }
+        for ino in first_ino + initialized..first_ino + inodes_per_group {
+            inodes.push((ino, zeroed(ino)));
+        }
+        Ok(inodes.into_iter())
+    }
+
+    fn strict_checksum_seed(&self) -> Option<u32> {
+        self.super_block.get_checksum_seed().filter(|_| self.strict)
+    }
+
+    /// Verify the extent tree blocks of an inode, and the leaf blocks if it's a directory.
+    fn verify_inode_blocks(&self, seed: u32, ino: u64, inode: &Inode) -> Result<(), ExtfsError> {
+        if !inode.uses_extents() {
+            return Ok(());
+        }
+        let block_size = self.super_block.get_block_size();
+        let inode_seed = checksum::inode_seed(seed, ino, inode.generation());
+        let extents = inode.extents_checked(block_size, self.cursor(), Some(inode_seed))?;
+        if !inode.is_dir() {
+            return Ok(());
+        }
+
+        let mut buf = vec![0; block_size as usize];
+        for e in extents {
+            for block in e.get_block_loc()..e.get_block_loc() + e.get_len() as u64 {
+                let pos = block_pos(block, block_size, 0)?;
+                read_exact_at(self.cursor(), pos, &mut buf, "directory block")?;
+                if checksum::verify_dir_block(inode_seed, &buf) == Some(false) {
+                    return Err(ExtfsError::DirBlockChecksumMismatch { ino, block });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Read a logical block of directory `ino`, returning its position in the image too.
+    fn read_dir_block(
+        &self,
+        ino: u64,
+        extents: &[Extent],
+        logical: u32,
+    ) -> Result<(u64, Vec<u8>), ExtfsError> {
+        let block = physical_block(extents, logical as u64).ok_or(ExtfsError::InvalidHtree {
+            ino,
+            reason: "unmapped block",
+        })?;
+        let block_size = self.super_block.get_block_size();
+        let pos = block_pos(block, block_size, 0)?;
+        let mut buf = vec![0; block_size as usize];
+        read_exact_at(self.cursor(), pos, &mut buf, "directory block")?;
+        Ok((pos, buf))
+    }
+
+    /// Find `name` in directory `ino`, through its htree if it's indexed.
+    fn find_dir_entry(
+        &self,
+        ino: u64,
+        dir: &Inode,
+        name: &str,
+    ) -> Result<Option<DirEntryEnum>, ExtfsError> {
+        // casefolded and encrypted names aren't hashed as they are
+        let hashed = dir.is_indexed()
+            && !dir.uses_inline_data()
+            && dir.flags & (INODE_FLAG_CASEFOLD | INODE_FLAG_ENCRYPT) == 0;
+        if hashed {
+            match self.htree_lookup(ino, dir, name) {
+                // a broken index falls back to a linear scan, like the kernel does
+                Err(ExtfsError::InvalidHtree { .. } | ExtfsError::UnsupportedHashVersion(_)) => {}
+                x => return x,
+            }
+        }
+
+        for x in self.read_dir_ref(dir)? {
+            // a directory failing to read isn't a missing entry, the caller may retry it
+            let e = x?;
+            if e.get_name_str() == name {
+                return Ok(Some(e));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Look `name` up in the htree of directory `ino`, reading one block per level.
+    ///
+    /// `dx_entry` blocks are logical blocks of the directory, they are mapped through its
+    /// extents before being read.
+    fn htree_lookup(
+        &self,
+        ino: u64,
+        dir: &Inode,
+        name: &str,
+    ) -> Result<Option<DirEntryEnum>, ExtfsError> {
+        let extents = self.extents_of_inode(dir)?;
+        let (_, root_block) = self.read_dir_block(ino, &extents, 0)?;
+        let largedir = self.super_block.feature_incompat_largedir();
+        let root = DxRoot::from_block(ino, &root_block, largedir)?;
+        let version = self.super_block.effective_hash_version(root.hash_version);
+        let (hash, _) = name_hash(name.as_bytes(), version, self.super_block.get_hash_seed())?;
+
+        // the entries of each level with the followed one, from the root to the leaf
+        let mut path = Vec::new();
+        let mut entries = dx_entries(ino, &root_block, root.entries_offset)?;
+        loop {
+            let at = find_entry(&entries, hash);
+            let logical = entries[at].1;
+            path.push((entries, at));
+            if path.len() > root.indirect_levels as usize {
+                break;
+            }
+            let (_, node) = self.read_dir_block(ino, &extents, logical)?;
+            entries = dx_entries(ino, &node, DX_NODE_ENTRIES_OFFSET)?;
+        }
+
+        let feature_incompat_filetype = self.super_block.feature_incompat_filetype();
+        loop {
+            let (entries, at) = &path[path.len() - 1];
+            let (pos, leaf) = self.read_dir_block(ino, &extents, entries[*at].1)?;
+            if let Some(e) = find_in_leaf(&leaf, pos, name, feature_incompat_filetype)? {
+                return Ok(Some(e));
+            }
+            if !self.htree_next_leaf(ino, &extents, &mut path, hash)? {
+                return Ok(None);
+            }
+        }
+    }
+
+    /// Move `path` to the next leaf if it may hold more names with `hash`, like
+    /// `ext4_htree_next_block`.
+    ///
+    /// Names with the same hash can spill over into the next leaf, whose hash then has the low
+    /// bit set.
+    fn htree_next_leaf(
+        &self,
+        ino: u64,
+        extents: &[Extent],
+        path: &mut Vec<(Vec<(u32, u32)>, usize)>,
+        hash: u32,
+    ) -> Result<bool, ExtfsError> {
+        let depth = path.len();
+        let Some(level) = path
+            .iter()
+            .rposition(|(entries, at)| at + 1 < entries.len())
+        else {
+            return Ok(false);
+        };
+        path.truncate(level + 1);
+        let (entries, at) = &mut path[level];
+        *at += 1;
+        if entries[*at].0 & !1 != hash {
+            return Ok(false);
+        }
+
+        // follow the first entries of the nodes below
+        let mut logical = entries[*at].1;
+        while path.len() < depth {
+            let (_, node) = self.read_dir_block(ino, extents, logical)?;
+            let entries = dx_entries(ino, &node, DX_NODE_ENTRIES_OFFSET)?;
+            logical = entries[0].1;
+            path.push((entries, 0));
+        }
+        Ok(true)
+    }
+
+    fn get_inode_by_path<P: AsRef<Path>>(&self, path: P) -> Result<Inode, ExtfsError> {
+        let (_, inode) = self.lookup_path(self.root, path)?;
+        Ok(inode)
+    }
+
+    /// Resolve a path to its inode number and inode, `/` being the directory `root`.
+    fn lookup_path<P: AsRef<Path>>(&self, root: u64, path: P) -> Result<(u64, Inode), ExtfsError> {
+        let p = path.as_ref();
+        if !path.as_ref().is_absolute() {
+            return Err(ExtfsError::RequireAbsolutePath(p.to_path_buf()));
+        }
+
+        // `components` drops a trailing `/` and `/.`, but like POSIX they require a directory
+        let raw = p.to_str().ok_or(ExtfsError::InvalidPath(p.to_path_buf()))?;
+        let require_dir = raw.ends_with('/') || raw.ends_with("/.");
+
+        let block_size = self.super_block.get_block_size();
+
+        // names still to resolve, symlink targets are spliced in front of them
+        let mut pending = VecDeque::new();
+        push_components(&mut pending, p, p)?;
+
+        // the resolved directories, the first one is the root
+        let mut name_inode_stack = vec![("/".to_string(), root, self.get_inode(root)?)];
+        let mut follows = 0;
+        while let Some(name) = pending.pop_front() {
+            if name == ".." {
+                if name_inode_stack.len() <= 1 {
+                    return Err(ExtfsError::UnexpectedParentDir(p.to_path_buf()));
+                }
+                name_inode_stack.pop();
+                continue;
+            }
+
+            let (_, last_ino, last_inode) = name_inode_stack
+                .last()
+                .ok_or(ExtfsError::InvalidPath(p.to_path_buf()))?;
+            if !last_inode.is_dir() {
+                let path: PathBuf = name_inode_stack.iter().map(|(s, _, _)| s).collect();
+                return Err(ExtfsError::IsNotDirecotry(path.join(name)));
+            }
+
+            let Some(e) = self.find_dir_entry(*last_ino, last_inode, &name)? else {
+                let path: PathBuf = name_inode_stack.iter().map(|(s, _, _)| s).collect();
+                return Err(ExtfsError::NoSuchFileOrDirectory(path.join(name)));
+            };
+            let ino = e.get_ino().ok_or(ExtfsError::UnexpectedDirEntry(e))? as u64;
+            let inode = self.get_inode(ino)?;
+
+            // follow symlinks in the middle of the path, the last one only if a directory is
+            // required, like `lstat`
+            if inode.is_symlink() && (!pending.is_empty() || require_dir) {
+                follows += 1;
+                if follows > MAX_SYMLINK_FOLLOWS {
+                    return Err(ExtfsError::TooManySymlinks(p.to_path_buf()));
+                }
+
+                let target = inode.read_link(block_size, self.cursor())?;
+                let target = PathBuf::from(String::from_utf8_lossy(&target).to_string());
+                if target.is_absolute() {
+                    name_inode_stack.truncate(1);
+                }
+
+                let mut rest = VecDeque::new();
+                push_components(&mut rest, &target, p)?;
+                rest.append(&mut pending);
+                pending = rest;
+                continue;
+            }
+
+            name_inode_stack.push((name, ino, inode));
+        }
+
+        let (_, ino, last_inode) = name_inode_stack
+            .pop()
+            .ok_or(ExtfsError::InvalidPath(p.to_path_buf()))?;
+
+        if require_dir && !last_inode.is_dir() {
+            return Err(ExtfsError::IsNotDirecotry(p.to_path_buf()));
+        }
+
+        Ok((ino, last_inode))
+    }
+
+    /// Get the extents of a file, mapping its logical blocks to physical blocks.
+    pub fn extents_of<P: AsRef<Path>>(&self, path: P) -> Result<Vec<Extent>, ExtfsError> {
+        let i = self.get_inode_by_path(path)?;
+        self.extents_of_inode(&i)
+    }
+
+    /// Get the `(logical, physical)` block pairs of a directory, sorted by logical block.
+    ///
+    /// The logical blocks are the ones `dx_entry` of an htree refer to. An inline directory has
+    /// no blocks.
+    pub fn dir_block_map<P: AsRef<Path>>(&self, path: P) -> Result<Vec<(u64, u64)>, ExtfsError> {
+        let i = self.get_inode_by_path(path.as_ref())?;
+        if !i.is_dir() {
+            return Err(ExtfsError::IsNotDirecotry(path.as_ref().to_path_buf()));
+        }
+        if i.uses_inline_data() {
+            return Ok(Vec::new());
+        }
+        let extents = self.extents_of_inode(&i)?;
+        Ok(block_map(&extents))
+    }
+
+    /// Get where the data of a regular file lives in the image, as runs of contiguous bytes.
+    ///
+    /// The runs cover the whole file in order. Holes and unwritten extents have no physical
+    /// offset, they read as zeros. The data of an inline data file is in its inode.
+    pub fn data_location<P: AsRef<Path>>(&self, path: P) -> Result<Vec<DataRun>, ExtfsError> {
+        let i = self.get_inode_by_path(path.as_ref())?;
+        if !i.is_regular() {
+            return Err(ExtfsError::IsNotRegular(path.as_ref().to_path_buf()));
+        }
+        if i.uses_inline_data() {
+            let pos = self.inode_pos(i.ino)?;
+            let size = i.get_size();
+            let mut runs = Vec::new();
+            let mut logical = 0;
+            for (offset, len) in i.inline_data_location(self.super_block.inode_size) {
+                let len = len.min(size - logical);
+                if len == 0 {
+                    break;
+                }
+                runs.push(DataRun {
+                    logical,
+                    physical: Some(pos + offset),
+                    len,
+                });
+                logical += len;
+            }
+            // a size past the inline data reads as zeros
+            if logical < size {
+                runs.push(DataRun {
+                    logical,
+                    physical: None,
+                    len: size - logical,
+                });
+            }
+            return Ok(runs);
+        }
+        let extents = self.extents_of_inode(&i)?;
+        data_runs(&extents, self.super_block.get_block_size(), i.get_size())
+    }
+
+    /// Summarize how fragmented a file is on disk, like `filefrag`.
+    pub fn fragmentation<P: AsRef<Path>>(&self, path: P) -> Result<FragStats, ExtfsError> {
+        let extents = self.extents_of(path)?;
+        Ok(FragStats::from_extents(&extents))
+    }
+
+    /// Resolve a path to its inode number, e.g. to use it later with `inode`.
+    pub fn path_to_ino<P: AsRef<Path>>(&self, path: P) -> Result<u64, ExtfsError> {
+        let (ino, _) = self.lookup_path(self.root, path)?;
+        Ok(ino)
+    }
+
+    /// Resolve a path as if the directory `root` was `/`, e.g. to look into a subtree or a
+    /// detached directory. `..` can't go above `root`.
+    pub fn path_to_ino_from<P: AsRef<Path>>(&self, root: u64, path: P) -> Result<u64, ExtfsError> {
+        let (ino, _) = self.lookup_path(root, path)?;
+        Ok(ino)
+    }
+
+    /// Get the inode number of the directory that paths are resolved from.
+    pub fn root(&self) -> u64 {
+        self.root
+    }
+
+    /// Resolve all paths from the directory `ino` instead of the real root, like `chroot`, e.g.
+    /// to inspect the `/rootfs` of a container layer.
+    ///
+    /// `..` can't go above the new root and absolute symlinks are resolved from it.
+    pub fn set_root(&mut self, ino: u64) -> Result<(), ExtfsError> {
+        if !self.get_inode(ino)?.is_dir() {
+            return Err(ExtfsError::InodeIsNotDirectory(ino));
+        }
+        self.root = ino;
+        Ok(())
+    }
+
+    /// Resolve `path` and make it the root of all paths, see `set_root`.
+    pub fn set_root_path<P: AsRef<Path>>(&mut self, path: P) -> Result<(), ExtfsError> {
+        let (ino, i) = self.lookup_path(self.root, path.as_ref().join(""))?;
+        if !i.is_dir() {
+            return Err(ExtfsError::IsNotDirecotry(path.as_ref().to_path_buf()));
+        }
+        self.root = ino;
+        Ok(())
+    }
+
+    /// Resolve many paths to their inode numbers, in the order of `paths`.
+    ///
+    /// The paths are resolved in sorted order, so each parent directory is resolved and read
+    /// once for all its entries instead of once per path. Like `path_to_ino`, a symlink at the
+    /// end of a path isn't followed.
+    pub fn resolve_many<P: AsRef<Path>>(&self, paths: &[P]) -> Vec<Result<u64, ExtfsError>> {
+        let mut order: Vec<usize> = (0..paths.len()).collect();
+        order.sort_by(|&a, &b| paths[a].as_ref().cmp(paths[b].as_ref()));
+
+        let mut results: Vec<Option<Result<u64, ExtfsError>>> =
+            paths.iter().map(|_| None).collect();
+        let mut dirs: HashMap<PathBuf, u64> = HashMap::new();
+        // entries of the last directory read, sorted paths of a directory come together
+        let mut listing: Option<(u64, HashMap<String, u64>)> = None;
+        for i in order {
+            let p = paths[i].as_ref();
+            results[i] = Some(self.resolve_cached(p, &mut dirs, &mut listing));
+        }
+        results.into_iter().flatten().collect()
+    }
+
+    fn resolve_cached(
+        &self,
+        p: &Path,
+        dirs: &mut HashMap<PathBuf, u64>,
+        listing: &mut Option<(u64, HashMap<String, u64>)>,
+    ) -> Result<u64, ExtfsError> {
+        // `file_name` skips a trailing `.`, let the full lookup handle these
+        let raw = p.to_str().ok_or(ExtfsError::InvalidPath(p.to_path_buf()))?;
+        let (Some(parent), Some(name)) = (p.parent(), p.file_name()) else {
+            return self.path_to_ino(p);
+        };
+        if raw.ends_with('/') || raw.ends_with("/.") || raw.ends_with("/..") {
+            return self.path_to_ino(p);
+        }
+
+        let dir = match dirs.get(parent) {
+            Some(&ino) => ino,
+            None => {
+                // a trailing `/` follows a symlink to a directory and requires a directory
+                let Ok((ino, _)) = self.lookup_path(self.root, parent.join("")) else {
+                    // resolve it again to report the error against `p`, not its parent
+                    return self.path_to_ino(p);
+                };
+                dirs.insert(parent.to_path_buf(), ino);
+                ino
+            }
+        };
+
+        if listing.as_ref().is_none_or(|(ino, _)| *ino != dir) {
+            let inode = self.get_inode(dir)?;
+            let entries = self
+                .read_dir_ref(&inode)?
+                .infos()
+                .map(|x| x.map(|info| (info.name, info.ino)))
+                .collect::<Result<_, _>>()?;
+            *listing = Some((dir, entries));
+        }
+
+        let name = name
+            .to_str()
+            .ok_or(ExtfsError::InvalidPath(p.to_path_buf()))?;
+        listing
+            .as_ref()
+            .and_then(|(_, entries)| entries.get(name).copied())
+            .ok_or(ExtfsError::NoSuchFileOrDirectory(p.to_path_buf()))
+    }
+
+    pub fn read_dir<P: AsRef<Path>>(self, path: P) -> Result<ReadDir<R>, ExtfsError> {
+        let i = self.get_inode_by_path(path.as_ref())?;
+        if !i.is_dir() {
+            return Err(ExtfsError::IsNotDirecotry(path.as_ref().to_path_buf()));
+        }
+        let block_size = self.super_block.get_block_size();
+        let feature_incompat_filetype = self.super_block.feature_incompat_filetype();
+        let tolerate_corruption = self.tolerate_corruption;
+
+        Ok(
+            i.read_dir(block_size, feature_incompat_filetype, self.into_reader())?
+                .with_skip_corrupt(tolerate_corruption),
+        )
+    }
+
+    /// Returns an iterator over the entries within a directory given its inode number.
+    pub fn read_dir_from_ino(self, ino: u64) -> Result<ReadDir<R>, ExtfsError> {
+        let i = self.get_inode(ino)?;
+        if !i.is_dir() {
+            return Err(ExtfsError::InodeIsNotDirectory(ino));
+        }
+        let block_size = self.super_block.get_block_size();
+        let feature_incompat_filetype = self.super_block.feature_incompat_filetype();
+        let tolerate_corruption = self.tolerate_corruption;
+
+        Ok(
+            i.read_dir(block_size, feature_incompat_filetype, self.into_reader())?
+                .with_skip_corrupt(tolerate_corruption),
+        )
+    }
+
+    /// Collect the entries of a directory, stopping at the first error.
+    pub fn list_dir<P: AsRef<Path>>(&self, path: P) -> Result<Vec<DirEntryInfo>, ExtfsError> {
+        let i = self.get_inode_by_path(path.as_ref())?;
+        if !i.is_dir() {
+            return Err(ExtfsError::IsNotDirecotry(path.as_ref().to_path_buf()));
+        }
+        self.read_dir_ref(&i)?.infos().collect()
+    }
+
+    /// Recursively walk the entries below a directory, yielding their paths and metadata.
+    pub fn walk_dir<P: AsRef<Path>>(&self, path: P) -> Result<WalkDir<'_, R>, ExtfsError> {
+        let (ino, i) = self.lookup_path(self.root, path.as_ref())?;
+        if !i.is_dir() {
+            return Err(ExtfsError::IsNotDirecotry(path.as_ref().to_path_buf()));
+        }
+        WalkDir::new(self, path.as_ref().to_path_buf(), ino, &i)
+    }
+
+    /// Walk the whole file system, yielding the paths and metadata of regular files only.
+    pub fn files(
+        &self,
+    ) -> Result<impl Iterator<Item = Result<(PathBuf, Metadata), ExtfsError>> + '_, ExtfsError>
+    {
+        let walk = self.walk_dir("/")?;
+        Ok(walk.filter(|x| !matches!(x, Ok((_, m)) if !m.is_file())))
+    }
+
+    /// List the sub directories of a directory as `(name, ino)`.
+    pub fn sub_dirs<P: AsRef<Path>>(
+        &self,
+        path: P,
+    ) -> Result<impl Iterator<Item = (String, u64)>, ExtfsError> {
+        let i = self.get_inode_by_path(path.as_ref())?;
+        if !i.is_dir() {
+            return Err(ExtfsError::IsNotDirecotry(path.as_ref().to_path_buf()));
+        }
+        self.sub_dirs_of_inode(&i)
+    }
+
+    /// List the sub directories of a directory given its inode number, e.g. one yielded by
+    /// `sub_dirs`, so a tree can be walked without resolving paths again.
+    pub fn sub_dirs_of_ino(
+        &self,
+        ino: u64,
+    ) -> Result<impl Iterator<Item = (String, u64)>, ExtfsError> {
+        let i = self.get_inode(ino)?;
+        if !i.is_dir() {
+            return Err(ExtfsError::InodeIsNotDirectory(ino));
+        }
+        self.sub_dirs_of_inode(&i)
+    }
+
+    fn sub_dirs_of_inode(
+        &self,
+        inode: &Inode,
+    ) -> Result<std::vec::IntoIter<(String, u64)>, ExtfsError> {
+        // only keep the entries that may be directories, their type is checked afterwards if the
+        // filetype feature is disabled
+        let mut dirs = Vec::new();
+        let mut unknown = Vec::new();
+        for info in self.read_dir_ref(inode)?.infos() {
+            let info = info?;
+            match info.file_type {
+                Some(t) if t.is_dir() => dirs.push((info.name, info.ino)),
+                Some(_) => {}
+                None => unknown.push((info.name, info.ino)),
+            }
+        }
+        for (name, ino) in unknown {
+            if self.get_inode(ino)?.is_dir() {
+                dirs.push((name, ino));
+            }
+        }
+        Ok(dirs.into_iter())
+    }
+
+    /// Read a directory with a cursor over the reader.
+    pub(crate) fn read_dir_ref(
+        &self,
+        inode: &Inode,
+    ) -> Result<ReadDir<ReadAtCursor<&Mutex<R>>>, ExtfsError> {
+        let block_size = self.super_block.get_block_size();
+        let feature_incompat_filetype = self.super_block.feature_incompat_filetype();
+
+        Ok(inode
+            .read_dir(block_size, feature_incompat_filetype, self.cursor())?
+            .with_skip_corrupt(self.tolerate_corruption))
+    }
+
+    /// Read the entire contents of a file into a bytes vector.
+    ///
+    /// Fails with `FileTooLargeForPlatform` if the file doesn't fit in memory, use `copy_to` to
+    /// stream it instead.
+    pub fn read<P: AsRef<Path>>(&self, path: P) -> Result<Vec<u8>, ExtfsError> {
+        let i = self.get_inode_by_path(path.as_ref())?;
+        if !i.is_regular() {
+            return Err(ExtfsError::IsNotRegular(path.as_ref().to_path_buf()));
+        }
+        let block_size = self.super_block.get_block_size();
+
+        let b = i.read_bytes(block_size, self.cursor())?;
+        Ok(b)
+    }
+
+    /// Read a file like `read`, verifying the checksums of the inodes, extent tree blocks and
+    /// directory blocks met on the way as if the file system was opened in strict mode.
+    ///
+    /// Nothing can be verified without metadata_csum.
+    pub fn read_checked<P: AsRef<Path>>(&mut self, path: P) -> Result<Vec<u8>, ExtfsError> {
+        let strict = std::mem::replace(&mut self.strict, true);
+        let result = self.read(path);
+        self.strict = strict;
+        result
+    }
+
+    /// Read a file like `read`, but on failure return the bytes read up to the failing block
+    /// along with the error, to salvage what's readable from a damaged file.
+    pub fn read_recoverable<P: AsRef<Path>>(&self, path: P) -> (Vec<u8>, Option<ExtfsError>) {
+        let mut data = Vec::new();
+        let block_size = self.super_block.get_block_size();
+        let mut f = match self.open_ref(path.as_ref()) {
+            Ok(f) => f,
+            Err(e) => return (data, Some(e)),
+        };
+        if let Err(e) = to_usize(f.len()) {
+            return (data, Some(e));
+        }
+
+        // a block at a time, so only the failing block is lost
+        let mut buf = vec![0; block_size as usize];
+        loop {
+            match f.read(&mut buf) {
+                Ok(0) => return (data, None),
+                Ok(n) => data.extend_from_slice(&buf[..n]),
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+                Err(e) => return (data, Some(e.into())),
+            }
+        }
+    }
+
+    /// Read at most `len` bytes of a file starting at `offset`, holes are read as zeros.
+    pub fn read_range<P: AsRef<Path>>(
+        &self,
+        path: P,
+        offset: u64,
+        len: u64,
+    ) -> Result<Vec<u8>, ExtfsError> {
+        let mut f = self.open_ref(path.as_ref())?;
+        f.seek(std::io::SeekFrom::Start(offset))?;
+
+        to_usize(len.min(f.len().saturating_sub(offset)))?;
+        let mut buf = Vec::new();
+        f.take(len).read_to_end(&mut buf)?;
+        Ok(buf)
+    }
+
+    /// Read at most the first `n` bytes of a file, e.g. to sniff its magic number.
+    pub fn read_head<P: AsRef<Path>>(&self, path: P, n: u64) -> Result<Vec<u8>, ExtfsError> {
+        self.read_range(path, 0, n)
+    }
+
+    /// Copy the entire contents of a file into a writer without buffering it in memory,
+    /// returning the number of bytes copied. Unlike `read`, it works for files larger than the
+    /// address space, e.g. above 4 GiB on 32-bit platforms.
+    pub fn copy_to<P: AsRef<Path>, W: Write>(
+        &self,
+        path: P,
+        writer: &mut W,
+    ) -> Result<u64, ExtfsError> {
+        let mut f = self.open_ref(path.as_ref())?;
+        Ok(std::io::copy(&mut f, writer)?)
+    }
+
+    /// Open a regular file with a cursor over the reader.
+    fn open_ref(&self, path: &Path) -> Result<File<ReadAtCursor<&Mutex<R>>>, ExtfsError> {
+        let i = self.get_inode_by_path(path)?;
+        if !i.is_regular() {
+            return Err(ExtfsError::IsNotRegular(path.to_path_buf()));
+        }
+        let block_size = self.super_block.get_block_size();
+
+        i.read_file(block_size, self.cursor())
+    }
+
+    /// Read the inode with the given number.
+    pub fn inode(&self, ino: u64) -> Result<Inode, ExtfsError> {
+        self.get_inode(ino)
+    }
+
+    /// Get the number of a special inode in this file system.
+    ///
+    /// `lost+found` is the first inode after the reserved ones and the journal is the one
+    /// recorded in the super block, the other inodes have fixed numbers. Returns `None` for the
+    /// journal if there's none or it's on an external device.
+    pub fn special_ino(&self, which: SpecialInode) -> Option<u64> {
+        match which {
+            SpecialInode::LostAndFound => Some(self.super_block.get_first_ino()),
+            SpecialInode::Journal => {
+                let ino = self.super_block.get_journal_inum();
+                (self.super_block.feature_compat_has_journal() && ino != 0).then_some(ino as u64)
+            }
+            _ => Some(which.default_ino()),
+        }
+    }
+
+    /// Read a special inode, e.g. the journal or `lost+found`.
+    pub fn special_inode(&self, which: SpecialInode) -> Result<Inode, ExtfsError> {
+        let ino = self.special_ino(which).ok_or(ExtfsError::NoJournalInode)?;
+        self.get_inode(ino)
+    }
+
+    /// Read the entire contents of an already resolved regular file into a bytes vector.
+    pub fn read_from_inode(&self, inode: &Inode) -> Result<Vec<u8>, ExtfsError> {
+        if !inode.is_regular() {
+            return Err(ExtfsError::InodeIsNotRegular(inode.file_type()));
+        }
+        let block_size = self.super_block.get_block_size();
+        inode.read_bytes(block_size, self.cursor())
+    }
+
+    /// Open an already resolved regular file in read-only mode, borrowing the reader.
+    pub fn open_from_inode(&mut self, inode: &Inode) -> Result<File<&mut R>, ExtfsError> {
+        let block_size = self.super_block.get_block_size();
+        inode.read_file(block_size, self.reader_mut())
+    }
+
+    /// Reads a symbolic link, returning the file that the link points to.
+    ///
+    /// Non UTF-8 targets are only kept as they are on unix, see `read_link_raw`.
+    pub fn read_link<P: AsRef<Path>>(&self, path: P) -> Result<PathBuf, ExtfsError> {
+        Ok(path_from_bytes(self.read_link_raw(path)?))
+    }
+
+    /// Reads the target of a symbolic link as the bytes stored on disk, exactly `i_size` long.
+    pub fn read_link_raw<P: AsRef<Path>>(&self, path: P) -> Result<Vec<u8>, ExtfsError> {
+        let i = self.get_inode_by_path(path.as_ref())?;
+        if !i.is_symlink() {
+            return Err(ExtfsError::IsNotSymlink(path.as_ref().to_path_buf()));
+        }
+        let block_size = self.super_block.get_block_size();
+
+        i.read_link(block_size, self.cursor())
+    }
+
+    /// Read the extended attributes stored in an inode and in its attribute block.
+    fn xattrs_of_inode(&self, inode: &Inode) -> Result<Vec<XattrEntry>, ExtfsError> {
+        let mut xattrs = xattr::parse_inode_xattrs(&inode.xattr_area);
+
+        let file_acl = inode.get_file_acl();
+        if file_acl != 0 {
+            let block_size = self.super_block.get_block_size();
+            let mut block = vec![0; block_size as usize];
+            let pos = block_pos(file_acl, block_size, 0)?;
+            read_exact_at(self.cursor(), pos, &mut block, "xattr block")?;
+            xattrs.extend(xattr::parse_block_xattrs(&block));
+        }
+        Ok(xattrs)
+    }
+
+    /// List the full names of the extended attributes of a file, e.g. `security.selinux`.
+    pub fn list_xattr<P: AsRef<Path>>(&self, path: P) -> Result<Vec<String>, ExtfsError> {
+        let i = self.get_inode_by_path(path.as_ref())?;
+        let xattrs = self.xattrs_of_inode(&i)?;
+        Ok(xattrs.iter().map(|x| x.full_name()).collect())
+    }
+
+    /// Get the value of an extended attribute by its full name, `None` if it isn't set.
+    pub fn xattr<P: AsRef<Path>>(
+        &self,
+        path: P,
+        name: &str,
+    ) -> Result<Option<Vec<u8>>, ExtfsError> {
+        let i = self.get_inode_by_path(path.as_ref())?;
+        let xattrs = self.xattrs_of_inode(&i)?;
+        Ok(xattrs
+            .into_iter()
+            .find(|x| x.matches(name))
+            .map(|x| x.value))
+    }
+
+    /// List the transactions in the journal that recovery would replay, without replaying them.
+    ///
+    /// Returns nothing if there's no journal or it's clean. A trailing transaction without
+    /// commit block is returned too, with `committed` unset.
+    pub fn journal_transactions(&self) -> Result<Vec<JournalTransaction>, ExtfsError> {
+        if !self.super_block.feature_compat_has_journal() {
+            return Ok(Vec::new());
+        }
+        let ino = self.super_block.get_journal_inum();
+        if ino == 0 {
+            return Err(ExtfsError::UnsupportedFeature {
+                name: "external journal",
+                bit: FEATURE_COMPAT_HAS_JOURNAL,
+            });
+        }
+        let inode = self.get_inode(ino as u64)?;
+        let extents = self.extents_of_inode(&inode)?;
+        let block_size = self.super_block.get_block_size();
+
+        let read_block = |block: u64| -> Result<Option<Vec<u8>>, ExtfsError> {
+            let Some(e) = extents.iter().find(|e| {
+                block >= e.get_logical_block() && block < e.get_logical_block() + e.get_len() as u64
+            }) else {
+                return Ok(None);
+            };
+            let mut buf = vec![0; block_size as usize];
+            let pos = block_pos(
+                e.get_block_loc() + block - e.get_logical_block(),
+                block_size,
+                0,
+            )?;
+            read_exact_at(self.cursor(), pos, &mut buf, "journal block")?;
+            Ok(Some(buf))
+        };
+
+        let raw = read_block(0)?.ok_or(ExtfsError::UnmappedBlock {
+            ino: ino as u64,
+            block: 0,
+        })?;
+        JournalSuperBlock::from_bytes(&raw)?.transactions(read_block)
+    }
+
+    /// Given a path, query the file system to get information about a file, directory, etc
+    ///
+    /// Only the directories along the path are read, the extent tree of the file itself isn't,
+    /// so its cost doesn't depend on how fragmented the file is. Strict mode is the exception,
+    /// it verifies the extent tree of every inode the first time it reads it.
+    pub fn metadata<P: AsRef<Path>>(&self, path: P) -> Result<Metadata, ExtfsError> {
+        let i = self.get_inode_by_path(path.as_ref())?;
+        Ok(Metadata::new(i))
+    }
+
+    /// Resolve a path once, returning a handle that can be stat-ed and read without
+    /// walking the directory tree again.
+    pub fn entry<P: AsRef<Path>>(&mut self, path: P) -> Result<Entry<'_, R>, ExtfsError> {
+        let i = self.get_inode_by_path(path.as_ref())?;
+        Ok(Entry::new(self, path.as_ref(), i))
+    }
+
+    /// Attempts to open a file in read-only mode.
+    pub fn open<P: AsRef<Path>>(self, path: P) -> Result<File<R>, ExtfsError> {
+        let i = self.get_inode_by_path(path.as_ref())?;
+        if !i.is_regular() {
+            return Err(ExtfsError::IsNotRegular(path.as_ref().to_path_buf()));
+        }
+        let block_size = self.super_block.get_block_size();
+
+        i.read_file(block_size, self.into_reader())
+    }
+}
+
+/// Append the names and `..` of a path to `pending`, `path` is the whole path for errors.
+fn push_components(
+    pending: &mut VecDeque<String>,
+    components: &Path,
+    path: &Path,
+) -> Result<(), ExtfsError> {
+    for component in components.components() {
+        match component {
+            Component::Normal(name) => {
+                let name = name
+                    .to_str()
+                    .ok_or(ExtfsError::InvalidPath(path.to_path_buf()))?;
+                pending.push_back(name.to_string());
+            }
+            Component::ParentDir => pending.push_back("..".to_string()),
+            Component::Prefix(_) | Component::RootDir | Component::CurDir => {}
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs::File,
+        io::{BufReader, Cursor, Read, Seek},
+        path::{Path, PathBuf},
+        time::{Duration, UNIX_EPOCH},
+    };
+
+    use crate::{
+        constants::INO_ROOT, BlkidInfo, BlockGroupInfo, ChecksumKind, CreatorOs, DataRun,
+        DirCookie, DirEntryInfo, ExtfsError, FileType, Metadata, Provenance, SpecialInode,
+    };
+
+    use super::FileSystem;
+
+    fn new_fs() -> FileSystem<BufReader<File>> {
+        let file = File::open("testdata/test.ext4").unwrap();
+        let reader = BufReader::new(file);
+        FileSystem::from_reader(reader).unwrap()
+    }
+
+    #[test]
+    fn test_send_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+
+        type R = BufReader<File>;
+        assert_send_sync::<FileSystem<R>>();
+        assert_send_sync::<crate::File<R>>();
+        assert_send_sync::<crate::File<&mut R>>();
+        assert_send_sync::<crate::ReadDir<R>>();
+        assert_send_sync::<crate::WalkDir<'_, R>>();
+        assert_send_sync::<crate::Entry<'_, R>>();
+        assert_send_sync::<crate::Metadata>();
+
+        // scan images in parallel
+        let handles: Vec<_> = ["testdata/test.ext4", "testdata/frag.ext4"]
+            .into_iter()
+            .map(|path| {
+                let fs =
+                    FileSystem::from_reader(BufReader::new(File::open(path).unwrap())).unwrap();
+                std::thread::spawn(move || fs.walk_dir("/").unwrap().count())
+            })
+            .collect();
+        for h in handles {
+            assert!(h.join().unwrap() > 0);
+        }
+
+        // read one image from several threads while walking it
+        let fs = new_fs();
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    for x in fs.files().unwrap() {
+                        let (path, m) = x.unwrap();
+                        assert_eq!(fs.read(&path).unwrap().len() as u64, m.len());
+                    }
+                });
+            }
+        });
+    }
+
+    #[test]
+    fn test_raw_inode() {
+        let data = std::fs::read("testdata/test.ext4").unwrap();
+        let fs = FileSystem::from_bytes(&data).unwrap();
+
+        // hello.txt is the 12th inode of the table at block 50
+        let raw = fs.raw_inode(12).unwrap();
+        assert_eq!(raw.len(), 128);
+        assert_eq!(raw, &data[50 * 1024 + 11 * 128..50 * 1024 + 12 * 128]);
+        // i_size_lo
+        assert_eq!(&raw[4..8], &6u32.to_le_bytes());
+        assert!(fs.raw_inode(0).is_err());
+    }
+
+    #[test]
+    fn test_get_inode() {
+        let fs = new_fs();
+        println!("fs: {:?}", fs);
+
+        let block_size = fs.super_block.get_block_size();
+
+        let inode = fs.get_inode(INO_ROOT).unwrap();
+        let extents = inode.extents(block_size, fs.cursor()).unwrap();
+        println!("root inode: {:?} \n extents: {:?}", inode, extents);
+    }
+
+    #[test]
+    fn test_read_dir() {
+        let fs = new_fs();
+
+        let rd = fs.read_dir("/dir1").unwrap();
+        for x in rd {
+            println!("{}", x.unwrap().get_name_str());
+        }
+
+        let fs = new_fs();
+        let dots: Vec<_> = fs
+            .read_dir("/dir1")
+            .unwrap()
+            .with_dots(true)
+            .take(2)
+            .map(|x| {
+                let e = x.unwrap();
+                (e.get_name_str(), e.get_ino())
+            })
+            .collect();
+        assert_eq!(
+            dots,
+            [(".".to_string(), Some(13)), ("..".to_string(), Some(2))]
+        );
+    }
+
+    #[test]
+    fn test_read_dir_infos() {
+        let fs = new_fs();
+
+        let infos: Vec<_> = fs
+            .read_dir("/dir1")
+            .unwrap()
+            .infos()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(
+            infos,
+            [
+                DirEntryInfo {
+                    name: "dir11".to_string(),
+                    ino: 15,
+                    file_type: Some(FileType::Directory)
+                },
+                DirEntryInfo {
+                    name: "dir12".to_string(),
+                    ino: 16,
+                    file_type: Some(FileType::Directory)
+                },
+                DirEntryInfo {
+                    name: "world.txt".to_string(),
+                    ino: 17,
+                    file_type: Some(FileType::Regular)
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_list_dir() {
+        let f = File::open("testdata/test.ext4").unwrap();
+        let fs = FileSystem::from_reader(BufReader::new(f)).unwrap();
+
+        let mut infos = fs.list_dir("/dir1").unwrap();
+        infos.sort_by(|a, b| a.name.cmp(&b.name));
+        assert_eq!(
+            infos,
+            [
+                DirEntryInfo {
+                    name: "dir11".to_string(),
+                    ino: 15,
+                    file_type: Some(FileType::Directory)
+                },
+                DirEntryInfo {
+                    name: "dir12".to_string(),
+                    ino: 16,
+                    file_type: Some(FileType::Directory)
+                },
+                DirEntryInfo {
+                    name: "world.txt".to_string(),
+                    ino: 17,
+                    file_type: Some(FileType::Regular)
+                },
+            ]
+        );
+
+        assert_eq!(fs.list_dir("/").unwrap().len(), 7);
+        assert!(matches!(
+            fs.list_dir("/hello.txt"),
+            Err(ExtfsError::IsNotDirecotry(_))
+        ));
+    }
+
+    #[test]
+    fn test_read_empty_dir() {
+        let f = File::open("testdata/test.ext4").unwrap();
+        let fs = FileSystem::from_reader(BufReader::new(f)).unwrap();
+
+        assert!(fs.list_dir("/dir2").unwrap().is_empty());
+        assert!(fs.list_dir("/dir1/dir12").unwrap().is_empty());
+        assert_eq!(fs.walk_dir("/dir2").unwrap().count(), 0);
+        assert_eq!(fs.sub_dirs("/dir2").unwrap().count(), 0);
+        assert_eq!(fs.read_dir("/dir2").unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_sub_dirs() {
+        let fs = new_fs();
+
+        let dirs: Vec<_> = fs.sub_dirs("/dir1").unwrap().collect();
+        assert_eq!(dirs, [("dir11".to_string(), 15), ("dir12".to_string(), 16)]);
+
+        let dirs: Vec<_> = fs.sub_dirs_of_ino(INO_ROOT).unwrap().collect();
+        let names: Vec<_> = dirs.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, ["lost+found", "dir1", "dir2", "a1234567890"]);
+
+        assert!(fs.sub_dirs("/hello.txt").is_err());
+        assert!(fs.sub_dirs_of_ino(12).is_err());
+    }
+
+    #[test]
+    fn test_path_to_ino() {
+        let f = File::open("testdata/test.ext4").unwrap();
+        let fs = FileSystem::from_reader(BufReader::new(f)).unwrap();
+
+        assert_eq!(fs.path_to_ino("/").unwrap(), INO_ROOT);
+        assert_eq!(fs.path_to_ino("/hello.txt").unwrap(), 12);
+        assert_eq!(fs.path_to_ino("/dir1/world.txt").unwrap(), 17);
+        assert_eq!(fs.path_to_ino("/dir1/dir11/..").unwrap(), 13);
+        assert!(fs.path_to_ino("/missing").is_err());
+
+        let ino = fs.path_to_ino("/dir1/world.txt").unwrap();
+        let inode = fs.inode(ino).unwrap();
+        assert_eq!(fs.read_from_inode(&inode).unwrap(), b"world\n");
+    }
+
+    #[test]
+    fn test_path_separators() {
+        let f = File::open("testdata/test.ext4").unwrap();
+        let fs = FileSystem::from_reader(BufReader::new(f)).unwrap();
+
+        assert_eq!(fs.path_to_ino("//dir1//world.txt").unwrap(), 17);
+        assert_eq!(fs.path_to_ino("/./dir1/./world.txt").unwrap(), 17);
+        assert_eq!(fs.path_to_ino("/dir1/").unwrap(), 13);
+        assert_eq!(fs.path_to_ino("/dir1/.").unwrap(), 13);
+        assert_eq!(fs.path_to_ino("//").unwrap(), INO_ROOT);
+
+        for path in ["/hello.txt/", "/hello.txt/.", "/dir1/world.txt//"] {
+            assert!(
+                matches!(
+                    fs.path_to_ino(path),
+                    Err(ExtfsError::IsNotDirecotry(p)) if p == std::path::Path::new(path)
+                ),
+                "{}",
+                path
+            );
+        }
+        assert!(matches!(
+            fs.path_to_ino("/hello.txt/x"),
+            Err(ExtfsError::IsNotDirecotry(_))
+        ));
+    }
+
+    #[test]
+    fn test_extents_of() {
+        let f = File::open("testdata/frag.ext4").unwrap();
+        let fs = FileSystem::from_reader(BufReader::new(f)).unwrap();
+
+        let extents = fs.extents_of("/big.bin").unwrap();
+        assert_eq!(extents.len(), 11);
+        assert_eq!(extents[0].get_logical_block(), 0);
+        let blocks: u64 = extents.iter().map(|e| e.get_len() as u64).sum();
+        assert_eq!(blocks, 21);
+        assert!(extents.iter().all(|e| !e.is_uninit()));
+        // every extent is 2 blocks or less, none of them are adjacent
+        let stats = fs.fragmentation("/big.bin").unwrap();
+        assert_eq!(stats.extents, 11);
+        assert_eq!(stats.gaps, 10);
+        assert_eq!(stats.largest_run, 2);
+        assert_eq!(fs.fragmentation("/f1").unwrap().gaps, 0);
+
+        let f = File::open("testdata/sparse.ext4").unwrap();
+        let fs = FileSystem::from_reader(BufReader::new(f)).unwrap();
+
+        let extents = fs.extents_of("/unwritten.bin").unwrap();
+        assert!(extents.iter().any(|e| e.is_uninit()));
+        assert_eq!(
+            fs.open_ref("/unwritten.bin".as_ref())
+                .unwrap()
+                .extents()
+                .len(),
+            extents.len()
+        );
+    }
+
+    #[test]
+    fn test_set_root() {
+        let mut fs = new_fs();
+        assert_eq!(fs.root(), INO_ROOT);
+        fs.set_root_path("/dir1").unwrap();
+        assert_eq!(fs.root(), 13);
+        assert_eq!(fs.path_to_ino("/").unwrap(), 13);
+        assert_eq!(fs.read("/world.txt").unwrap(), b"world\n");
+        assert_eq!(fs.read("/dir12/../world.txt").unwrap(), b"world\n");
+        assert!(fs.metadata("/dir12").unwrap().is_dir());
+        assert!(matches!(
+            fs.metadata("/../hello.txt"),
+            Err(ExtfsError::UnexpectedParentDir(_))
+        ));
+        assert_eq!(
+            fs.resolve_many(&["/world.txt"])[0].as_ref().unwrap(),
+            &fs.path_to_ino("/world.txt").unwrap()
+        );
+
+        assert!(matches!(
+            fs.set_root(12),
+            Err(ExtfsError::InodeIsNotDirectory(12))
+        ));
+        fs.set_root(INO_ROOT).unwrap();
+        assert_eq!(fs.read("/hello.txt").unwrap(), b"hello\n");
+
+        // absolute symlinks stay in the new root: /a/b/up -> ../c, /link_abs -> /a/b
+        let mut fs = FileSystem::from_path("testdata/symlink.ext4").unwrap();
+        let c = fs.path_to_ino("/a/c").unwrap();
+        fs.set_root_path("/a").unwrap();
+        assert_eq!(fs.path_to_ino("/b/up/").unwrap(), c);
+        assert!(fs.walk_dir("/").unwrap().all(|x| x.is_ok()));
+    }
+
+    #[test]
+    fn test_resolve_many() {
+        let fs = new_fs();
+        let paths = [
+            "/dir1/world.txt",
+            "/hello.txt",
+            "/dir1/missing",
+            "/dir1/dir12",
+            "relative",
+            "/dir1/",
+            "/",
+            "hello.txt",
+            "/missing/file",
+            "/hello.txt/file",
+        ];
+        let results = fs.resolve_many(&paths);
+        assert_eq!(results.len(), paths.len());
+        for (path, result) in paths.iter().zip(&results) {
+            match fs.path_to_ino(path) {
+                Ok(ino) => assert_eq!(*result.as_ref().unwrap(), ino, "{path}"),
+                // errors name the path itself, not its parent
+                Err(e) => assert_eq!(
+                    result.as_ref().unwrap_err().to_string(),
+                    e.to_string(),
+                    "{path}"
+                ),
+            }
+        }
+        assert!(matches!(
+            &results[2],
+            Err(ExtfsError::NoSuchFileOrDirectory(p)) if p == Path::new("/dir1/missing")
+        ));
+        assert_eq!(*results[1].as_ref().unwrap(), 12);
+        assert_eq!(*results[6].as_ref().unwrap(), INO_ROOT);
+
+        // symlinks to directories in the middle are followed, the last one isn't
+        let fs = FileSystem::from_path("testdata/symlink.ext4").unwrap();
+        let paths = [
+            Path::new("/link_abs"),
+            Path::new("/a/b/up"),
+            Path::new("/link_abs/up"),
+        ];
+        for (path, result) in paths.iter().zip(fs.resolve_many(&paths)) {
+            assert_eq!(result.unwrap(), fs.path_to_ino(path).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_read_checked() {
+        let mut data = std::fs::read("testdata/test.ext4").unwrap();
+        let fs = FileSystem::from_bytes(&data).unwrap();
+        // flip a byte of i_atime of /hello.txt, the inode table starts at block 50
+        let pos = 50 * 1024 + 11 * fs.inode_size() as usize + 0x8;
+        data[pos] ^= 0xFF;
+
+        let mut fs = FileSystem::from_bytes(&data).unwrap();
+        assert_eq!(fs.read("/hello.txt").unwrap(), b"hello\n");
+        assert!(matches!(
+            fs.read_checked("/hello.txt"),
+            Err(ExtfsError::InodeChecksumMismatch(12))
+        ));
+        assert_eq!(fs.read_checked("/dir1/world.txt").unwrap(), b"world\n");
+        // other reads stay lenient
+        assert_eq!(fs.read("/hello.txt").unwrap(), b"hello\n");
+    }
+
+    #[test]
+    fn test_read_recoverable() {
+        let data = std::fs::read("testdata/frag.ext4").unwrap();
+        let fs = FileSystem::from_bytes(&data).unwrap();
+        let expected = fs.read("/big.bin").unwrap();
+        let (all, err) = fs.read_recoverable("/big.bin");
+        assert_eq!(all, expected);
+        assert!(err.is_none());
+        let runs = fs.data_location("/big.bin").unwrap();
+
+        // the 3rd extent is unreadable
+        let bad = runs[2].physical.unwrap();
+        let reader = FailingReader {
+            inner: Cursor::new(data),
+            fail: bad..bad + 1024,
+        };
+        let fs = FileSystem::from_reader(reader).unwrap();
+        let (partial, err) = fs.read_recoverable("/big.bin");
+        assert_eq!(partial, &expected[..runs[2].logical as usize]);
+        assert!(matches!(err, Some(ExtfsError::Io(_))));
+
+        let (partial, err) = fs.read_recoverable("/missing");
+        assert!(partial.is_empty());
+        assert!(matches!(err, Some(ExtfsError::NoSuchFileOrDirectory(_))));
+    }
+
+    #[test]
+    fn test_file_by_ref() {
+        let file = File::open("testdata/frag.ext4").unwrap();
+        let fs = FileSystem::from_reader(BufReader::new(file)).unwrap();
+        let data = fs.read("/big.bin").unwrap();
+
+        // `Read` is implemented for `&mut File` by the blanket impl
+        let mut f = fs.open("/big.bin").unwrap();
+        let mut head = Vec::new();
+        f.by_ref().take(10).read_to_end(&mut head).unwrap();
+        assert_eq!(head, &data[..10]);
+        let mut rest = Vec::new();
+        std::io::copy(&mut f.by_ref(), &mut rest).unwrap();
+        assert_eq!(rest, &data[10..]);
+        assert_eq!(f.by_ref().read(&mut [0; 1]).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_read_eofblocks() {
+        // 3 blocks of data, then shrink i_size and set EXT4_EOFBLOCKS_FL like a file whose
+        // preallocated blocks past the end were written
+        let data: Vec<u8> = (0..3072).map(|i| (i % 251) as u8 + 1).collect();
+        let mut image = crate::testutil::ImageBuilder::new()
+            .file("eof.bin", &data)
+            .build();
+        // inode 12 in the inode table at block 5
+        let inode = 5 * 1024 + 11 * 128;
+        image[inode + 0x4..inode + 0x8].copy_from_slice(&100u32.to_le_bytes());
+        image[inode + 0x20..inode + 0x24].copy_from_slice(&(0x8_0000u32 | 0x40_0000).to_le_bytes());
+        let fs = crate::testutil::from_vec(image).unwrap();
+
+        assert_eq!(fs.read("/eof.bin").unwrap(), &data[..100]);
+        assert_eq!(fs.read_range("/eof.bin", 50, 4096).unwrap(), &data[50..100]);
+        assert!(fs.read_range("/eof.bin", 1024, 10).unwrap().is_empty());
+        assert_eq!(fs.read_head("/eof.bin", 4096).unwrap(), &data[..100]);
+        let mut out = Vec::new();
+        assert_eq!(fs.copy_to("/eof.bin", &mut out).unwrap(), 100);
+        assert_eq!(out, &data[..100]);
+
+        let mut f = fs.open("/eof.bin").unwrap();
+        f.seek(std::io::SeekFrom::Start(2048)).unwrap();
+        let mut buf = Vec::new();
+        assert_eq!(f.read_to_end(&mut buf).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_file_read_at() {
+        let data: Vec<u8> = (0..3000).map(|i| (i % 251) as u8).collect();
+        let image = crate::testutil::ImageBuilder::new()
+            .file("records", &data)
+            .build();
+        let fs = crate::testutil::from_vec(image).unwrap();
+        let mut f = fs.open("/records").unwrap();
+
+        // across the first block boundary
+        let mut record = [0; 16];
+        f.read_exact_at(&mut record, 1020).unwrap();
+        assert_eq!(record, data[1020..1036]);
+        assert_eq!(f.read_at(&mut record, 2990).unwrap(), 10);
+        assert_eq!(record[..10], data[2990..]);
+        assert_eq!(f.read_at(&mut record, 5000).unwrap(), 0);
+        let err = f.read_exact_at(&mut record, 2990).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+        // the position isn't moved
+        assert_eq!(f.stream_position().unwrap(), 0);
+
+        let (mut header, mut body) = ([0; 8], vec![0; 2000]);
+        let mut bufs = [
+            std::io::IoSliceMut::new(&mut header),
+            std::io::IoSliceMut::new(&mut body),
+        ];
+        f.seek(std::io::SeekFrom::Start(1000)).unwrap();
+        assert_eq!(f.read_vectored(&mut bufs).unwrap(), 2000);
+        assert_eq!(header, data[1000..1008]);
+        assert_eq!(body[..1992], data[1008..]);
+        assert_eq!(f.stream_position().unwrap(), 3000);
+    }
+
+    #[test]
+    fn test_owner_per_creator_os() {
+        let mut image = crate::testutil::ImageBuilder::new()
+            .file("owned", b"x")
+            .build();
+        // i_uid and i_gid, then l_i_uid_high and l_i_gid_high in osd2
+        let inode = 5 * 1024 + 11 * 128;
+        image[inode + 0x2..inode + 0x4].copy_from_slice(&1000u16.to_le_bytes());
+        image[inode + 0x18..inode + 0x1A].copy_from_slice(&100u16.to_le_bytes());
+        image[inode + 0x78..inode + 0x7A].copy_from_slice(&1u16.to_le_bytes());
+        image[inode + 0x7A..inode + 0x7C].copy_from_slice(&2u16.to_le_bytes());
+
+        let fs = crate::testutil::from_vec(image.clone()).unwrap();
+        let m = fs.metadata("/owned").unwrap();
+        assert_eq!((m.uid32(), m.gid32()), (0x1_03E8, 0x2_0064));
+        assert_eq!((m.uid(), m.gid()), (1000, 100));
+
+        // s_creator_os, the Hurd has the upper bits at the same place
+        image[1024 + 0x48..1024 + 0x4C].copy_from_slice(&1u32.to_le_bytes());
+        let fs = crate::testutil::from_vec(image.clone()).unwrap();
+        assert_eq!(fs.creator_os(), CreatorOs::Hurd);
+        let m = fs.metadata("/owned").unwrap();
+        assert_eq!((m.uid32(), m.gid32()), (0x1_03E8, 0x2_0064));
+
+        // FreeBSD doesn't use osd2
+        image[1024 + 0x48..1024 + 0x4C].copy_from_slice(&3u32.to_le_bytes());
+        let fs = crate::testutil::from_vec(image).unwrap();
+        let m = fs.metadata("/owned").unwrap();
+        assert_eq!((m.uid32(), m.gid32()), (1000, 100));
+    }
+
+    #[test]
+    fn test_data_location() {
+        let f = File::open("testdata/sparse.ext4").unwrap();
+        let fs = FileSystem::from_reader(BufReader::new(f)).unwrap();
+
+        let run = |logical, physical, len| DataRun {
+            logical,
+            physical,
+            len,
+        };
+        // a single block at 512K in a 1M file
+        assert_eq!(
+            fs.data_location("/sparse.bin").unwrap(),
+            [
+                run(0, None, 512 * 1024),
+                run(512 * 1024, Some(25 * 1024), 1024),
+                run(513 * 1024, None, 511 * 1024),
+            ]
+        );
+        assert_eq!(
+            fs.data_location("/unwritten.bin").unwrap(),
+            [run(0, Some(27 * 1024), 1024), run(1024, None, 3 * 1024)]
+        );
+        // blocks preallocated past the end aren't part of the file
+        assert_eq!(
+            fs.data_location("/prealloc.bin").unwrap(),
+            [run(0, Some(31 * 1024), 3)]
+        );
+        assert!(fs.data_location("/").is_err());
+
+        // the runs point to the data in the image
+        let image = std::fs::read("testdata/frag.ext4").unwrap();
+        let fs = FileSystem::from_bytes(&image).unwrap();
+        let mut data: Vec<u8> = Vec::new();
+        for r in fs.data_location("/big.bin").unwrap() {
+            let start = r.physical.unwrap() as usize;
+            data.extend(&image[start..start + r.len as usize]);
+        }
+        assert_eq!(data, fs.read("/big.bin").unwrap());
+
+        // data stored in the inode, spilling into the system.data attribute
+        let image = std::fs::read("testdata/inline.ext4").unwrap();
+        let fs = FileSystem::from_bytes(&image).unwrap();
+        for path in ["/small.txt", "/spill.txt"] {
+            let runs = fs.data_location(path).unwrap();
+            let mut data: Vec<u8> = Vec::new();
+            for r in &runs {
+                assert_eq!(r.logical, data.len() as u64);
+                let start = r.physical.unwrap() as usize;
+                data.extend(&image[start..start + r.len as usize]);
+            }
+            assert_eq!(data, fs.read(path).unwrap());
+        }
+        assert_eq!(fs.data_location("/small.txt").unwrap().len(), 1);
+        assert_eq!(fs.data_location("/spill.txt").unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_read_dir_offsets() {
+        let image = std::fs::read("testdata/inline.ext4").unwrap();
+        let fs = FileSystem::from_bytes(&image).unwrap();
+        let extents = fs.extents_of("/spilldir").unwrap();
+        assert_eq!(extents.len(), 1);
+        let base = (extents[0].get_block_loc() * 1024) as usize;
+
+        let rd = fs.read_dir("/spilldir").unwrap();
+        let mut names = Vec::new();
+        for x in rd.offsets() {
+            let (e, offset) = x.unwrap();
+            // the entry is at the offset in the directory data
+            let pos = base + offset as usize;
+            assert_eq!(
+                u32::from_le_bytes(image[pos..pos + 4].try_into().unwrap()),
+                e.get_ino().unwrap()
+            );
+            let name = e.get_name_str();
+            assert_eq!(&image[pos + 8..pos + 8 + name.len()], name.as_bytes());
+            names.push(name);
+        }
+        assert_eq!(names.len(), 5);
+
+        // entries of an inline directory are after the parent inode number
+        let fs = FileSystem::from_bytes(&image).unwrap();
+        let offsets: Vec<u64> = fs
+            .read_dir("/smalldir")
+            .unwrap()
+            .offsets()
+            .map(|x| x.unwrap().1)
+            .collect();
+        assert_eq!(offsets[0], 4);
+        assert_eq!(offsets.len(), 2);
+
+        // only the parent is stored, `.` and `..` are made up
+        let fs = FileSystem::from_bytes(&image).unwrap();
+        let ino = fs.path_to_ino("/smalldir").unwrap();
+        let dots: Vec<_> = fs
+            .read_dir("/smalldir")
+            .unwrap()
+            .with_dots(true)
+            .offsets()
+            .take(2)
+            .map(|x| {
+                let (e, offset) = x.unwrap();
+                (e.get_name_str(), e.get_ino(), offset)
+            })
+            .collect();
+        assert_eq!(
+            dots,
+            [
+                (".".to_string(), Some(ino as u32), 0),
+                ("..".to_string(), Some(2), 2)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_read_dir_htree() {
+        // /big holds 4000 hard links of /big/target, indexed by a two level htree
+        let f = File::open("testdata/htree.ext4").unwrap();
+        let fs = FileSystem::from_reader_strict(BufReader::new(f)).unwrap();
+
+        let ino = fs.path_to_ino("/big").unwrap();
+        assert!(fs.inode(ino).unwrap().is_indexed());
+        assert!(fs.metadata("/big").unwrap().is_indexed_dir());
+        assert!(!fs.metadata("/").unwrap().is_indexed_dir());
+        let target = fs.path_to_ino("/big/target").unwrap();
+
+        let infos: Vec<DirEntryInfo> = fs
+            .read_dir("/big")
+            .unwrap()
+            .infos()
+            .map(|x| x.unwrap())
+            .collect();
+        assert_eq!(infos.len(), 4001);
+        assert!(infos.iter().all(|x| x.ino == target));
+
+        let mut names: Vec<&str> = infos.iter().map(|x| x.name.as_str()).collect();
+        names.sort();
+        names.dedup();
+        assert_eq!(names.len(), 4001);
+        assert_eq!(
+            names[3999],
+            "a_hard_link_with_a_rather_long_name_to_fill_blocks_quickly_4000"
+        );
+        assert_eq!(names[4000], "target");
+
+        // `.` and `..` are before the index in the dx_root block
+        let fs =
+            FileSystem::from_reader(BufReader::new(File::open("testdata/htree.ext4").unwrap()))
+                .unwrap();
+        let entries: Vec<_> = fs
+            .read_dir("/big")
+            .unwrap()
+            .with_dots(true)
+            .map(|x| x.unwrap())
+            .collect();
+        assert_eq!(entries.len(), 4003);
+        let dots: Vec<_> = entries[..2]
+            .iter()
+            .map(|e| (e.get_name_str(), e.get_ino()))
+            .collect();
+        assert_eq!(
+            dots,
+            [
+                (".".to_string(), Some(ino as u32)),
+                ("..".to_string(), Some(2))
+            ]
+        );
+        assert!(!entries[2..].iter().any(|e| e.is_dot() || e.is_dotdot()));
+    }
+
+    #[test]
+    fn test_dir_block_map() {
+        let f = File::open("testdata/htree.ext4").unwrap();
+        let mut fs = FileSystem::from_reader(BufReader::new(f)).unwrap();
+        let map = fs.dir_block_map("/big").unwrap();
+        assert_eq!(map.len() as u64 * 1024, fs.metadata("/big").unwrap().len());
+        assert!(map.iter().enumerate().all(|(i, (l, _))| *l == i as u64));
+
+        // every block referenced by the root of the htree is mapped
+        let root_block = map[0].1;
+        let mut block = vec![0; 1024];
+        let reader = fs.reader_mut();
+        reader
+            .seek(std::io::SeekFrom::Start(root_block * 1024))
+            .unwrap();
+        reader.read_exact(&mut block).unwrap();
+        let root = crate::htree::DxRoot::from_block(0, &block, false).unwrap();
+        let entries = crate::htree::dx_entries(0, &block, root.entries_offset).unwrap();
+        assert!(entries.len() > 1);
+        assert!(entries
+            .iter()
+            .all(|(_, logical)| (*logical as usize) < map.len()));
+
+        let f = File::open("testdata/inline.ext4").unwrap();
+        let fs = FileSystem::from_reader(BufReader::new(f)).unwrap();
+        assert!(fs.dir_block_map("/smalldir").unwrap().is_empty());
+        assert!(!fs.dir_block_map("/spilldir").unwrap().is_empty());
+        assert!(matches!(
+            fs.dir_block_map("/small.txt"),
+            Err(ExtfsError::IsNotDirecotry(_))
+        ));
+    }
+
+    #[test]
+    fn test_htree_lookup() {
+        let f = File::open("testdata/htree.ext4").unwrap();
+        let fs = FileSystem::from_reader(BufReader::new(f)).unwrap();
+        let ino = fs.path_to_ino("/big").unwrap();
+        let dir = fs.inode(ino).unwrap();
+        let target = fs.path_to_ino("/big/target").unwrap();
+
+        // every name is found through the index, without falling back to a scan
+        let names: Vec<String> = fs
+            .read_dir_ref(&dir)
+            .unwrap()
+            .infos()
+            .map(|x| x.unwrap().name)
+            .collect();
+        assert_eq!(names.len(), 4001);
+        for name in &names {
+            let e = fs.htree_lookup(ino, &dir, name).unwrap().unwrap();
+            assert_eq!(e.get_ino(), Some(target as u32));
+        }
+        assert!(fs.htree_lookup(ino, &dir, "missing").unwrap().is_none());
+        assert!(matches!(
+            fs.metadata("/big/missing"),
+            Err(ExtfsError::NoSuchFileOrDirectory(_))
+        ));
+
+        // a broken index falls back to a linear scan
+        let mut data = std::fs::read("testdata/htree.ext4").unwrap();
+        let extents = fs.extents_of_inode(&dir).unwrap();
+        let root = super::physical_block(&extents, 0).unwrap() as usize * 1024;
+        // indirect_levels
+        data[root + 30] = 7;
+        let fs = FileSystem::from_bytes(&data).unwrap();
+        assert!(matches!(
+            fs.htree_lookup(ino, &dir, "target"),
+            Err(ExtfsError::InvalidHtree { .. })
+        ));
+        assert_eq!(fs.path_to_ino("/big/target").unwrap(), target);
+    }
+
+    #[test]
+    fn test_read_dir_from() {
+        let f = File::open("testdata/htree.ext4").unwrap();
+        let mut fs = FileSystem::from_reader(BufReader::new(f)).unwrap();
+
+        let ino = fs.path_to_ino("/big").unwrap();
+        let inode = fs.inode(ino).unwrap();
+        let block_size = fs.block_size();
+        let all: Vec<String> = fs
+            .read_dir_ref(&inode)
+            .unwrap()
+            .infos()
+            .map(|x| x.unwrap().name)
+            .collect();
+
+        // page through the directory like a FUSE readdir, 100 entries per call
+        let mut paged = Vec::new();
+        let mut cookie = DirCookie::default();
+        loop {
+            let mut rd = inode
+                .read_dir_from(cookie, block_size, true, fs.reader_mut())
+                .unwrap();
+            let page: Vec<String> = rd
+                .by_ref()
+                .take(100)
+                .map(|x| DirEntryInfo::from_entry(&x.unwrap()).unwrap().name)
+                .collect();
+            if page.is_empty() {
+                break;
+            }
+            paged.extend(page);
+            cookie = DirCookie::from_u64(rd.position().as_u64());
+        }
+        assert_eq!(paged, all);
+    }
+
+    #[test]
+    fn test_custom_root() {
+        let f = File::open("testdata/test.ext4").unwrap();
+        let fs = FileSystem::from_reader(BufReader::new(f)).unwrap();
+
+        let dir1 = fs.path_to_ino("/dir1").unwrap();
+        assert_eq!(fs.path_to_ino_from(dir1, "/").unwrap(), dir1);
+        assert_eq!(fs.path_to_ino_from(dir1, "/world.txt").unwrap(), 17);
+        assert_eq!(fs.path_to_ino_from(dir1, "/dir11/..").unwrap(), dir1);
+        assert!(matches!(
+            fs.path_to_ino_from(dir1, "/.."),
+            Err(ExtfsError::UnexpectedParentDir(_))
+        ));
+        assert!(matches!(
+            fs.path_to_ino_from(dir1, "/hello.txt"),
+            Err(ExtfsError::NoSuchFileOrDirectory(_))
+        ));
+
+        let mut names: Vec<String> = fs
+            .read_dir_from_ino(dir1)
+            .unwrap()
+            .infos()
+            .map(|x| x.unwrap().name)
+            .collect();
+        names.sort();
+        assert_eq!(names, ["dir11", "dir12", "world.txt"]);
+
+        let f = File::open("testdata/test.ext4").unwrap();
+        let fs = FileSystem::from_reader(BufReader::new(f)).unwrap();
+        assert!(matches!(
+            fs.read_dir_from_ino(12),
+            Err(ExtfsError::InodeIsNotDirectory(12))
+        ));
+    }
+
+    #[test]
+    fn test_files() {
+        let fs = FileSystem::from_path("testdata/symlink.ext4").unwrap();
+        let files: Vec<(PathBuf, Metadata)> = fs.files().unwrap().map(|x| x.unwrap()).collect();
+        assert!(!files.is_empty());
+        assert!(files.iter().all(|(_, m)| m.is_file()));
+        // symlinks to files aren't followed
+        assert!(!files.iter().any(|(p, _)| p == Path::new("/link_file")));
+
+        let mut expected: Vec<PathBuf> = fs
+            .walk_dir("/")
+            .unwrap()
+            .map(|x| x.unwrap())
+            .filter(|(_, m)| m.is_file())
+            .map(|(p, _)| p)
+            .collect();
+        expected.sort();
+        let mut paths: Vec<PathBuf> = files.into_iter().map(|(p, _)| p).collect();
+        paths.sort();
+        assert_eq!(paths, expected);
+    }
+
+    #[test]
+    fn test_walk_dir() {
+        let f = File::open("testdata/test.ext4").unwrap();
+        let fs = FileSystem::from_reader(BufReader::new(f)).unwrap();
+
+        let mut paths: Vec<String> = fs
+            .walk_dir("/")
+            .unwrap()
+            .map(|x| x.unwrap().0.to_str().unwrap().to_string())
+            .collect();
+        paths.sort();
+        let deepest = "/a1234567890/b1234567890/c1234567890/d1234567890/e1234567890/f1234567890";
+        assert_eq!(paths.len(), 17);
+        assert!(paths.contains(&format!("{}/test.txt", deepest)));
+        assert!(paths.contains(&"/dir1/dir11/world.txt.lnk".to_string()));
+
+        let walk: Vec<_> = fs.walk_dir("/dir1").unwrap().map(|x| x.unwrap()).collect();
+        assert_eq!(walk.len(), 4);
+        let (_, m) = walk
+            .iter()
+            .find(|(p, _)| p == std::path::Path::new("/dir1/world.txt"))
+            .unwrap();
+        assert_eq!(m.len(), 6);
+
+        let mut top: Vec<String> = fs
+            .walk_dir("/")
+            .unwrap()
+            .max_depth(1)
+            .map(|x| x.unwrap().0.to_str().unwrap().to_string())
+            .collect();
+        top.sort();
+        assert_eq!(
+            top,
+            [
+                "/a1234567890",
+                "/dir1",
+                "/dir2",
+                "/hello.txt",
+                "/hello.txt.lnk",
+                "/lost+found",
+                "/test.txt.lnk"
+            ]
+        );
+
+        assert!(fs.walk_dir("/hello.txt").is_err());
+
+        // a directory block is read once for all its entries
+        let root = fs.extents_of("/").unwrap()[0].get_block_loc() * 1024;
+        let reader = CountReads {
+            inner: Cursor::new(std::fs::read("testdata/test.ext4").unwrap()),
+            watched: root,
+            count: 0,
+        };
+        let mut fs = FileSystem::from_reader(reader).unwrap();
+        assert_eq!(fs.walk_dir("/").unwrap().max_depth(1).count(), 7);
+        assert_eq!(fs.reader_mut().count, 1);
+    }
+
+    #[test]
+    fn test_resolve_symlinks() {
+        // /link_abs -> /a/b, /link_rel -> a/b, /a/b/up -> ../c, /link_file -> /a/c/x,
+        // /loop1 -> loop2 -> loop1
+        let f = File::open("testdata/symlink.ext4").unwrap();
+        let fs = FileSystem::from_reader(BufReader::new(f)).unwrap();
+
+        let x = fs.path_to_ino("/a/c/x").unwrap();
+        let y = fs.path_to_ino("/a/b/y").unwrap();
+        let b = fs.path_to_ino("/a/b").unwrap();
+
+        assert_eq!(fs.path_to_ino("/a/b/../c/x").unwrap(), x);
+        assert_eq!(fs.path_to_ino("/link_abs/y").unwrap(), y);
+        assert_eq!(fs.path_to_ino("/link_rel/y").unwrap(), y);
+        // `..` applies to the target of the link, not to the link itself
+        assert_eq!(fs.path_to_ino("/link_abs/../c/x").unwrap(), x);
+        assert_eq!(fs.path_to_ino("/link_abs/../../a/b").unwrap(), b);
+        assert_eq!(fs.path_to_ino("/link_rel/up/x").unwrap(), x);
+        assert_eq!(
+            fs.path_to_ino("/link_abs/up/../b/up/../../a/c/x").unwrap(),
+            x
+        );
+        assert!(matches!(
+            fs.path_to_ino("/link_abs/../../.."),
+            Err(ExtfsError::UnexpectedParentDir(_))
+        ));
+
+        // the last component isn't followed, unless a directory is required
+        assert!(fs.metadata("/link_abs").unwrap().is_symlink());
+        assert_eq!(fs.path_to_ino("/link_abs/").unwrap(), b);
+        assert!(fs.metadata("/link_file").unwrap().is_symlink());
+        assert!(matches!(
+            fs.path_to_ino("/link_file/"),
+            Err(ExtfsError::IsNotDirecotry(_))
+        ));
+        assert_eq!(fs.read("/link_abs/up/x").unwrap(), b"x\n");
+
+        assert!(matches!(
+            fs.path_to_ino("/loop1/x"),
+            Err(ExtfsError::TooManySymlinks(_))
+        ));
+        assert!(matches!(
+            fs.path_to_ino("/long_missing/x"),
+            Err(ExtfsError::NoSuchFileOrDirectory(_))
+        ));
+
+        // relative targets resolve below a custom root too
+        let a = fs.path_to_ino("/a").unwrap();
+        assert_eq!(fs.path_to_ino_from(a, "/b/up/x").unwrap(), x);
+    }
+
+    #[test]
+    fn test_read_link() {
+        let fs = new_fs();
+
+        let p = fs.read_link("/hello.txt.lnk").unwrap();
+        assert_eq!("hello.txt", p.to_str().unwrap());
+
+        let p = fs.read_link("/test.txt.lnk").unwrap();
+        assert_eq!(
+            "a1234567890/b1234567890/c1234567890/d1234567890/e1234567890/f1234567890/test.txt",
+            p.to_str().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_read_link_raw() {
+        let fs = FileSystem::from_path("testdata/linkraw.ext4").unwrap();
+        // 60 bytes don't fit in i_block with the NUL, it's in a block
+        let p = fs.read_link("/len60").unwrap();
+        assert_eq!(p, PathBuf::from(format!("{}.txt", "x".repeat(56))));
+        assert_eq!(fs.read_link_raw("/len59").unwrap(), [b'y'; 59]);
+        let long = fs.read_link_raw("/long").unwrap();
+        assert_eq!(long.len(), 314);
+        assert_eq!(long, vec!["d".repeat(20); 15].join("/").as_bytes());
+
+        assert_eq!(fs.read_link_raw("/latin1").unwrap(), b"caf\xe9/\xff");
+        #[cfg(unix)]
+        {
+            use std::os::unix::ffi::OsStrExt;
+            let p = fs.read_link("/latin1").unwrap();
+            assert_eq!(p.as_os_str().as_bytes(), b"caf\xe9/\xff");
+        }
+        assert!(matches!(
+            fs.read_link_raw("/"),
+            Err(ExtfsError::IsNotSymlink(_))
+        ));
+    }
+
+    #[test]
+    fn test_read() {
+        let fs = new_fs();
+
+        let b = fs.read("/hello.txt").unwrap();
+        assert_eq!("hello\n", String::from_utf8_lossy(&b).to_string());
+    }
+
+    #[test]
+    fn test_extent_tree_depth() {
+        let file = File::open("testdata/frag.ext4").unwrap();
+        let fs = FileSystem::from_reader(BufReader::new(file)).unwrap();
+        // 11 extents don't fit in the 4 slots of the inode
+        assert_eq!(
+            fs.metadata("/big.bin").unwrap().extent_tree_depth(),
+            Some(1)
+        );
+        assert_eq!(fs.metadata("/f1").unwrap().extent_tree_depth(), Some(0));
+    }
+
+    #[test]
+    fn test_read_multiple_extents() {
+        let file = File::open("testdata/frag.ext4").unwrap();
+        let fs = FileSystem::from_reader(BufReader::new(file)).unwrap();
+
+        // 20 KiB + 123 bytes spread over 11 extents
+        let b = fs.read("/big.bin").unwrap();
+        let expected: Vec<u8> = (0..20 * 1024 + 123).map(|i| (i % 251) as u8).collect();
+        assert_eq!(b, expected);
+
+        let mut buf = Vec::new();
+        fs.open("/big.bin").unwrap().read_to_end(&mut buf).unwrap();
+        assert_eq!(buf, expected);
+    }
+
+    #[test]
+    fn test_read_sparse() {
+        let file = File::open("testdata/sparse.ext4").unwrap();
+        let fs = FileSystem::from_reader(BufReader::new(file)).unwrap();
+
+        // 1 MiB with "hello sparse\n" at 512 KiB
+        let mut expected = vec![0; 1024 * 1024];
+        expected[512 * 1024..512 * 1024 + 13].copy_from_slice(b"hello sparse\n");
+        assert_eq!(fs.read("/sparse.bin").unwrap(), expected);
+
+        let mut out = Vec::new();
+        assert_eq!(fs.copy_to("/sparse.bin", &mut out).unwrap(), 1024 * 1024);
+        assert_eq!(out, expected);
+
+        let b = fs.read_range("/sparse.bin", 512 * 1024 - 2, 8).unwrap();
+        assert_eq!(b, b"\0\0hello ");
+        let b = fs.read_range("/sparse.bin", 1024 * 1024 - 2, 8).unwrap();
+        assert_eq!(b, b"\0\0");
+
+        // "abc" followed by uninitialized extents
+        let mut expected = vec![0; 4096];
+        expected[..3].copy_from_slice(b"abc");
+        assert_eq!(fs.read("/unwritten.bin").unwrap(), expected);
+    }
+
+    #[test]
+    fn test_read_head() {
+        let f = File::open("testdata/frag.ext4").unwrap();
+        let fs = FileSystem::from_reader(BufReader::new(f)).unwrap();
+
+        assert_eq!(fs.read_head("/big.bin", 4).unwrap(), [0, 1, 2, 3]);
+        let head = fs.read_head("/big.bin", 3000).unwrap();
+        assert_eq!(head.len(), 3000);
+        assert_eq!(head[2999], (2999 % 251) as u8);
+        assert_eq!(
+            fs.read_head("/big.bin", 1 << 20).unwrap().len(),
+            20 * 1024 + 123
+        );
+        assert!(fs.read_head("/big.bin", 0).unwrap().is_empty());
+
+        // data stored in the inode
+        let f = File::open("testdata/inline.ext4").unwrap();
+        let fs = FileSystem::from_reader(BufReader::new(f)).unwrap();
+        assert_eq!(fs.read_head("/small.txt", 2).unwrap(), b"in");
+        assert_eq!(fs.read_range("/small.txt", 2, 3).unwrap(), b"lin");
+        assert_eq!(fs.read_range("/small.txt", 5, 100).unwrap(), b"e\n");
+        assert!(fs.read_range("/small.txt", 7, 1).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_read_from_inode() {
+        let mut fs = new_fs();
+
+        let inode = fs.inode(12).unwrap();
+        assert_eq!(fs.read_from_inode(&inode).unwrap(), b"hello\n");
+
+        let mut buf = String::new();
+        let mut f = fs.open_from_inode(&inode).unwrap();
+        f.seek(std::io::SeekFrom::Start(1)).unwrap();
+        f.read_to_string(&mut buf).unwrap();
+        assert_eq!(buf, "ello\n");
+
+        // the blocks of a directory aren't file data
+        let root = fs.inode(INO_ROOT).unwrap();
+        assert!(matches!(
+            fs.read_from_inode(&root),
+            Err(ExtfsError::InodeIsNotRegular(FileType::Directory))
+        ));
+        assert!(matches!(
+            fs.open_from_inode(&root).err().unwrap(),
+            ExtfsError::InodeIsNotRegular(FileType::Directory)
+        ));
+    }
+
+    #[test]
+    fn test_metadata() {
+        let fs = new_fs();
+
+        let m = fs.metadata("/hello.txt").unwrap();
+        println!(
+            "uid={} gid={} permissions={:o} len={} created={:?} accessed={:?} modified={:?}",
+            m.uid(),
+            m.gid(),
+            m.permissions(),
+            m.len(),
+            m.created(),
+            m.accessed(),
+            m.modified(),
+        );
+    }
+
+    #[test]
+    fn test_metadata_storage() {
+        let f = File::open("testdata/inline.ext4").unwrap();
+        let fs = FileSystem::from_reader(BufReader::new(f)).unwrap();
+
+        let m = fs.metadata("/small.txt").unwrap();
+        assert!(m.uses_inline_data());
+        assert!(!m.uses_extents());
+
+        let m = fs.metadata("/big.txt").unwrap();
+        assert!(!m.uses_inline_data());
+        assert!(m.uses_extents());
+
+        assert!(fs.metadata("/smalldir").unwrap().uses_inline_data());
+        assert!(fs.metadata("/spilldir").unwrap().uses_extents());
+    }
+
+    #[test]
+    fn test_read_inline_dir() {
+        let f = File::open("testdata/inline.ext4").unwrap();
+        let fs = FileSystem::from_reader_strict(BufReader::new(f)).unwrap();
+
+        let mut names: Vec<String> = fs
+            .list_dir("/smalldir")
+            .unwrap()
+            .into_iter()
+            .map(|x| x.name)
+            .collect();
+        names.sort();
+        assert_eq!(names, ["a", "b"]);
+        assert_eq!(fs.metadata("/smalldir/b").unwrap().len(), 2);
+
+        let walk: Vec<_> = fs
+            .walk_dir("/")
+            .unwrap()
+            .map(|x| x.unwrap().0.to_str().unwrap().to_string())
+            .collect();
+        assert!(walk.contains(&"/smalldir/a".to_string()));
+        assert!(walk.contains(&"/spilldir/entry_5".to_string()));
+
+        let ino = fs.path_to_ino("/smalldir").unwrap();
+        let names: Vec<String> = fs
+            .read_dir_from_ino(ino)
+            .unwrap()
+            .infos()
+            .map(|x| x.unwrap().name)
+            .collect();
+        assert_eq!(names.len(), 2);
+    }
+
+    #[test]
+    fn test_open() {
+        let fs = new_fs();
+
+        let mut f = fs.open("/hello.txt").unwrap();
+        let mut buf = String::new();
+        f.read_to_string(&mut buf).unwrap();
+        assert_eq!(buf, "hello\n");
+
+        buf.clear();
+        f.seek(std::io::SeekFrom::Start(2)).unwrap();
+        f.read_to_string(&mut buf).unwrap();
+        assert_eq!(buf, "llo\n");
+
+        buf.clear();
+        f.seek(std::io::SeekFrom::End(-2)).unwrap();
+        f.read_to_string(&mut buf).unwrap();
+        assert_eq!(buf, "o\n");
+
+        buf.clear();
+        f.seek(std::io::SeekFrom::Current(-1)).unwrap();
+        f.read_to_string(&mut buf).unwrap();
+        assert_eq!(buf, "\n");
+    }
+
+    #[test]
+    fn test_open_inline_data() {
+        let f = File::open("testdata/inline.ext4").unwrap();
+        let fs = FileSystem::from_reader(BufReader::new(f)).unwrap();
+
+        assert_eq!(fs.read_head("/small.txt", 4).unwrap(), b"inli");
+        assert_eq!(fs.read_head("/small.txt", 100).unwrap(), b"inline\n");
+
+        let mut f = fs.open("/small.txt").unwrap();
+        assert!(f.extents().is_empty());
+        let mut buf = String::new();
+        f.read_to_string(&mut buf).unwrap();
+        assert_eq!(buf, "inline\n");
+
+        buf.clear();
+        f.seek(std::io::SeekFrom::End(-3)).unwrap();
+        f.read_to_string(&mut buf).unwrap();
+        assert_eq!(buf, "ne\n");
+    }
+
+    #[test]
+    fn test_strict() {
+        let data = std::fs::read("testdata/test.ext4").unwrap();
+        let fs = FileSystem::from_reader_strict(Cursor::new(&data)).unwrap();
+        assert_eq!(fs.read("/hello.txt").unwrap(), b"hello\n");
+        assert_eq!(fs.read_dir("/dir1").unwrap().count(), 3);
+
+        // with an extent tree block
+        let file = File::open("testdata/frag.ext4").unwrap();
+        let fs = FileSystem::from_reader_strict(BufReader::new(file)).unwrap();
+        assert_eq!(fs.read("/big.bin").unwrap().len(), 20 * 1024 + 123);
+
+        // corrupt the super block
+        let mut bad = data.clone();
+        bad[1024 + 0x200] ^= 0xFF;
+        assert!(FileSystem::from_reader(Cursor::new(&bad)).is_ok());
+        assert!(matches!(
+            FileSystem::from_reader_strict(Cursor::new(&bad)),
+            Err(ExtfsError::SuperBlockChecksumMismatch)
+        ));
+
+        // corrupt mtime of /hello.txt
+        let fs = FileSystem::from_reader(Cursor::new(&data)).unwrap();
+        let pos = (fs.block_group_descriptors[0].get_inode_table_loc() * 1024 + 11 * 128) as usize;
+        let mut bad = data.clone();
+        bad[pos + 0x10] ^= 0xFF;
+        let fs = FileSystem::from_reader(Cursor::new(&bad)).unwrap();
+        assert_eq!(
+            fs.metadata("/hello.txt").unwrap().checksum_valid(),
+            Some(false)
+        );
+        assert_eq!(fs.metadata("/dir1").unwrap().checksum_valid(), Some(true));
+        let fs = FileSystem::from_reader_strict(Cursor::new(&bad)).unwrap();
+        assert!(matches!(
+            fs.metadata("/hello.txt"),
+            Err(ExtfsError::InodeChecksumMismatch(12))
+        ));
+
+        // corrupt the name of /dir1/dir11, which is stored in block 1092
+        let mut bad = data.clone();
+        bad[1092 * 1024 + 24 + 8] ^= 0xFF;
+        let fs = FileSystem::from_reader_strict(Cursor::new(&bad)).unwrap();
+        assert!(matches!(
+            fs.read_dir("/dir1"),
+            Err(ExtfsError::DirBlockChecksumMismatch {
+                ino: 13,
+                block: 1092
+            })
+        ));
+    }
+
+    #[test]
+    fn test_dir_size_high() {
+        // set i_dir_acl of /dir1, which was never part of the size of directories
+        let mut data = std::fs::read("testdata/test.ext4").unwrap();
+        let fs = FileSystem::from_bytes(&data).unwrap();
+        let pos = (fs.block_group_descriptors[0].get_inode_table_loc() * 1024 + 12 * 128) as usize;
+        data[pos + 0x6C] = 1;
+        let fs = FileSystem::from_bytes(&data).unwrap();
+        assert_eq!(fs.metadata("/dir1").unwrap().len(), 1024);
+        assert_eq!(fs.list_dir("/dir1").unwrap().len(), 3);
+
+        // with largedir it's the upper half of the size, like for regular files
+        data[1024 + 0x61] |= 0x40;
+        let fs = FileSystem::from_bytes(&data).unwrap();
+        assert!(fs.super_block.feature_incompat_largedir());
+        assert_eq!(fs.metadata("/dir1").unwrap().len(), (1 << 32) + 1024);
+        assert_eq!(fs.metadata("/hello.txt").unwrap().len(), 6);
+    }
+
+    #[test]
+    fn test_unsupported_feature() {
+        let mut data = std::fs::read("testdata/test.ext4").unwrap();
+        // clear the extents bit of s_feature_incompat
+        data[1024 + 0x60] &= !0x40;
+        assert!(matches!(
+            FileSystem::from_bytes(&data),
+            Err(ExtfsError::UnsupportedFeature {
+                name: "extents",
+                bit: 0x40
+            })
+        ));
+    }
+
+    /// A reader counting the reads starting at the `watched` position.
+    struct CountReads {
+        inner: Cursor<Vec<u8>>,
+        watched: u64,
+        count: usize,
+    }
+
+    impl Read for CountReads {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            if self.inner.position() == self.watched {
+                self.count += 1;
+            }
+            self.inner.read(buf)
+        }
+    }
+
+    impl Seek for CountReads {
+        fn seek(&mut self, pos: std::io::SeekFrom) -> std::io::Result<u64> {
+            self.inner.seek(pos)
+        }
+    }
+
+    /// A reader failing to read anything in the `fail` range of bytes.
+    struct FailingReader {
+        inner: Cursor<Vec<u8>>,
+        fail: std::ops::Range<u64>,
+    }
+
+    impl Read for FailingReader {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let pos = self.inner.position();
+            if self.fail.contains(&pos) {
+                return Err(std::io::Error::other("bad sector"));
+            }
+            let n = if pos < self.fail.start {
+                buf.len().min((self.fail.start - pos) as usize)
+            } else {
+                buf.len()
+            };
+            self.inner.read(&mut buf[..n])
+        }
+    }
+
+    impl Seek for FailingReader {
+        fn seek(&mut self, pos: std::io::SeekFrom) -> std::io::Result<u64> {
+            self.inner.seek(pos)
+        }
+    }
+
+    #[test]
+    fn test_metadata_skips_extents() {
+        let data = std::fs::read("testdata/frag.ext4").unwrap();
+        let fs = FileSystem::from_bytes(&data).unwrap();
+        let ino = fs.path_to_ino("/big.bin").unwrap();
+        let expected = fs.metadata("/big.bin").unwrap();
+        assert_eq!(expected.extent_tree_depth(), Some(1));
 
-        let super_block = SuperBlock::from_reader(&mut reader)?;
-        if !super_block.feature_incompat_extents() {
-            return Err(ExtfsError::Other("Only support extents.".to_string()));
-        }
-        let is_64bit = super_block.feature_incompat_64bit();
-        let mut block_group_descriptors = Vec::new();
-        for _ in 0..super_block.get_block_group_count() {
-            let bgd = BlockGroupDescriptor::from_reader(&mut reader, is_64bit)?;
-            block_group_descriptors.push(bgd);
+        // ei_leaf_lo of the only index in i_block
+        let raw = fs.raw_inode(ino).unwrap();
+        let leaf = u32::from_le_bytes(raw[0x28 + 16..0x28 + 20].try_into().unwrap()) as u64;
+        let reader = FailingReader {
+            inner: Cursor::new(data),
+            fail: leaf * 1024..(leaf + 1) * 1024,
+        };
+        let fs = FileSystem::from_reader(reader).unwrap();
+        // stat-ing doesn't touch the unreadable leaf, reading does
+        assert_eq!(fs.metadata("/big.bin").unwrap(), expected);
+        assert!(fs.read("/big.bin").is_err());
+    }
+
+    #[test]
+    fn test_strict_verifies_once() {
+        let data = std::fs::read("testdata/frag.ext4").unwrap();
+        let fs = FileSystem::from_bytes(&data).unwrap();
+        let raw = fs.raw_inode(fs.path_to_ino("/big.bin").unwrap()).unwrap();
+        // ei_leaf_lo of the only index in i_block
+        let leaf = u32::from_le_bytes(raw[0x28 + 16..0x28 + 20].try_into().unwrap()) as u64;
+
+        let reader = CountReads {
+            inner: Cursor::new(data),
+            watched: leaf * 1024,
+            count: 0,
+        };
+        let mut fs = FileSystem::from_reader_strict(reader).unwrap();
+        // the extent tree of an inode is verified on its first lookup only
+        for _ in 0..3 {
+            fs.metadata("/big.bin").unwrap();
         }
+        assert_eq!(fs.reader_mut().count, 1);
+    }
 
-        Ok(Self {
-            super_block,
-            block_group_descriptors,
-            reader,
-        })
+    #[test]
+    fn test_io_error_offset() {
+        let data = std::fs::read("testdata/test.ext4").unwrap();
+        // the inode table starts at block 50
+        let reader = FailingReader {
+            inner: Cursor::new(data),
+            fail: 50 * 1024..u64::MAX,
+        };
+        let fs = FileSystem::from_reader(reader).unwrap();
+
+        let err = fs.metadata("/").err().unwrap();
+        assert!(matches!(
+            err,
+            ExtfsError::IoAt {
+                offset: 51328,
+                context: "inode table",
+                ..
+            }
+        ));
+        assert_eq!(
+            err.to_string(),
+            "IO error while reading inode table at offset 51328: bad sector"
+        );
     }
 
-    fn get_inode(&mut self, ino: u64) -> Result<Inode, ExtfsError> {
-        let bgd_num = (ino - 1) / self.super_block.inodes_per_group as u64;
-        let bgd = self
-            .block_group_descriptors
-            .get(bgd_num as usize)
-            .ok_or(ExtfsError::BlockGroupDescriptorNotFound(bgd_num))?;
+    #[test]
+    fn test_inode_table_out_of_bounds() {
+        let mut data = std::fs::read("testdata/test.ext4").unwrap();
+        // bg_inode_table_lo of group 0, the last of the 2048 blocks can't hold the table
+        data[2048 + 0x8..2048 + 0xC].copy_from_slice(&2047u32.to_le_bytes());
+        let err = FileSystem::from_bytes(&data)
+            .and_then(|fs| fs.metadata("/hello.txt"))
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            ExtfsError::InodeTableOutOfBounds {
+                group: 0,
+                block: 2047
+            }
+        ));
+
+        // nor can the super block
+        data[2048 + 0x8..2048 + 0xC].copy_from_slice(&1u32.to_le_bytes());
+        let fs = FileSystem::from_bytes(&data).unwrap();
+        assert!(matches!(
+            fs.inodes_in_group(0).map(|_| ()),
+            Err(ExtfsError::InodeTableOutOfBounds { group: 0, block: 1 })
+        ));
+    }
 
-        let inode_table_index = (ino - 1) % self.super_block.inodes_per_group as u64;
+    #[test]
+    fn test_zeroed_descriptor() {
+        let mut data = std::fs::read("testdata/metabg.ext4").unwrap();
+        let fs = FileSystem::from_bytes(&data).unwrap();
+        // the descriptor of group 17, holding the inode of /many/zz_late.txt
+        let pos = fs.super_block.descriptor_pos(17).unwrap() as usize;
+        let desc_size = fs.super_block.get_desc_size() as usize;
 
-        let pos = bgd.get_inode_table_loc() * self.super_block.get_block_size()
-            + inode_table_index * self.super_block.inode_size as u64;
-        self.reader.seek(std::io::SeekFrom::Start(pos))?;
+        data[pos..pos + desc_size].fill(0);
+        let fs = FileSystem::from_bytes(&data).unwrap();
+        assert!(matches!(
+            fs.read("/many/zz_late.txt"),
+            Err(ExtfsError::ZeroedBlockGroupDescriptor(17))
+        ));
+        // inodes of the other groups are still readable
+        assert!(fs.metadata("/many").is_ok());
 
-        Inode::decode_from(&mut self.reader)
+        data[pos..pos + desc_size].fill(0xFF);
+        let fs = FileSystem::from_bytes(&data).unwrap();
+        assert!(matches!(
+            fs.read("/many/zz_late.txt"),
+            Err(ExtfsError::InodeTableOutOfBounds { group: 17, .. })
+        ));
     }
 
-    fn get_inode_by_path<P: AsRef<Path>>(&mut self, path: P) -> Result<Inode, ExtfsError> {
-        let p = path.as_ref();
-        if !path.as_ref().is_absolute() {
-            return Err(ExtfsError::RequireAbsolutePath(p.to_path_buf()));
-        }
+    #[test]
+    fn test_lookup_errors() {
+        let data = std::fs::read("testdata/test.ext4").unwrap();
+        let fs = FileSystem::from_bytes(&data).unwrap();
+        let root_block = fs.extents_of("/").unwrap()[0].get_block_loc();
+        assert!(matches!(
+            fs.metadata("/dir1/missing"),
+            Err(ExtfsError::NoSuchFileOrDirectory(p)) if p == Path::new("/dir1/missing")
+        ));
 
-        let block_size = self.super_block.get_block_size();
-        let feature_incompat_filetype = self.super_block.feature_incompat_filetype();
+        // failing to read the directory isn't reported as a missing entry
+        let reader = FailingReader {
+            inner: Cursor::new(data.clone()),
+            fail: root_block * 1024..(root_block + 1) * 1024,
+        };
+        let fs = FileSystem::from_reader(reader).unwrap();
+        assert!(matches!(
+            fs.metadata("/missing"),
+            Err(ExtfsError::IoAt {
+                context: "directory block",
+                ..
+            })
+        ));
 
-        let mut name_inode_stack = Vec::new();
-        for component in p.components() {
-            let name = component
-                .as_os_str()
-                .to_str()
-                .ok_or(ExtfsError::InvalidPath(p.to_path_buf()))?;
+        // neither is a corrupted directory
+        let mut data = data;
+        let pos = (root_block * 1024 + 4) as usize;
+        data[pos..pos + 2].copy_from_slice(&2000u16.to_le_bytes());
+        let fs = FileSystem::from_bytes(&data).unwrap();
+        let err = fs.metadata("/missing").err().unwrap();
+        assert!(
+            !matches!(err, ExtfsError::NoSuchFileOrDirectory(_)),
+            "{err:?}"
+        );
+    }
 
-            match component {
-                std::path::Component::Prefix(_) => {}
-                std::path::Component::RootDir => {
-                    name_inode_stack.push((name, self.get_inode(INO_ROOT)?));
-                }
-                std::path::Component::CurDir => {}
-                std::path::Component::ParentDir => {
-                    if name_inode_stack.len() <= 1 {
-                        return Err(ExtfsError::UnexpectedParentDir(p.to_path_buf()));
-                    }
+    #[test]
+    fn test_truncated_image() {
+        let data = std::fs::read("testdata/test.ext4").unwrap();
 
-                    name_inode_stack
-                        .pop()
-                        .ok_or(ExtfsError::InvalidPath(p.to_path_buf()))?;
-                }
-                std::path::Component::Normal(_) => {
-                    let (_, last_inode) = name_inode_stack
-                        .last()
-                        .ok_or(ExtfsError::InvalidPath(p.to_path_buf()))?;
-
-                    if !last_inode.is_dir() {
-                        let path: PathBuf = name_inode_stack.iter().map(|&(s, _)| s).collect();
-                        return Err(ExtfsError::IsNotDirecotry(path.join(name)));
-                    }
+        assert!(matches!(
+            FileSystem::from_bytes(&data[..1500]),
+            Err(ExtfsError::TruncatedImage {
+                reading: "super block",
+                offset: 1024
+            })
+        ));
 
-                    let rd = last_inode.read_dir(
-                        block_size,
-                        feature_incompat_filetype,
-                        &mut self.reader,
-                    )?;
-
-                    let mut entry = None;
-                    for x in rd {
-                        let dir_entry_enum = x?;
-                        if dir_entry_enum.get_name_str().eq(name) {
-                            entry = Some(dir_entry_enum);
-                            break;
-                        }
-                    }
+        // the inode table starts at block 50, root is the 2nd inode
+        let fs = FileSystem::from_bytes(&data[..50 * 1024 + 200]).unwrap();
+        assert!(matches!(
+            fs.metadata("/hello.txt"),
+            Err(ExtfsError::TruncatedImage {
+                reading: "inode table",
+                offset: 51328
+            })
+        ));
 
-                    match entry {
-                        Some(e) => {
-                            let ino = e.get_ino().ok_or(ExtfsError::UnexpectedDirEntry(e))?;
-                            let inode = self.get_inode(ino as u64)?;
-
-                            name_inode_stack.push((name, inode));
-                        }
-                        None => {
-                            let path: PathBuf = name_inode_stack.iter().map(|&(s, _)| s).collect();
-                            return Err(ExtfsError::NoSuchFileOrDirectory(path.join(name)));
-                        }
-                    }
-                }
-            }
-        }
+        // data of /hello.txt is in block 1091
+        let fs = FileSystem::from_bytes(&data[..1091 * 1024]).unwrap();
+        assert!(fs.metadata("/hello.txt").is_ok());
+        let err = fs.read("/hello.txt").unwrap_err();
+        assert!(err.to_string().contains("offset 1117184"), "{}", err);
+    }
 
-        let (_, last_inode) = name_inode_stack
-            .last()
-            .ok_or(ExtfsError::InvalidPath(p.to_path_buf()))?;
-        Ok(last_inode.clone())
+    #[test]
+    fn test_arithmetic_overflow() {
+        let mut data = std::fs::read("testdata/test.ext4").unwrap();
+        let fs = FileSystem::from_bytes(&data).unwrap();
+        assert!(matches!(
+            fs.inode(0),
+            Err(ExtfsError::ArithmeticOverflow(_))
+        ));
+
+        // a huge bg_inode_table_hi in the first descriptor is caught before computing its
+        // position
+        data[2048 + 0x28..2048 + 0x2C].fill(0xFF);
+        let fs = FileSystem::from_bytes(&data).unwrap();
+        assert!(matches!(
+            fs.metadata("/"),
+            Err(ExtfsError::InodeTableOutOfBounds { group: 0, .. })
+        ));
     }
 
-    pub fn read_dir<P: AsRef<Path>>(mut self, path: P) -> Result<ReadDir<R>, ExtfsError> {
-        let i = self.get_inode_by_path(path.as_ref())?;
-        if !i.is_dir() {
-            return Err(ExtfsError::IsNotDirecotry(path.as_ref().to_path_buf()));
-        }
-        let block_size = self.super_block.get_block_size();
-        let feature_incompat_filetype = self.super_block.feature_incompat_filetype();
+    #[test]
+    fn test_verify_descriptors() {
+        let fs = new_fs();
+        assert_eq!(fs.super_block.descriptor_pos(1).unwrap(), 2048 + 64);
+        fs.verify_descriptors().unwrap();
 
-        i.read_dir(block_size, feature_incompat_filetype, self.reader)
+        // corrupt free blocks count of the 1st group
+        let mut data = std::fs::read("testdata/test.ext4").unwrap();
+        data[2048 + 0x0C] ^= 0xFF;
+        let fs = FileSystem::from_reader(Cursor::new(&data)).unwrap();
+        assert!(matches!(
+            fs.verify_descriptors(),
+            Err(ExtfsError::DescriptorChecksumMismatch { group: 0 })
+        ));
+        assert!(matches!(
+            FileSystem::from_reader_strict(Cursor::new(&data)),
+            Err(ExtfsError::DescriptorChecksumMismatch { group: 0 })
+        ));
     }
 
-    /// Read the entire contents of a file into a bytes vector.
-    pub fn read<P: AsRef<Path>>(&mut self, path: P) -> Result<Vec<u8>, ExtfsError> {
-        let i = self.get_inode_by_path(path.as_ref())?;
-        if !i.is_regular() {
-            return Err(ExtfsError::IsNotRegular(path.as_ref().to_path_buf()));
-        }
-        let block_size = self.super_block.get_block_size();
+    #[test]
+    fn test_free_blocks_verified() {
+        // test.ext4 wasn't cleanly unmounted, the groups have 15 blocks allocated since the
+        // super block count was last written
+        let fs = new_fs();
+        let check = fs.free_blocks_verified();
+        assert_eq!(check.super_block, 958);
+        assert_eq!(check.groups, 943);
+        assert!(!check.consistent);
 
-        let b = i.read_bytes(block_size, &mut self.reader)?;
-        Ok(b)
+        let fs = FileSystem::from_path("testdata/fs32.ext4").unwrap();
+        let check = fs.free_blocks_verified();
+        assert_eq!(check.groups, 469);
+        assert!(check.consistent);
     }
 
-    /// Reads a symbolic link, returning the file that the link points to.
-    pub fn read_link<P: AsRef<Path>>(&mut self, path: P) -> Result<PathBuf, ExtfsError> {
-        let i = self.get_inode_by_path(path.as_ref())?;
-        if !i.is_symlink() {
-            return Err(ExtfsError::IsNotSymlink(path.as_ref().to_path_buf()));
+    #[test]
+    fn test_inodes_in_group() {
+        let fs = new_fs();
+        let inodes: Vec<_> = fs.inodes_in_group(0).unwrap().collect();
+        assert_eq!(inodes.len(), fs.inodes_per_group() as usize);
+        assert_eq!(inodes[0].0, 1);
+        for (ino, inode) in inodes.iter().filter(|(_, inode)| inode.mode != 0) {
+            assert_eq!(
+                Metadata::from(inode),
+                Metadata::from(&fs.inode(*ino).unwrap())
+            );
         }
-        let block_size = self.super_block.get_block_size();
+        assert!(inodes[1].1.is_dir());
+        assert!(matches!(
+            fs.inodes_in_group(1),
+            Err(ExtfsError::BlockGroupDescriptorNotFound(1))
+        ));
 
-        let b = i.read_link(block_size, &mut self.reader)?;
+        // garbage past bg_itable_unused isn't decoded, even in strict mode
+        let mut data = std::fs::read("testdata/test.ext4").unwrap();
+        let last = (50 * 1024 + 255 * 128) as usize;
+        data[last..last + 128].fill(0xAA);
+        let fs = FileSystem::from_reader_strict(Cursor::new(&data)).unwrap();
+        let inodes: Vec<_> = fs.inodes_in_group(0).unwrap().collect();
+        assert_eq!(inodes.len(), 256);
+        assert_eq!(inodes[255].0, 256);
+        assert_eq!(inodes[255].1.mode, 0);
+        assert!(inodes.iter().all(|(ino, inode)| inode.ino == *ino));
 
-        Ok(String::from_utf8_lossy(&b).to_string().into())
+        // nothing is read with INODE_UNINIT
+        data[2048 + 0x12] |= 0x1;
+        let fs = FileSystem::from_bytes(&data).unwrap();
+        assert!(fs
+            .inodes_in_group(0)
+            .unwrap()
+            .all(|(_, inode)| inode.mode == 0));
+
+        // inodes of the second meta group
+        let data = std::fs::read("testdata/metabg.ext4").unwrap();
+        let fs = FileSystem::from_reader_strict(Cursor::new(&data)).unwrap();
+        let (ino, inode) = fs.inodes_in_group(17).unwrap().nth(29).unwrap();
+        assert_eq!(ino, 574);
+        assert_eq!(fs.read_from_inode(&inode).unwrap(), b"meta group 1\n");
     }
 
-    /// Given a path, query the file system to get information about a file, directory, etc
-    pub fn metadata<P: AsRef<Path>>(&mut self, path: P) -> Result<Metadata, ExtfsError> {
-        let i = self.get_inode_by_path(path.as_ref())?;
-        Ok(Metadata::new(i))
+    #[test]
+    fn test_meta_bg() {
+        // 20 groups of 256 blocks, 16 descriptors per block: the second meta group keeps its
+        // descriptors in group 16
+        let data = std::fs::read("testdata/metabg.ext4").unwrap();
+        let fs = FileSystem::from_reader_strict(Cursor::new(&data)).unwrap();
+        assert_eq!(fs.block_group_descriptors.len(), 20);
+        assert_eq!(fs.super_block.descriptor_pos(0).unwrap(), 2 * 1024);
+        assert_eq!(fs.super_block.descriptor_pos(17).unwrap(), 4097 * 1024 + 64);
+        fs.verify_descriptors().unwrap();
+
+        assert_eq!(fs.read("/hello.txt").unwrap(), b"hello meta_bg\n");
+        // the inode is in group 17
+        assert_eq!(fs.path_to_ino("/many/zz_late.txt").unwrap(), 574);
+        assert_eq!(fs.read("/many/zz_late.txt").unwrap(), b"meta group 1\n");
     }
 
-    /// Attempts to open a file in read-only mode.
-    pub fn open<P: AsRef<Path>>(mut self, path: P) -> Result<File<R>, ExtfsError> {
-        let i = self.get_inode_by_path(path.as_ref())?;
-        if !i.is_regular() {
-            return Err(ExtfsError::IsNotRegular(path.as_ref().to_path_buf()));
-        }
-        let block_size = self.super_block.get_block_size();
+    #[test]
+    fn test_bigalloc() {
+        let data = std::fs::read("testdata/bigalloc.ext4").unwrap();
+        let fs = FileSystem::from_reader_strict(Cursor::new(&data)).unwrap();
+        assert_eq!(fs.read("/hello.txt").unwrap(), b"hello bigalloc\n");
+        assert_eq!(fs.read("/sub/deep.txt").unwrap(), b"deep\n");
+        assert_eq!(fs.read("/big.bin").unwrap().len(), 200000);
+
+        // 1 KiB blocks in 4 KiB clusters, groups start at block 0
+        assert_eq!(fs.cluster_size(), 4096);
+        assert_eq!(fs.block_group_descriptors.len(), 3);
+        let check = fs.free_blocks_verified();
+        assert_eq!(check.super_block, 2808);
+        assert!(check.consistent);
 
-        i.read_file(block_size, self.reader)
+        let fs = new_fs();
+        assert_eq!(fs.cluster_size(), 1024);
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use std::{
-        fs::File,
-        io::{BufReader, Read, Seek},
-    };
+    #[test]
+    fn test_block_groups() {
+        let data = std::fs::read("testdata/bigalloc.ext4").unwrap();
+        let fs = FileSystem::from_bytes(&data).unwrap();
+        let groups = fs.block_groups();
+        assert_eq!(groups.len(), 3);
+        // as reported by dumpe2fs, the bitmaps and inode tables are packed in group 0
+        assert_eq!(
+            groups[1],
+            BlockGroupInfo {
+                group: 1,
+                first_block: 1024,
+                block_bitmap: 4,
+                inode_bitmap: 7,
+                inode_table: 17,
+                free_blocks: 255,
+                free_inodes: 32,
+                used_dirs: 0,
+                itable_unused: 32,
+                flags: 0x3,
+            }
+        );
+        assert_eq!(groups[0].used_dirs, 3);
+        assert_eq!(groups[2].inode_table, 25);
+    }
 
-    use crate::constants::INO_ROOT;
+    #[test]
+    fn test_first_data_block() {
+        // 1 KiB blocks: the super block is block 1, the descriptors follow in block 2
+        let fs = new_fs();
+        assert_eq!(fs.first_data_block(), 1);
+        assert_eq!(fs.super_block.descriptor_pos(0).unwrap(), 2 * 1024);
+        assert_eq!(fs.block_groups()[0].first_block, 1);
 
-    use super::FileSystem;
+        // 4 KiB blocks: the super block is in block 0 after the boot sector
+        let data = std::fs::read("testdata/blk4k.ext4").unwrap();
+        let fs = FileSystem::from_reader_strict(Cursor::new(&data)).unwrap();
+        assert_eq!(fs.first_data_block(), 0);
+        assert_eq!(fs.super_block.descriptor_pos(0).unwrap(), 4096);
+        let groups = fs.block_groups();
+        assert_eq!(groups.len(), 3);
+        assert_eq!(groups[2].first_block, 512);
+        fs.verify_descriptors().unwrap();
+        assert_eq!(fs.read("/d/f.txt").unwrap(), b"four k\n");
+    }
 
-    fn new_fs() -> FileSystem<BufReader<File>> {
-        let file = File::open("testdata/test.ext4").unwrap();
-        let reader = BufReader::new(file);
-        FileSystem::from_reader(reader).unwrap()
+    #[test]
+    fn test_flex_bg_size() {
+        let fs = new_fs();
+        assert_eq!(fs.flex_bg_size(), Some(16));
+
+        let mut data = std::fs::read("testdata/test.ext4").unwrap();
+        // clear the flex_bg bit of s_feature_incompat
+        data[1024 + 0x61] &= !0x2;
+        let fs = FileSystem::from_bytes(&data).unwrap();
+        assert_eq!(fs.flex_bg_size(), None);
     }
 
     #[test]
-    fn test_get_inode() {
-        let mut fs = new_fs();
-        println!("fs: {:?}", fs);
+    fn test_inode_usage() {
+        // the super block of test.ext4 only counts the 11 reserved inodes as used, the groups
+        // count the files created after it too
+        let fs = new_fs();
+        let usage = fs.inode_usage();
+        assert_eq!(usage.total, 256);
+        assert_eq!(usage.free, 245);
+        assert_eq!(usage.used, 11);
+        assert_eq!(usage.groups_free, 230);
+        assert!(!usage.consistent);
 
-        let block_size = fs.super_block.get_block_size();
+        let fs = FileSystem::from_path("testdata/fs32.ext4").unwrap();
+        let usage = fs.inode_usage();
+        assert_eq!((usage.total, usage.free, usage.used), (64, 50, 14));
+        assert!(usage.consistent);
+    }
 
-        let inode = fs.get_inode(INO_ROOT).unwrap();
-        let extents = inode.extents(block_size, &mut fs.reader).unwrap();
-        println!("root inode: {:?} \n extents: {:?}", inode, extents);
+    #[test]
+    fn test_checksum_kind() {
+        let fs = new_fs();
+        assert_eq!(fs.checksum_kind(), ChecksumKind::Crc32c);
+        let fs = FileSystem::from_path("testdata/fs32.ext4").unwrap();
+        assert_eq!(fs.checksum_kind(), ChecksumKind::None);
+        assert_eq!(fs.metadata("/hello.txt").unwrap().checksum_valid(), None);
+
+        // uninit_bg with 64 bytes descriptors
+        let data = std::fs::read("testdata/gdtcsum.ext4").unwrap();
+        let fs = FileSystem::from_reader_strict(Cursor::new(&data)).unwrap();
+        assert_eq!(fs.checksum_kind(), ChecksumKind::Crc16);
+        fs.verify_descriptors().unwrap();
+        assert_eq!(fs.read("/hello.txt").unwrap(), b"hello gdt\n");
+
+        let mut bad = data.clone();
+        bad[2048 + 0x0C] ^= 0xFF;
+        assert!(matches!(
+            FileSystem::from_reader_strict(Cursor::new(&bad)),
+            Err(ExtfsError::DescriptorChecksumMismatch { group: 0 })
+        ));
+        // the upper half of the descriptor is covered too
+        let mut bad = data.clone();
+        bad[2048 + 0x2C] ^= 0xFF;
+        let fs = FileSystem::from_reader(Cursor::new(&bad)).unwrap();
+        assert!(fs.verify_descriptors().is_err());
+
+        // metadata_csum only defines crc32c
+        let mut bad = std::fs::read("testdata/test.ext4").unwrap();
+        bad[1024 + 0x175] = 2;
+        assert!(FileSystem::from_reader(Cursor::new(&bad)).is_ok());
+        assert!(matches!(
+            FileSystem::from_reader_strict(Cursor::new(&bad)),
+            Err(ExtfsError::UnsupportedChecksumType(2))
+        ));
     }
 
     #[test]
-    fn test_read_dir() {
+    fn test_dir_hash() {
         let fs = new_fs();
 
-        let rd = fs.read_dir("/dir1").unwrap();
-        for x in rd {
-            println!("{}", x.unwrap().get_name_str());
+        assert_eq!(fs.dir_hash_version(), 1);
+        assert_eq!(
+            fs.dir_hash_seed(),
+            [0x0003_b3ff, 0xe748_acb2, 0xf25d_2bb9, 0x31ce_c279]
+        );
+    }
+
+    #[test]
+    fn test_inode_size() {
+        let f = File::open("testdata/test.ext4").unwrap();
+        let fs = FileSystem::from_reader(BufReader::new(f)).unwrap();
+        assert_eq!(fs.inode_size(), 128);
+        assert_eq!(fs.min_extra_isize(), 0);
+        assert_eq!(fs.want_extra_isize(), 0);
+        assert_eq!(fs.blocks_per_group(), 8192);
+        assert_eq!(fs.inodes_per_group(), 256);
+
+        let f = File::open("testdata/frag.ext4").unwrap();
+        let fs = FileSystem::from_reader(BufReader::new(f)).unwrap();
+        assert_eq!(fs.inode_size(), 256);
+        assert_eq!(fs.min_extra_isize(), 32);
+        assert_eq!(fs.want_extra_isize(), 32);
+
+        let fs = FileSystem::from_path("testdata/fs32.ext4").unwrap();
+        assert_eq!(fs.inodes_per_group(), 64);
+    }
+
+    #[test]
+    fn test_try_from() {
+        let f = File::open("testdata/test.ext4").unwrap();
+        let fs = FileSystem::try_from(f).unwrap();
+        assert_eq!(fs.read("/hello.txt").unwrap(), b"hello\n");
+
+        let fs = FileSystem::try_from(Path::new("testdata/test.ext4")).unwrap();
+        assert_eq!(fs.read("/hello.txt").unwrap(), b"hello\n");
+        let fs = FileSystem::from_path("testdata/test.ext4").unwrap();
+        assert_eq!(fs.read("/hello.txt").unwrap(), b"hello\n");
+
+        assert!(FileSystem::try_from(Path::new("testdata/missing.ext4")).is_err());
+    }
+
+    #[test]
+    fn test_from_reader_at() {
+        // a disk image with the file system in a partition starting at 1 MiB
+        let mut disk = vec![0xAA; 1 << 20];
+        disk.extend(std::fs::read("testdata/test.ext4").unwrap());
+        let fs = FileSystem::from_reader_at(Cursor::new(disk), 1 << 20).unwrap();
+        assert_eq!(fs.read("/hello.txt").unwrap(), b"hello\n");
+        assert_eq!(fs.read("/dir1/world.txt").unwrap(), b"world\n");
+        assert!(fs.metadata("/dir2").unwrap().is_dir());
+
+        assert!(FileSystem::from_reader_at(Cursor::new(vec![0; 4096]), 1024).is_err());
+    }
+
+    #[test]
+    fn test_from_read_at() {
+        let f = File::open("testdata/test.ext4").unwrap();
+        let fs = FileSystem::from_read_at(&f).unwrap();
+        assert_eq!(fs.read("/hello.txt").unwrap(), b"hello\n");
+        // the source is shared, not moved
+        let fs2 = FileSystem::from_read_at(&f).unwrap();
+        assert_eq!(fs2.read("/dir1/world.txt").unwrap(), b"world\n");
+
+        // a plain reader behind a lock
+        let shared = std::sync::Arc::new(std::sync::Mutex::new(BufReader::new(
+            File::open("testdata/test.ext4").unwrap(),
+        )));
+        let handles: Vec<_> = (0..2)
+            .map(|_| {
+                let shared = shared.clone();
+                std::thread::spawn(move || {
+                    let fs = FileSystem::from_read_at(shared).unwrap();
+                    fs.read("/hello.txt").unwrap()
+                })
+            })
+            .collect();
+        for h in handles {
+            assert_eq!(h.join().unwrap(), b"hello\n");
         }
     }
 
     #[test]
-    fn test_read_link() {
-        let mut fs = new_fs();
+    fn test_open_shared() {
+        let data = std::fs::read("testdata/frag.ext4").unwrap();
+        let fs = FileSystem::from_bytes(&data).unwrap();
+        let expected = fs.read("/big.bin").unwrap();
 
-        let p = fs.read_link("/hello.txt.lnk").unwrap();
-        assert_eq!("hello.txt", p.to_str().unwrap());
+        let fs = std::sync::Arc::new(FileSystem::from_read_at(data).unwrap());
+        let handles: Vec<_> = (0..4)
+            .map(|i| {
+                let fs = fs.clone();
+                std::thread::spawn(move || {
+                    let mut f = fs.open_shared("/big.bin").unwrap();
+                    // positioned reads don't depend on the other handles
+                    let mut buf = vec![0; 1000];
+                    f.read_exact_at(&mut buf, i * 1000).unwrap();
+                    let mut all = Vec::new();
+                    f.read_to_end(&mut all).unwrap();
+                    (buf, all)
+                })
+            })
+            .collect();
+        for (i, h) in handles.into_iter().enumerate() {
+            let (buf, all) = h.join().unwrap();
+            assert_eq!(buf, expected[i * 1000..(i + 1) * 1000]);
+            assert_eq!(all, expected);
+        }
 
-        let p = fs.read_link("/test.txt.lnk").unwrap();
+        assert!(matches!(
+            fs.open_shared("/"),
+            Err(ExtfsError::IsNotRegular(_))
+        ));
+        assert!(matches!(
+            fs.open_shared("/missing"),
+            Err(ExtfsError::NoSuchFileOrDirectory(_))
+        ));
+
+        // data stored in the inode
+        let data = std::fs::read("testdata/inline.ext4").unwrap();
+        let fs = std::sync::Arc::new(FileSystem::from_read_at(data).unwrap());
+        let mut f = fs.open_shared("/small.txt").unwrap();
+        let mut buf = [0; 4];
+        f.read_exact_at(&mut buf, 2).unwrap();
+        assert_eq!(&buf, b"line");
+        let mut all = Vec::new();
+        f.read_to_end(&mut all).unwrap();
+        assert_eq!(all, b"inline\n");
+    }
+
+    #[test]
+    fn test_special_inode() {
+        let fs = new_fs();
+        let lost_found = fs.path_to_ino("/lost+found").unwrap();
+        assert_eq!(fs.special_ino(SpecialInode::LostAndFound), Some(lost_found));
+        assert!(fs
+            .special_inode(SpecialInode::LostAndFound)
+            .unwrap()
+            .is_dir());
+        assert_eq!(fs.special_ino(SpecialInode::Root), Some(INO_ROOT));
+        assert_eq!(SpecialInode::ResizeInode.default_ino(), 7);
+
+        // s_first_ino is only defined from revision 1 on
+        let mut data = std::fs::read("testdata/test.ext4").unwrap();
+        data[1024 + 0x4C..1024 + 0x50].fill(0);
+        data[1024 + 0x54..1024 + 0x58].fill(0);
+        let fs = FileSystem::from_bytes(&data).unwrap();
+        assert_eq!(fs.special_ino(SpecialInode::LostAndFound), Some(11));
+
+        // without has_journal, s_journal_inum doesn't name a journal
+        data[1024 + 0x5C] &= !0x4;
+        let fs = FileSystem::from_bytes(&data).unwrap();
+        assert_eq!(fs.special_ino(SpecialInode::Journal), None);
+        assert!(matches!(
+            fs.special_inode(SpecialInode::Journal),
+            Err(ExtfsError::NoJournalInode)
+        ));
+
+        let f = File::open("testdata/journal.ext4").unwrap();
+        let fs = FileSystem::from_reader(BufReader::new(f)).unwrap();
+        assert_eq!(fs.special_ino(SpecialInode::Journal), Some(8));
+        let journal = fs.special_inode(SpecialInode::Journal).unwrap();
+        assert!(journal.is_regular());
+        assert!(journal.get_size() > 0);
+    }
+
+    #[test]
+    fn test_journal_transactions() {
+        // written with debugfs `journal_write`, the last transaction has no commit block
+        let f = File::open("testdata/journal.ext4").unwrap();
+        let fs = FileSystem::from_reader(BufReader::new(f)).unwrap();
+
+        let txs = fs.journal_transactions().unwrap();
+        let summary: Vec<_> = txs
+            .iter()
+            .map(|tx| {
+                (
+                    tx.sequence,
+                    tx.blocks.clone(),
+                    tx.revoked.clone(),
+                    tx.committed,
+                )
+            })
+            .collect();
         assert_eq!(
-            "a1234567890/b1234567890/c1234567890/d1234567890/e1234567890/f1234567890/test.txt",
-            p.to_str().unwrap()
+            summary,
+            [
+                (1, vec![100, 101, 102], vec![], true),
+                (2, vec![200], vec![101], true),
+                (3, vec![300, 301], vec![], true),
+                (4, vec![400], vec![], false),
+            ]
         );
+
+        // checksum v3 tags, compared with `debugfs -R "logdump -a"`
+        let txs = new_fs().journal_transactions().unwrap();
+        assert_eq!(txs.len(), 18);
+        assert!(txs.iter().all(|tx| tx.committed));
+        assert_eq!((txs[0].sequence, txs[0].blocks.as_slice()), (2, &[50][..]));
+        assert_eq!(txs[1].blocks, [34, 2, 51, 18, 1090, 50, 19, 1]);
+        assert_eq!(txs[17].sequence, 19);
+
+        // a journal inode without extents has no super block
+        let mut data = std::fs::read("testdata/test.ext4").unwrap();
+        data[50 * 1024 + 7 * 128 + 0x2A] = 0;
+        let fs = FileSystem::from_bytes(&data).unwrap();
+        assert!(matches!(
+            fs.journal_transactions(),
+            Err(ExtfsError::UnmappedBlock { ino: 8, block: 0 })
+        ));
+
+        // no journal
+        let f = File::open("testdata/xattr.ext4").unwrap();
+        let fs = FileSystem::from_reader(BufReader::new(f)).unwrap();
+        assert!(fs.journal_transactions().unwrap().is_empty());
     }
 
     #[test]
-    fn test_read() {
-        let mut fs = new_fs();
+    fn test_32bit() {
+        // no 64bit feature, so 32 bytes descriptors
+        let f = File::open("testdata/fs32.ext4").unwrap();
+        let fs = FileSystem::from_reader(BufReader::new(f)).unwrap();
+        let sb = fs.superblock();
+        assert_eq!(sb.feature_incompat & 0x80, 0);
+        assert_eq!(sb.desc_size, 32);
+        assert_eq!(sb.blocks_count, 512);
 
-        let b = fs.read("/hello.txt").unwrap();
-        assert_eq!("hello\n", String::from_utf8_lossy(&b).to_string());
+        let ino = fs.path_to_ino("/hello.txt").unwrap();
+        assert!(fs.inode(ino).unwrap().is_regular());
+        assert_eq!(fs.read("/hello.txt").unwrap(), b"hello 32\n");
+        assert_eq!(fs.read("/dir/rand.bin").unwrap().len(), 5000);
+        assert_eq!(fs.metadata("/dir/rand.bin").unwrap().file_acl(), 0);
+
+        let mut names: Vec<String> = fs
+            .read_dir("/")
+            .unwrap()
+            .map(|x| x.unwrap().get_name_str())
+            .collect();
+        names.sort();
+        assert_eq!(names, ["dir", "hello.txt", "lost+found"]);
+
+        // the upper half of the block count is ignored without the 64bit feature
+        let mut data = std::fs::read("testdata/fs32.ext4").unwrap();
+        data[1024 + 0x150] = 1;
+        let fs = FileSystem::from_bytes(&data).unwrap();
+        assert_eq!(fs.superblock().blocks_count, 512);
     }
 
     #[test]
-    fn test_metadata() {
-        let mut fs = new_fs();
+    fn test_superblock() {
+        let f = File::open("testdata/test.ext4").unwrap();
+        let fs = FileSystem::from_reader(BufReader::new(f)).unwrap();
 
-        let m = fs.metadata("/hello.txt").unwrap();
-        println!(
-            "uid={} gid={} permissions={:o} len={} created={:?} accessed={:?} modified={:?}",
-            m.uid(),
-            m.gid(),
-            m.permissions(),
-            m.len(),
-            m.created(),
-            m.accessed(),
-            m.modified(),
+        // values from dumpe2fs
+        let sb = fs.superblock();
+        assert_eq!(sb.inodes_count, 256);
+        assert_eq!(sb.blocks_count, 2048);
+        assert_eq!(sb.reserved_blocks_count, 102);
+        assert_eq!(sb.free_blocks_count, 958);
+        assert_eq!(sb.free_inodes_count, 245);
+        assert_eq!(sb.first_data_block, 1);
+        assert_eq!(sb.block_size, 1024);
+        assert_eq!(sb.blocks_per_group, 8192);
+        assert_eq!(sb.inodes_per_group, 256);
+        assert_eq!(sb.block_group_count, 1);
+        assert_eq!(sb.inode_size, 128);
+        assert_eq!(sb.desc_size, 64);
+        assert_eq!(sb.first_ino, 11);
+        assert_eq!(sb.rev_level, 1);
+        assert_eq!(sb.creator_os, CreatorOs::Linux);
+        assert_eq!(fs.creator_os(), CreatorOs::Linux);
+        assert_eq!(fs.revision(), (1, 0));
+        assert_eq!(sb.mount_count, 1);
+        assert_eq!(sb.max_mount_count, u16::MAX);
+        // Wed Jan  3 08:12:24 2024
+        assert_eq!(sb.mkfs_time, 1704269544);
+        assert_eq!(
+            fs.created_at(),
+            UNIX_EPOCH + Duration::from_secs(1704269544)
+        );
+        assert_eq!(
+            fs.provenance(),
+            Provenance {
+                created_at: fs.created_at(),
+                creator_os: CreatorOs::Linux,
+                rev_level: 1,
+                minor_rev_level: 0,
+            }
+        );
+        assert_eq!(sb.last_check_time, 1704269544);
+        assert_eq!(sb.write_time, 1704271398);
+        assert_eq!(
+            sb.uuid,
+            [
+                0x5a, 0x3a, 0xe3, 0x9f, 0x1e, 0xde, 0x42, 0xce, 0xba, 0xbd, 0x2b, 0x81, 0x7d, 0xb3,
+                0xad, 0x51
+            ]
+        );
+        assert_eq!(sb.volume_name, "");
+        assert_eq!(
+            fs.blkid_info(),
+            BlkidInfo {
+                uuid: "5a3ae39f-1ede-42ce-babd-2b817db3ad51".to_string(),
+                label: None,
+                fs_type: "ext4",
+            }
         );
+        // s_volume_name, trailing spaces are trimmed like blkid does
+        let mut data = std::fs::read("testdata/test.ext4").unwrap();
+        data[1024 + 0x78..1024 + 0x80].copy_from_slice(b"my data ");
+        let fs = FileSystem::from_bytes(&data).unwrap();
+        assert_eq!(fs.blkid_info().label.as_deref(), Some("my data"));
+        assert_eq!(sb.last_mounted, "/mnt/ext4");
+        assert_ne!(sb.feature_incompat & 0x40, 0);
     }
 
     #[test]
-    fn test_open() {
+    fn test_xattr() {
+        let f = File::open("testdata/xattr.ext4").unwrap();
+        let fs = FileSystem::from_reader(BufReader::new(f)).unwrap();
+
+        assert_eq!(
+            fs.list_xattr("/f.txt").unwrap(),
+            ["user.foo", "security.selinux", "trusted.t"]
+        );
+        assert_eq!(fs.xattr("/f.txt", "user.foo").unwrap().unwrap(), b"bar");
+        assert_eq!(
+            fs.xattr("/f.txt", "security.selinux").unwrap().unwrap(),
+            b"system_u:object_r:etc_t:s0"
+        );
+        assert_eq!(fs.xattr("/f.txt", "user.selinux").unwrap(), None);
+        assert_eq!(fs.xattr("/f.txt", "foo").unwrap(), None);
+
+        // the large value lives in a separate block
+        assert_eq!(
+            fs.list_xattr("/big.txt").unwrap(),
+            ["user.small", "user.big"]
+        );
+        assert_eq!(
+            fs.xattr("/big.txt", "user.big").unwrap().unwrap(),
+            [b'x'; 300]
+        );
+        assert!(fs.list_xattr("/").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_kbytes_written() {
+        let f = File::open("testdata/test.ext4").unwrap();
+        let fs = FileSystem::from_reader(BufReader::new(f)).unwrap();
+        assert_eq!(fs.kbytes_written(), 35);
+
+        let f = File::open("testdata/frag.ext4").unwrap();
+        let fs = FileSystem::from_reader(BufReader::new(f)).unwrap();
+        assert_eq!(fs.kbytes_written(), 151);
+    }
+
+    #[test]
+    fn test_mount_options() {
         let fs = new_fs();
 
-        let mut f = fs.open("/hello.txt").unwrap();
-        let mut buf = String::new();
-        f.read_to_string(&mut buf).unwrap();
-        assert_eq!(buf, "hello\n");
+        assert_eq!(fs.default_mount_options().names(), ["user_xattr", "acl"]);
+        assert_eq!(fs.mount_opts_string(), "");
+    }
 
-        buf.clear();
-        f.seek(std::io::SeekFrom::Start(2)).unwrap();
-        f.read_to_string(&mut buf).unwrap();
-        assert_eq!(buf, "llo\n");
+    #[test]
+    fn test_from_bytes() {
+        let data = std::fs::read("testdata/test.ext4").unwrap();
+        let fs = FileSystem::from_bytes(&data).unwrap();
+        assert_eq!(fs.read("/hello.txt").unwrap(), b"hello\n");
 
-        buf.clear();
-        f.seek(std::io::SeekFrom::End(-2)).unwrap();
-        f.read_to_string(&mut buf).unwrap();
-        assert_eq!(buf, "o\n");
+        let mut f = fs.open("/dir1/world.txt").unwrap();
+        let mut buf = [0; 3];
+        f.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"wor");
+    }
+
+    #[test]
+    fn test_from_block_device() {
+        let fs = FileSystem::from_block_device("testdata/test.ext4").unwrap();
+        assert_eq!(fs.read("/hello.txt").unwrap(), b"hello\n");
+
+        let mut dev = fs.into_reader();
+        assert_eq!(dev.len(), 2 * 1024 * 1024);
+        assert_eq!(
+            dev.seek(std::io::SeekFrom::End(-1024)).unwrap(),
+            2047 * 1024
+        );
+        assert_eq!(
+            dev.seek(std::io::SeekFrom::Current(24)).unwrap(),
+            2047 * 1024 + 24
+        );
+    }
+
+    #[test]
+    fn test_into_inner() {
+        let fs = new_fs();
+
+        let rd = fs.read_dir("/dir1").unwrap();
+        let fs = FileSystem::from_reader(rd.into_inner()).unwrap();
+
+        let f = fs.open("/hello.txt").unwrap();
+        let fs = FileSystem::from_reader(f.into_inner()).unwrap();
+        assert_eq!(fs.read("/dir1/world.txt").unwrap(), b"world\n");
+    }
+
+    #[test]
+    fn test_entry() {
+        let mut fs = new_fs();
+
+        let mut e = fs.entry("/dir1/world.txt").unwrap();
+        let m = e.metadata();
+        assert!(m.is_file());
+        assert_eq!(m.len(), 6);
+        assert!(!m.is_empty());
+
+        let mut buf = String::new();
+        e.open().unwrap().read_to_string(&mut buf).unwrap();
+        assert_eq!(buf, "world\n");
 
+        let mut e = fs.entry("/dir1").unwrap();
+        assert!(e.metadata().is_dir());
+        assert!(e.open().is_err());
+
+        // data stored in the inode
+        let f = File::open("testdata/inline.ext4").unwrap();
+        let mut fs = FileSystem::from_reader(BufReader::new(f)).unwrap();
+        let mut e = fs.entry("/small.txt").unwrap();
+        assert!(e.metadata().uses_inline_data());
         buf.clear();
-        f.seek(std::io::SeekFrom::Current(-1)).unwrap();
-        f.read_to_string(&mut buf).unwrap();
-        assert_eq!(buf, "\n");
+        e.open().unwrap().read_to_string(&mut buf).unwrap();
+        assert_eq!(buf, "inline\n");
     }
 }
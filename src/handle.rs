@@ -0,0 +1,59 @@
+use std::{
+    io::{Read, Seek},
+    path::{Path, PathBuf},
+};
+
+use super::{
+    errors::ExtfsError, extent::Extent, file::File, fs::FileSystem, inode::Inode,
+    metadata::Metadata,
+};
+
+/// A path resolved by `FileSystem::entry`.
+///
+/// The inode and its extents are cached, so the common stat-then-read pattern
+/// walks the directory tree only once.
+pub struct Entry<'a, R> {
+    fs: &'a mut FileSystem<R>,
+    path: PathBuf,
+    inode: Inode,
+    extents: Option<Vec<Extent>>,
+}
+
+impl<'a, R: Read + Seek> Entry<'a, R> {
+    pub(crate) fn new(fs: &'a mut FileSystem<R>, path: &Path, inode: Inode) -> Self {
+        Self {
+            fs,
+            path: path.to_path_buf(),
+            inode,
+            extents: None,
+        }
+    }
+
+    fn extents(&mut self) -> Result<&[Extent], ExtfsError> {
+        if self.extents.is_none() {
+            self.extents = Some(self.fs.extents_of_inode(&self.inode)?);
+        }
+        Ok(self.extents.as_deref().unwrap_or_default())
+    }
+
+    /// Query information about the entry.
+    pub fn metadata(&self) -> Metadata {
+        Metadata::new(self.inode.clone())
+    }
+
+    /// Open the entry in read-only mode, borrowing the reader of the file system.
+    pub fn open(&mut self) -> Result<File<&mut R>, ExtfsError> {
+        if !self.inode.is_regular() {
+            return Err(ExtfsError::IsNotRegular(self.path.clone()));
+        }
+        if self.inode.uses_inline_data() {
+            // no extents to cache, the data is in the inode
+            return self.fs.open_from_inode(&self.inode);
+        }
+        let extents = self.extents()?.to_vec();
+        let len = self.inode.get_size();
+        let block_size = self.fs.block_size();
+
+        Ok(File::new(self.fs.reader_mut(), extents, len, block_size))
+    }
+}
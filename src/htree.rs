@@ -0,0 +1,182 @@
+// https://www.kernel.org/doc/html/latest/filesystems/ext4/dynamic.html#hash-tree-directories
+//
+// The index blocks hold logical block numbers of the directory, they must be mapped through
+// its extents like any other directory block.
+
+use std::io::ErrorKind;
+
+use super::{entry::DirEntryEnum, errors::ExtfsError, extent::dir_entry_error};
+
+/// Offset of `dx_root_info` in the first block, after the fake `.` and `..` entries.
+pub(crate) const DX_ROOT_INFO_OFFSET: usize = 24;
+/// Offset of the entries of a `dx_node`, after its fake empty entry.
+pub(crate) const DX_NODE_ENTRIES_OFFSET: usize = 8;
+/// Size of `dx_entry`, the first one of a block holds `dx_countlimit` instead of its hash.
+const DX_ENTRY_SIZE: usize = 8;
+/// Levels below the root with largedir, `ext4_dir_htree_level`.
+const DX_MAX_INDIRECT_LEVELS_LARGEDIR: u8 = 3;
+/// Levels below the root without largedir.
+const DX_MAX_INDIRECT_LEVELS: u8 = 2;
+/// The upper bits of `dx_entry.block` are reserved.
+const DX_BLOCK_MASK: u32 = 0x0FFF_FFFF;
+
+fn read_u16(buf: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(
+        buf.get(offset..offset + 2)?.try_into().ok()?,
+    ))
+}
+
+fn read_u32(buf: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(
+        buf.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+/// The fields of `dx_root_info` needed for a lookup.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct DxRoot {
+    pub hash_version: u8,
+    /// Number of `dx_node` levels between the root and the leaves.
+    pub indirect_levels: u8,
+    /// Offset of the entries in the root block.
+    pub entries_offset: usize,
+}
+
+impl DxRoot {
+    /// Parse the `dx_root_info` of the first block of directory `ino`, largedir allows one more
+    /// level of `dx_node`.
+    pub fn from_block(ino: u64, block: &[u8], largedir: bool) -> Result<Self, ExtfsError> {
+        let invalid = |reason| ExtfsError::InvalidHtree { ino, reason };
+        let info = block
+            .get(DX_ROOT_INFO_OFFSET..DX_ROOT_INFO_OFFSET + 8)
+            .ok_or(invalid("root block too short"))?;
+        let (hash_version, info_length, indirect_levels) = (info[4], info[5], info[6]);
+        if info_length < 8 {
+            return Err(invalid("bad root info length"));
+        }
+        let max_levels = if largedir {
+            DX_MAX_INDIRECT_LEVELS_LARGEDIR
+        } else {
+            DX_MAX_INDIRECT_LEVELS
+        };
+        if indirect_levels >= max_levels {
+            return Err(invalid("too many levels"));
+        }
+        Ok(Self {
+            hash_version,
+            indirect_levels,
+            entries_offset: DX_ROOT_INFO_OFFSET + info_length as usize,
+        })
+    }
+}
+
+/// Parse the `(hash, logical block)` entries of a dx_root or dx_node block starting at
+/// `offset`, the hash of the first entry is 0.
+pub(crate) fn dx_entries(
+    ino: u64,
+    block: &[u8],
+    offset: usize,
+) -> Result<Vec<(u32, u32)>, ExtfsError> {
+    let invalid = |reason| ExtfsError::InvalidHtree { ino, reason };
+    let (Some(limit), Some(count)) = (read_u16(block, offset), read_u16(block, offset + 2)) else {
+        return Err(invalid("index block too short"));
+    };
+    let (limit, count) = (limit as usize, count as usize);
+    if count == 0 || count > limit || offset + limit * DX_ENTRY_SIZE > block.len() {
+        return Err(invalid("bad entry count"));
+    }
+
+    let mut entries = Vec::with_capacity(count);
+    for i in 0..count {
+        let pos = offset + i * DX_ENTRY_SIZE;
+        let hash = if i == 0 {
+            0
+        } else {
+            read_u32(block, pos).unwrap_or(0)
+        };
+        let logical = read_u32(block, pos + 4).unwrap_or(0) & DX_BLOCK_MASK;
+        entries.push((hash, logical));
+    }
+    if entries.windows(2).any(|x| x[0].0 > x[1].0) {
+        return Err(invalid("unsorted hashes"));
+    }
+    Ok(entries)
+}
+
+/// Get the index of the entry whose hash range holds `hash`.
+pub(crate) fn find_entry(entries: &[(u32, u32)], hash: u32) -> usize {
+    // the first entry covers everything below the second one
+    entries[1..].partition_point(|(h, _)| *h <= hash)
+}
+
+/// Find `name` in a leaf block read at `pos` of the image.
+pub(crate) fn find_in_leaf(
+    block: &[u8],
+    pos: u64,
+    name: &str,
+    feature_incompat_filetype: bool,
+) -> Result<Option<DirEntryEnum>, ExtfsError> {
+    let mut offset = 0;
+    while offset < block.len() {
+        match DirEntryEnum::from_block(block, offset, feature_incompat_filetype) {
+            Ok(e) => {
+                offset += e.get_rec_len() as usize;
+                if !matches!(e, DirEntryEnum::DirEntryTail(_))
+                    && !e.is_unused()
+                    && e.get_name_str() == name
+                {
+                    return Ok(Some(e));
+                }
+            }
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(dir_entry_error(e, pos + offset as u64)),
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{dx_entries, find_entry, DxRoot};
+
+    #[test]
+    fn test_dx_entries() {
+        let mut block = vec![0; 1024];
+        // hash_version, info_length and indirect_levels
+        block[28..31].copy_from_slice(&[1, 8, 0]);
+        let root = DxRoot::from_block(2, &block, false).unwrap();
+        assert_eq!(root.hash_version, 1);
+        assert_eq!(root.indirect_levels, 0);
+        assert_eq!(root.entries_offset, 32);
+
+        // limit 123, count 3, then blocks 1, 3 and 2 with the reserved bits set on the last
+        let entries = &mut block[32..56];
+        entries[0..4].copy_from_slice(&[123, 0, 3, 0]);
+        entries[4..8].copy_from_slice(&1u32.to_le_bytes());
+        entries[8..12].copy_from_slice(&0x1000u32.to_le_bytes());
+        entries[12..16].copy_from_slice(&3u32.to_le_bytes());
+        entries[16..20].copy_from_slice(&0x2001u32.to_le_bytes());
+        entries[20..24].copy_from_slice(&0xF000_0002u32.to_le_bytes());
+        let entries = dx_entries(2, &block, 32).unwrap();
+        assert_eq!(entries, [(0, 1), (0x1000, 3), (0x2001, 2)]);
+
+        assert_eq!(find_entry(&entries, 0), 0);
+        assert_eq!(find_entry(&entries, 0xFFE), 0);
+        assert_eq!(find_entry(&entries, 0x1000), 1);
+        assert_eq!(find_entry(&entries, 0x2000), 1);
+        assert_eq!(find_entry(&entries, 0xFFFF_FFFE), 2);
+
+        // a limit running past the block
+        block[32] = 255;
+        assert!(dx_entries(2, &block, 32).is_err());
+        // 2 levels of dx_node need largedir
+        block[28..31].copy_from_slice(&[1, 8, 2]);
+        assert!(DxRoot::from_block(2, &block, false).is_err());
+        assert_eq!(
+            DxRoot::from_block(2, &block, true).unwrap().indirect_levels,
+            2
+        );
+        block[28..31].copy_from_slice(&[1, 8, 3]);
+        assert!(DxRoot::from_block(2, &block, true).is_err());
+    }
+}
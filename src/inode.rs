@@ -1,19 +1,29 @@
 use std::{
+    cmp,
     collections::VecDeque,
-    io::{Cursor, Read, Seek, SeekFrom},
+    io::{Cursor, Read, Seek},
 };
 
 use serde::Deserialize;
 use serde_big_array::BigArray;
 
 use super::{
+    checksum,
     codec::Decoder,
-    constants::{INODE_FLAG_EXTENTS, INODE_MODE_DIR, INODE_MODE_LNK, INODE_MODE_REG},
+    constants::{
+        DOTDOT_DIR_NAME, DOT_DIR_NAME, EXTENT_MAX_DEPTH, INODE_BLOCK_OFFSET, INODE_FLAG_EXTENTS,
+        INODE_FLAG_INDEX, INODE_FLAG_INLINE_DATA, INODE_MODE_DIR, INODE_MODE_LNK, INODE_MODE_REG,
+        INODE_SIZE_FULL, INODE_SIZE_GOOD_OLD,
+    },
+    entry::DirEntryEnum,
     errors::ExtfsError,
     extent::{Extent, ExtentHeader, ExtentIdx, ExtentOrIdx},
     file::File,
-    read_dir::ReadDir,
-    utils::compute_u64,
+    file_type::FileType,
+    read_dir::{DirCookie, ReadDir},
+    superblock::CreatorOs,
+    utils::{block_pos, compute_u64, read_exact_at, to_usize},
+    xattr::{self, XATTR_INDEX_SYSTEM},
 };
 
 /// https://www.kernel.org/doc/html/latest/filesystems/ext4/dynamic.html#index-nodes
@@ -34,7 +44,7 @@ pub struct Inode {
     pub(crate) gid: u16,
     links_count: u16,
     blocks_lo: u32,
-    flags: u32,
+    pub(crate) flags: u32,
     osd1: [u8; 4],
     #[serde(with = "BigArray")]
     block: [u8; 60], // extent_header + (extent | extent_idx)
@@ -53,12 +63,105 @@ pub struct Inode {
     crtime_extra: u32,
     version_hi: u32,
     projid: u32,
+    /// Extended attributes stored in the inode after the extra fields.
+    #[serde(skip)]
+    pub(crate) xattr_area: Vec<u8>,
+    /// Whether the checksum matched when the inode was read, `None` without metadata_csum.
+    #[serde(skip)]
+    pub(crate) checksum_valid: Option<bool>,
+    /// Number of the inode, 0 unless it was read from the inode table.
+    #[serde(skip)]
+    pub(crate) ino: u64,
+    /// Whether the file system has the largedir feature, `i_size_high` is then part of the size
+    /// of any type of inode.
+    #[serde(skip)]
+    pub(crate) largedir: bool,
 }
 
 impl Inode {
+    /// Read an inode of `inode_size` bytes.
+    ///
+    /// Fields after the first 128 bytes are only decoded when they're covered by `extra_isize`,
+    /// otherwise they're zero.
+    pub fn from_reader(mut reader: impl Read, inode_size: u16) -> Result<Self, ExtfsError> {
+        let inode_size = inode_size as usize;
+        let mut buf = vec![0; cmp::max(inode_size, INODE_SIZE_FULL)];
+        reader.read_exact(&mut buf[..inode_size])?;
+
+        let mut valid_len = INODE_SIZE_GOOD_OLD;
+        if inode_size > INODE_SIZE_GOOD_OLD {
+            let extra_isize = u16::from_le_bytes([buf[128], buf[129]]) as usize;
+            valid_len = cmp::min(INODE_SIZE_GOOD_OLD + extra_isize, inode_size);
+        }
+        let xattr_area = buf[valid_len..inode_size.max(valid_len)].to_vec();
+        if valid_len < INODE_SIZE_FULL {
+            buf[valid_len..INODE_SIZE_FULL].fill(0);
+        }
+
+        let mut inode = Inode::decode_from(buf.as_slice())?;
+        inode.xattr_area = xattr_area;
+        Ok(inode)
+    }
+
     /// Get file/directory/symlink size.
+    ///
+    /// `i_size_high` is only the upper half of the size for regular files, it was `i_dir_acl`
+    /// for directories and is ignored for other types, unless largedir is enabled. See
+    /// `ext4_isize`.
     pub fn get_size(&self) -> u64 {
-        compute_u64(self.size_lo, self.size_high)
+        if self.is_regular() || self.largedir {
+            compute_u64(self.size_lo, self.size_high)
+        } else {
+            self.size_lo as u64
+        }
+    }
+
+    /// Get the block number of the extended attribute block, 0 if there's none.
+    pub fn get_file_acl(&self) -> u64 {
+        // l_i_file_acl_high of the Linux osd2
+        let file_acl_high = u16::from_le_bytes([self.osd2[2], self.osd2[3]]);
+        compute_u64(self.file_acl_lo, file_acl_high as u32)
+    }
+
+    /// Get the owner's user id, with the upper 16 bits of the Linux and Hurd `osd2`.
+    pub fn get_uid(&self) -> u32 {
+        let uid_high = u16::from_le_bytes([self.osd2[4], self.osd2[5]]);
+        (uid_high as u32) << 16 | self.uid as u32
+    }
+
+    /// Get the group id, with the upper 16 bits of the Linux and Hurd `osd2`.
+    pub fn get_gid(&self) -> u32 {
+        let gid_high = u16::from_le_bytes([self.osd2[6], self.osd2[7]]);
+        (gid_high as u32) << 16 | self.gid as u32
+    }
+
+    /// Get the file type from the mode.
+    pub fn file_type(&self) -> FileType {
+        FileType::from_mode(self.mode)
+    }
+
+    /// Get the number of hard links.
+    pub(crate) fn links_count(&self) -> u16 {
+        self.links_count
+    }
+
+    /// Clear the upper 16 bits of the extended attribute block, which are only valid with the
+    /// 64bit feature.
+    pub(crate) fn clear_file_acl_high(&mut self) {
+        self.osd2[2..4].fill(0);
+    }
+
+    /// Clear the `osd2` fields read with the Linux layout that `creator_os` lays out
+    /// differently.
+    ///
+    /// The Hurd keeps the upper uid and gid bits at the same place but has the upper mode bits
+    /// instead of `l_i_file_acl_high`, other OSes only have reserved bytes there.
+    pub(crate) fn clear_foreign_osd2(&mut self, creator_os: CreatorOs) {
+        match creator_os {
+            CreatorOs::Linux => {}
+            CreatorOs::Hurd => self.osd2[2..4].fill(0),
+            _ => self.osd2[2..8].fill(0),
+        }
     }
 
     /// Check whether it's a directory.
@@ -76,12 +179,92 @@ impl Inode {
         self.mode & 0xF000 == INODE_MODE_LNK
     }
 
+    pub(crate) fn generation(&self) -> u32 {
+        self.generation
+    }
+
+    /// Check whether the data is stored inline in the inode.
+    pub fn uses_inline_data(&self) -> bool {
+        self.flags & INODE_FLAG_INLINE_DATA != 0
+    }
+
+    /// Get the inline data, `i_block` followed by the value of the `system.data` attribute.
+    pub(crate) fn inline_data(&self) -> Vec<u8> {
+        let mut data = self.block.to_vec();
+        let spilled = xattr::parse_inode_xattrs(&self.xattr_area)
+            .into_iter()
+            .find(|x| x.name_index == XATTR_INDEX_SYSTEM && x.name == b"data");
+        if let Some(x) = spilled {
+            data.extend(x.value);
+        }
+        data
+    }
+
+    /// Get where the inline data is in the on-disk inode, as offsets and lengths in order:
+    /// `i_block` followed by the value of the `system.data` attribute.
+    ///
+    /// The attributes are at the end of the inode, whose size is `inode_size`.
+    pub(crate) fn inline_data_location(&self, inode_size: u16) -> Vec<(u64, u64)> {
+        let mut location = vec![(INODE_BLOCK_OFFSET as u64, self.block.len() as u64)];
+        let area_offset = (inode_size as usize).saturating_sub(self.xattr_area.len());
+        let spilled = xattr::parse_located_inode_xattrs(&self.xattr_area)
+            .into_iter()
+            .find(|(x, _)| x.name_index == XATTR_INDEX_SYSTEM && x.name == b"data");
+        if let Some((x, Some(offset))) = spilled {
+            location.push(((area_offset + offset) as u64, x.value.len() as u64));
+        }
+        location
+    }
+
+    /// Parse the entries of an inline directory.
+    ///
+    /// The first 4 bytes of `i_block` are the inode number of the parent, followed by the
+    /// entries. Those in the `system.data` attribute are a separate list. Each entry comes with
+    /// its offset in the inline data.
+    pub(crate) fn inline_dir_entries(
+        &self,
+        feature_incompat_filetype: bool,
+    ) -> Result<Vec<(DirEntryEnum, u64)>, ExtfsError> {
+        let data = self.inline_data();
+        let (in_inode, spilled) = data.split_at(self.block.len());
+
+        let mut entries = Vec::new();
+        for (base, region) in [(4, &in_inode[4..]), (self.block.len() as u64, spilled)] {
+            let mut cursor = Cursor::new(region);
+            while (cursor.position() as usize) < region.len() {
+                let offset = base + cursor.position();
+                let e = DirEntryEnum::from_reader(&mut cursor, feature_incompat_filetype)?;
+                if !e.is_unused() {
+                    entries.push((e, offset));
+                }
+            }
+        }
+        Ok(entries)
+    }
+
+    /// Check whether the directory is indexed by an htree.
+    pub fn is_indexed(&self) -> bool {
+        self.flags & INODE_FLAG_INDEX != 0
+    }
+
     /// Check whether extents is used
     pub fn uses_extents(&self) -> bool {
         self.flags & INODE_FLAG_EXTENTS != 0
     }
 
-    fn parse_extents(mut reader: impl Read) -> Result<Vec<ExtentOrIdx>, ExtfsError> {
+    /// Get the depth of the extent tree from the header in `i_block`, 0 if the extents fit in
+    /// the inode. `None` without extents or with a corrupted header.
+    pub fn extent_tree_depth(&self) -> Option<u16> {
+        if !self.uses_extents() {
+            return None;
+        }
+        ExtentHeader::from_reader(self.block.as_slice())
+            .ok()
+            .map(|eh| eh.depth)
+    }
+
+    /// Parse an extent tree node, returning its depth with its entries.
+    fn parse_extents(mut reader: impl Read) -> Result<(u16, Vec<ExtentOrIdx>), ExtfsError> {
         let eh = ExtentHeader::from_reader(&mut reader)?;
         let mut result = Vec::new();
 
@@ -96,33 +279,70 @@ impl Inode {
                 result.push(ExtentOrIdx::Idx(idx));
             }
         }
-        // TODO: extents checksum
-        Ok(result)
+        Ok((eh.depth, result))
     }
 
     /// Get all extents of the inode recursively.
     pub fn extents(
+        &self,
+        block_size: u64,
+        reader: impl Read + Seek,
+    ) -> Result<Vec<Extent>, ExtfsError> {
+        self.extents_checked(block_size, reader, None)
+    }
+
+    /// Get all extents of the inode recursively, verifying the checksum of each extent tree block
+    /// if the inode checksum seed is given.
+    pub(crate) fn extents_checked(
         &self,
         block_size: u64,
         mut reader: impl Read + Seek,
+        csum_seed: Option<u32>,
     ) -> Result<Vec<Extent>, ExtfsError> {
+        // nothing has ever been mapped, e.g. an empty inode without the extents flag
+        if self.block.iter().all(|&b| b == 0) {
+            return Ok(Vec::new());
+        }
+
         let mut cursor = Cursor::new(self.block);
 
         let mut result = Vec::new();
         let mut queue = VecDeque::new();
-        queue.extend(Self::parse_extents(&mut cursor)?);
+        let (depth, entries) = Self::parse_extents(&mut cursor)?;
+        if depth > EXTENT_MAX_DEPTH {
+            return Err(ExtfsError::ExtentTreeTooDeep(depth));
+        }
+        queue.extend(entries.into_iter().map(|x| (depth, x)));
 
-        while let Some(item) = queue.pop_front() {
+        // each level is one less deep than its parent, a corrupted tree can't loop
+        while let Some((depth, item)) = queue.pop_front() {
             match item {
                 ExtentOrIdx::Extent(extent) => {
                     result.push(extent);
                 }
                 ExtentOrIdx::Idx(idx) => {
-                    let pos = idx.get_extent_loc() * block_size;
-                    reader.seek(SeekFrom::Start(pos))?;
-                    for i in Self::parse_extents(&mut reader)? {
-                        queue.push_back(i);
+                    let loc = idx.get_extent_loc();
+                    let mut block = vec![0; block_size as usize];
+                    let pos = block_pos(loc, block_size, 0)?;
+                    read_exact_at(&mut reader, pos, &mut block, "extent tree block")?;
+
+                    if let Some(seed) = csum_seed {
+                        let eh = ExtentHeader::from_reader(block.as_slice())?;
+                        if !checksum::verify_extent_block(seed, eh.max, &block) {
+                            return Err(ExtfsError::ExtentBlockChecksumMismatch(loc));
+                        }
+                    }
+                    let (found, entries) = Self::parse_extents(block.as_slice())?;
+                    // an index only exists at depth > 0
+                    let expected = depth - 1;
+                    if found != expected {
+                        return Err(ExtfsError::InvalidExtentDepth {
+                            block: loc,
+                            expected,
+                            found,
+                        });
                     }
+                    queue.extend(entries.into_iter().map(|x| (found, x)));
                 }
             }
         }
@@ -140,15 +360,76 @@ impl Inode {
     where
         R: Read + Seek,
     {
+        if self.uses_inline_data() {
+            // only the parent is stored, `.` and `..` are at offsets 0 and 2 like in the kernel
+            let parent =
+                u32::from_le_bytes([self.block[0], self.block[1], self.block[2], self.block[3]]);
+            let mut entries = vec![
+                (
+                    DirEntryEnum::dot_entry(
+                        self.ino as u32,
+                        DOT_DIR_NAME,
+                        feature_incompat_filetype,
+                    ),
+                    0,
+                ),
+                (
+                    DirEntryEnum::dot_entry(parent, DOTDOT_DIR_NAME, feature_incompat_filetype),
+                    2,
+                ),
+            ];
+            entries.extend(self.inline_dir_entries(feature_incompat_filetype)?);
+            let rd = ReadDir::new(reader, Vec::new(), block_size, feature_incompat_filetype)
+                .with_inline(entries);
+            return Ok(rd);
+        }
+
         let extents = self.extents(block_size, &mut reader)?;
-        let rd = ReadDir::new(reader, extents, block_size, feature_incompat_filetype);
+        let rd = ReadDir::new(reader, extents, block_size, feature_incompat_filetype)
+            .with_index(self.is_indexed());
         Ok(rd)
     }
 
-    pub fn read_file<R>(&self, block_size: u64, mut reader: R) -> Result<File<R>, ExtfsError>
+    /// Returns an iterator over the entries within a directory, starting from a position
+    /// returned by `ReadDir::position`.
+    pub fn read_dir_from<R>(
+        &self,
+        cookie: DirCookie,
+        block_size: u64,
+        feature_incompat_filetype: bool,
+        reader: R,
+    ) -> Result<ReadDir<R>, ExtfsError>
     where
         R: Read + Seek,
     {
+        let rd = self.read_dir(block_size, feature_incompat_filetype, reader)?;
+        Ok(rd.with_position(cookie))
+    }
+
+    /// Open the data of a regular file, other types of inodes give `InodeIsNotRegular`.
+    pub fn read_file<R>(&self, block_size: u64, reader: R) -> Result<File<R>, ExtfsError>
+    where
+        R: Read + Seek,
+    {
+        if !self.is_regular() {
+            return Err(ExtfsError::InodeIsNotRegular(self.file_type()));
+        }
+        self.open_data(block_size, reader)
+    }
+
+    /// Open the data blocks of any type of inode.
+    fn open_data<R>(&self, block_size: u64, mut reader: R) -> Result<File<R>, ExtfsError>
+    where
+        R: Read + Seek,
+    {
+        if self.uses_inline_data() {
+            return Ok(File::new_inline(
+                reader,
+                self.inline_data(),
+                self.get_size(),
+                block_size,
+            ));
+        }
         let extents = self.extents(block_size, &mut reader)?;
         let f = File::new(reader, extents, self.get_size(), block_size);
         Ok(f)
@@ -159,9 +440,11 @@ impl Inode {
         block_size: u64,
         mut reader: impl Read + Seek,
     ) -> Result<Vec<u8>, ExtfsError> {
-        let size = self.get_size() as usize;
-        if size <= self.block.len() {
-            return Ok(self.block[0..size].to_vec());
+        let size = self.get_size();
+        // a target of exactly 60 bytes doesn't fit with its NUL, see
+        // `ext4_inode_is_fast_symlink`
+        if size < self.block.len() as u64 {
+            return Ok(self.block[0..size as usize].to_vec());
         }
         self.read_bytes(block_size, &mut reader)
     }
@@ -171,19 +454,16 @@ impl Inode {
         block_size: u64,
         mut reader: impl Read + Seek,
     ) -> Result<Vec<u8>, ExtfsError> {
-        let mut size = self.get_size() as usize;
-        let extents = self.extents(block_size, &mut reader)?;
-        let mut data = Vec::new();
-        for extent in extents {
-            if size == 0 {
-                break;
-            }
-            let buf = extent.read_bytes(block_size, &mut reader, 0, size as u64)?;
-            if size >= buf.len() {
-                size -= buf.len();
-            }
-            data.extend(buf);
+        // refuse up front instead of failing to grow the buffer
+        let size = to_usize(self.get_size())?;
+        if self.uses_inline_data() {
+            let mut data = self.inline_data();
+            data.truncate(size);
+            return Ok(data);
         }
+        let mut data = Vec::new();
+        self.open_data(block_size, &mut reader)?
+            .read_to_end(&mut data)?;
 
         Ok(data)
     }
@@ -191,11 +471,155 @@ impl Inode {
 
 #[cfg(test)]
 mod tests {
+    use std::io::Cursor;
+
     use super::Inode;
+    use crate::{testutil::dir_entry, ExtfsError};
 
     #[test]
     fn test_inode() {
         let size = std::mem::size_of::<Inode>();
         println!("{}", size);
     }
+
+    #[test]
+    fn test_file_acl() {
+        let mut buf = vec![0; 128];
+        buf[0x68..0x6C].copy_from_slice(&0x89AB_CDEFu32.to_le_bytes());
+        buf[0x76..0x78].copy_from_slice(&0x0123u16.to_le_bytes());
+        let inode = Inode::from_reader(buf.as_slice(), 128).unwrap();
+        assert_eq!(inode.get_file_acl(), 0x0123_89AB_CDEF);
+    }
+
+    #[test]
+    fn test_size_high() {
+        let with_mode = |mode: u16| {
+            let mut buf = vec![0; 128];
+            buf[0..2].copy_from_slice(&mode.to_le_bytes());
+            buf[0x4..0x8].copy_from_slice(&1024u32.to_le_bytes());
+            buf[0x6C..0x70].copy_from_slice(&1u32.to_le_bytes());
+            Inode::from_reader(buf.as_slice(), 128).unwrap()
+        };
+        assert_eq!(with_mode(0o100644).get_size(), (1 << 32) + 1024);
+        assert_eq!(with_mode(0o040755).get_size(), 1024);
+        assert_eq!(with_mode(0o120777).get_size(), 1024);
+    }
+
+    #[test]
+    fn test_inline_dir_entries() {
+        let mut buf = vec![0; 256];
+        // directory with inline data, extra_isize = 32
+        buf[0..2].copy_from_slice(&0o040755u16.to_le_bytes());
+        buf[0x20..0x24].copy_from_slice(&0x1000_0000u32.to_le_bytes());
+        buf[128..130].copy_from_slice(&32u16.to_le_bytes());
+
+        // parent, then the entries in i_block
+        buf[0x28..0x2C].copy_from_slice(&2u32.to_le_bytes());
+        buf[0x2C..0x38].copy_from_slice(&dir_entry(12, 12, b"a"));
+        buf[0x38..0x64].copy_from_slice(&dir_entry(0, 44, b""));
+
+        // system.data with two more entries
+        let area = 160;
+        buf[area..area + 4].copy_from_slice(&0xEA02_0000u32.to_le_bytes());
+        buf[area + 4] = 4;
+        buf[area + 5] = 7;
+        buf[area + 6..area + 8].copy_from_slice(&40u16.to_le_bytes());
+        buf[area + 12..area + 16].copy_from_slice(&28u32.to_le_bytes());
+        buf[area + 20..area + 24].copy_from_slice(b"data");
+        let value = [dir_entry(13, 12, b"bb"), dir_entry(14, 16, b"ccc")].concat();
+        buf[area + 44..area + 72].copy_from_slice(&value);
+
+        let inode = Inode::from_reader(buf.as_slice(), 256).unwrap();
+        assert!(inode.uses_inline_data());
+        let names: Vec<(String, Option<u32>, u64)> = inode
+            .inline_dir_entries(true)
+            .unwrap()
+            .iter()
+            .map(|(e, offset)| (e.get_name_str(), e.get_ino(), *offset))
+            .collect();
+        assert_eq!(
+            names,
+            [
+                ("a".to_string(), Some(12), 4),
+                ("bb".to_string(), Some(13), 60),
+                ("ccc".to_string(), Some(14), 72)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_extent_depth() {
+        let header = |entries: u16, depth: u16| {
+            let mut b = Vec::new();
+            b.extend(0xF30Au16.to_le_bytes());
+            b.extend(entries.to_le_bytes());
+            b.extend(4u16.to_le_bytes());
+            b.extend(depth.to_le_bytes());
+            b.extend(0u32.to_le_bytes());
+            b
+        };
+        let with_root = |root: &[u8]| {
+            let mut buf = vec![0; 128];
+            buf[0..2].copy_from_slice(&0o100644u16.to_le_bytes());
+            buf[0x20..0x24].copy_from_slice(&0x8_0000u32.to_le_bytes());
+            buf[0x28..0x28 + root.len()].copy_from_slice(root);
+            Inode::from_reader(buf.as_slice(), 128).unwrap()
+        };
+
+        let inode = with_root(&header(0, 6));
+        assert!(matches!(
+            inode.extents(1024, Cursor::new(Vec::new())),
+            Err(ExtfsError::ExtentTreeTooDeep(6))
+        ));
+
+        // an index at depth 2 pointing to block 1, which points to itself
+        let mut root = header(1, 2);
+        root.extend([0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0]);
+        let mut image = vec![0; 2048];
+        image[1024..1024 + root.len()].copy_from_slice(&root);
+        let inode = with_root(&root);
+        assert!(matches!(
+            inode.extents(1024, Cursor::new(image)),
+            Err(ExtfsError::InvalidExtentDepth {
+                block: 1,
+                expected: 1,
+                found: 2
+            })
+        ));
+    }
+
+    #[test]
+    fn test_read_empty_dir() {
+        let mut buf = vec![0; 128];
+        buf[0..2].copy_from_slice(&0o040755u16.to_le_bytes());
+        let inode = Inode::from_reader(buf.as_slice(), 128).unwrap();
+        let rd = inode.read_dir(1024, true, Cursor::new(Vec::new())).unwrap();
+        assert_eq!(rd.count(), 0);
+
+        // an inline directory holding only its parent
+        buf[0x20..0x24].copy_from_slice(&0x1000_0000u32.to_le_bytes());
+        buf[0x28..0x2C].copy_from_slice(&2u32.to_le_bytes());
+        buf[0x30..0x32].copy_from_slice(&56u16.to_le_bytes());
+        let inode = Inode::from_reader(buf.as_slice(), 128).unwrap();
+        let rd = inode.read_dir(1024, true, Cursor::new(Vec::new())).unwrap();
+        assert_eq!(rd.count(), 0);
+    }
+
+    #[test]
+    fn test_inode_extra_fields() {
+        let mut buf = vec![0xFF; 256];
+        // only extra_isize and checksum_hi are valid
+        buf[128..130].copy_from_slice(&4u16.to_le_bytes());
+        let inode = Inode::from_reader(buf.as_slice(), 256).unwrap();
+        assert_eq!(inode.extra_isize, 4);
+        assert_eq!(inode.checksum_hi, 0xFFFF);
+        assert_eq!(inode.ctime_extra, 0);
+        assert_eq!(inode.projid, 0);
+
+        let buf = vec![0xFF; 128 + 160];
+        let inode = Inode::from_reader(buf.as_slice(), 128).unwrap();
+        assert_eq!(inode.mtime, u32::MAX);
+        assert_eq!(inode.extra_isize, 0);
+        assert_eq!(inode.crtime, 0);
+    }
 }
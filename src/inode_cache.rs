@@ -0,0 +1,78 @@
+use std::collections::{HashMap, VecDeque};
+
+use super::inode::Inode;
+
+/// The most recently used inodes keyed by inode number, evicting the least recently used one
+/// once `capacity` is reached. A capacity of 0 caches nothing.
+#[derive(Debug, Default)]
+pub(crate) struct InodeCache {
+    capacity: usize,
+    inodes: HashMap<u64, Inode>,
+    /// Inode numbers from the least to the most recently used, it's meant to stay small.
+    order: VecDeque<u64>,
+}
+
+impl InodeCache {
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            ..Self::default()
+        }
+    }
+
+    pub(crate) fn get(&mut self, ino: u64) -> Option<Inode> {
+        let inode = self.inodes.get(&ino)?.clone();
+        self.touch(ino);
+        Some(inode)
+    }
+
+    pub(crate) fn insert(&mut self, ino: u64, inode: Inode) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.inodes.insert(ino, inode).is_some() {
+            self.touch(ino);
+            return;
+        }
+        self.order.push_back(ino);
+        if self.order.len() > self.capacity {
+            if let Some(lru) = self.order.pop_front() {
+                self.inodes.remove(&lru);
+            }
+        }
+    }
+
+    /// Mark an inode as the most recently used.
+    fn touch(&mut self, ino: u64) {
+        if let Some(pos) = self.order.iter().position(|&x| x == ino) {
+            self.order.remove(pos);
+        }
+        self.order.push_back(ino);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::InodeCache;
+    use crate::testutil;
+
+    #[test]
+    fn test_lru() {
+        let fs = testutil::from_vec(testutil::ImageBuilder::new().build()).unwrap();
+        let inode = fs.inode(2).unwrap();
+
+        let mut cache = InodeCache::new(2);
+        cache.insert(1, inode.clone());
+        cache.insert(2, inode.clone());
+        assert!(cache.get(1).is_some());
+        // 2 is the least recently used now
+        cache.insert(3, inode.clone());
+        assert!(cache.get(2).is_none());
+        assert!(cache.get(1).is_some());
+        assert!(cache.get(3).is_some());
+
+        let mut cache = InodeCache::new(0);
+        cache.insert(1, inode);
+        assert!(cache.get(1).is_none());
+    }
+}
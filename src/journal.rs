@@ -0,0 +1,307 @@
+// https://www.kernel.org/doc/html/latest/filesystems/ext4/journal.html
+//
+// All fields of the journal are big-endian.
+
+use super::errors::ExtfsError;
+
+/// Magic number of every journal metadata block.
+pub const JBD2_MAGIC: u32 = 0xC03B_3998;
+
+const BLOCKTYPE_DESCRIPTOR: u32 = 1;
+const BLOCKTYPE_COMMIT: u32 = 2;
+const BLOCKTYPE_SUPERBLOCK_V1: u32 = 3;
+const BLOCKTYPE_SUPERBLOCK_V2: u32 = 4;
+const BLOCKTYPE_REVOKE: u32 = 5;
+
+/// Block numbers are 64bit.
+const INCOMPAT_64BIT: u32 = 0x2;
+/// Tags and blocks have crc32c checksums, v2 and v3 differ in the tag format.
+const INCOMPAT_CSUM_V2: u32 = 0x8;
+const INCOMPAT_CSUM_V3: u32 = 0x10;
+/// Fast commit blocks are reserved at the end of the journal.
+const INCOMPAT_FAST_COMMIT: u32 = 0x20;
+
+/// Default number of fast commit blocks if the super block doesn't set it.
+const DEFAULT_FC_BLOCKS: u32 = 256;
+
+/// The tag doesn't have a UUID, it's the same as the previous one.
+const TAG_FLAG_SAME_UUID: u32 = 0x2;
+/// Last tag of the descriptor block.
+const TAG_FLAG_LAST_TAG: u32 = 0x8;
+
+/// Size of `journal_header_s`.
+const HEADER_SIZE: usize = 12;
+/// Size of the UUID following a tag without `TAG_FLAG_SAME_UUID`.
+const UUID_SIZE: usize = 16;
+/// Size of the checksum tail of descriptor blocks.
+const TAIL_SIZE: usize = 4;
+
+/// A transaction found in the journal, see `FileSystem::journal_transactions`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JournalTransaction {
+    pub sequence: u32,
+    /// File system blocks logged by the transaction, in the order of their tags.
+    pub blocks: Vec<u64>,
+    /// File system blocks revoked by the transaction, earlier copies of them aren't replayed.
+    pub revoked: Vec<u64>,
+    /// Whether the commit block was found, recovery only replays committed transactions.
+    pub committed: bool,
+}
+
+impl JournalTransaction {
+    fn new(sequence: u32) -> Self {
+        Self {
+            sequence,
+            blocks: Vec::new(),
+            revoked: Vec::new(),
+            committed: false,
+        }
+    }
+}
+
+fn be_u16(buf: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(
+        buf.get(offset..offset + 2)?.try_into().ok()?,
+    ))
+}
+
+fn be_u32(buf: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(
+        buf.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+/// The fields of the journal super block needed to walk the log.
+#[derive(Debug)]
+pub(crate) struct JournalSuperBlock {
+    /// Total number of blocks in the journal.
+    maxlen: u32,
+    /// First block of the log.
+    first: u32,
+    /// Sequence number of the first transaction expected in the log.
+    sequence: u32,
+    /// Block of the first transaction, 0 if the journal is clean.
+    start: u32,
+    feature_incompat: u32,
+    num_fc_blocks: u32,
+}
+
+impl JournalSuperBlock {
+    pub fn from_bytes(buf: &[u8]) -> Result<Self, ExtfsError> {
+        let field = |offset| be_u32(buf, offset).unwrap_or(0);
+        let magic = field(0x0);
+        let block_type = field(0x4);
+        if magic != JBD2_MAGIC
+            || (block_type != BLOCKTYPE_SUPERBLOCK_V1 && block_type != BLOCKTYPE_SUPERBLOCK_V2)
+        {
+            return Err(ExtfsError::InvalidJournalMagic(magic));
+        }
+
+        // features only exist in v2
+        let v2 = block_type == BLOCKTYPE_SUPERBLOCK_V2;
+        Ok(Self {
+            maxlen: field(0x10),
+            first: field(0x14),
+            sequence: field(0x18),
+            start: field(0x1C),
+            feature_incompat: if v2 { field(0x28) } else { 0 },
+            num_fc_blocks: if v2 { field(0x54) } else { 0 },
+        })
+    }
+
+    fn has_incompat(&self, feature: u32) -> bool {
+        self.feature_incompat & feature != 0
+    }
+
+    /// Get the block after the end of the log.
+    fn last(&self) -> u32 {
+        if !self.has_incompat(INCOMPAT_FAST_COMMIT) {
+            return self.maxlen;
+        }
+        let fc_blocks = match self.num_fc_blocks {
+            0 => DEFAULT_FC_BLOCKS,
+            n => n,
+        };
+        self.maxlen.saturating_sub(fc_blocks)
+    }
+
+    /// Get the size of a block tag in descriptor blocks, like `journal_tag_bytes`.
+    fn tag_bytes(&self) -> usize {
+        if self.has_incompat(INCOMPAT_CSUM_V3) {
+            return 16;
+        }
+        let mut size = 12;
+        if self.has_incompat(INCOMPAT_CSUM_V2) {
+            size += 2;
+        }
+        if self.has_incompat(INCOMPAT_64BIT) {
+            size
+        } else {
+            size - 4
+        }
+    }
+
+    fn has_csum(&self) -> bool {
+        self.has_incompat(INCOMPAT_CSUM_V2) || self.has_incompat(INCOMPAT_CSUM_V3)
+    }
+
+    /// Get the file system blocks tagged by a descriptor block.
+    fn parse_tags(&self, buf: &[u8]) -> Vec<u64> {
+        let tag_bytes = self.tag_bytes();
+        let end = if self.has_csum() {
+            buf.len().saturating_sub(TAIL_SIZE)
+        } else {
+            buf.len()
+        };
+
+        let mut blocks = Vec::new();
+        let mut pos = HEADER_SIZE;
+        while pos + tag_bytes <= end {
+            let (Some(lo), Some(flags)) = (
+                be_u32(buf, pos),
+                if self.has_incompat(INCOMPAT_CSUM_V3) {
+                    be_u32(buf, pos + 4)
+                } else {
+                    be_u16(buf, pos + 6).map(u32::from)
+                },
+            ) else {
+                break;
+            };
+            let hi = if self.has_incompat(INCOMPAT_64BIT) {
+                be_u32(buf, pos + 8).unwrap_or(0)
+            } else {
+                0
+            };
+            blocks.push((hi as u64) << 32 | lo as u64);
+
+            pos += tag_bytes;
+            if flags & TAG_FLAG_SAME_UUID == 0 {
+                pos += UUID_SIZE;
+            }
+            if flags & TAG_FLAG_LAST_TAG != 0 {
+                break;
+            }
+        }
+        blocks
+    }
+
+    /// Get the file system blocks listed by a revoke block.
+    fn parse_revoke(&self, buf: &[u8]) -> Vec<u64> {
+        // r_count is the number of bytes used, including the 16 bytes header
+        let count = be_u32(buf, HEADER_SIZE).unwrap_or(0) as usize;
+        let records = buf.get(16..count.min(buf.len())).unwrap_or_default();
+        if self.has_incompat(INCOMPAT_64BIT) {
+            records
+                .chunks_exact(8)
+                .map(|x| u64::from_be_bytes(x.try_into().unwrap()))
+                .collect()
+        } else {
+            records
+                .chunks_exact(4)
+                .map(|x| u32::from_be_bytes(x.try_into().unwrap()) as u64)
+                .collect()
+        }
+    }
+
+    /// Walk the log from its start like the scan pass of the recovery, without replaying it.
+    ///
+    /// `read_block` reads a block of the journal by its number, `None` if it isn't mapped. The
+    /// walk stops at the first block which isn't the expected metadata block, a trailing
+    /// transaction without commit block is returned with `committed` unset.
+    pub fn transactions<F>(&self, mut read_block: F) -> Result<Vec<JournalTransaction>, ExtfsError>
+    where
+        F: FnMut(u64) -> Result<Option<Vec<u8>>, ExtfsError>,
+    {
+        let mut transactions = Vec::new();
+        // the journal is clean
+        if self.start == 0 {
+            return Ok(transactions);
+        }
+
+        let (first, last) = (self.first as u64, self.last() as u64);
+        if first >= last {
+            return Ok(transactions);
+        }
+        let next = |block: u64, n: u64| {
+            let b = block + n;
+            if b >= last {
+                first + (b - last) % (last - first)
+            } else {
+                b
+            }
+        };
+
+        let mut block = self.start as u64;
+        let mut tx = JournalTransaction::new(self.sequence);
+        // the log can't be longer than the journal, even if it wraps around
+        let mut remaining = last - first;
+        while remaining > 0 {
+            remaining -= 1;
+            let Some(buf) = read_block(block)? else {
+                break;
+            };
+            if be_u32(&buf, 0) != Some(JBD2_MAGIC) || be_u32(&buf, 8) != Some(tx.sequence) {
+                break;
+            }
+
+            match be_u32(&buf, 4) {
+                Some(BLOCKTYPE_DESCRIPTOR) => {
+                    let tags = self.parse_tags(&buf);
+                    // the logged blocks follow the descriptor
+                    block = next(block, 1 + tags.len() as u64);
+                    remaining = remaining.saturating_sub(tags.len() as u64);
+                    tx.blocks.extend(tags);
+                    continue;
+                }
+                Some(BLOCKTYPE_REVOKE) => tx.revoked.extend(self.parse_revoke(&buf)),
+                Some(BLOCKTYPE_COMMIT) => {
+                    tx.committed = true;
+                    let sequence = tx.sequence.wrapping_add(1);
+                    transactions.push(std::mem::replace(
+                        &mut tx,
+                        JournalTransaction::new(sequence),
+                    ));
+                }
+                _ => break,
+            }
+            block = next(block, 1);
+        }
+
+        if !tx.blocks.is_empty() || !tx.revoked.is_empty() {
+            transactions.push(tx);
+        }
+        Ok(transactions)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        JournalSuperBlock, INCOMPAT_64BIT, INCOMPAT_CSUM_V3, TAG_FLAG_LAST_TAG, TAG_FLAG_SAME_UUID,
+    };
+
+    #[test]
+    fn test_parse_tags_csum_v3() {
+        let sb = JournalSuperBlock {
+            maxlen: 1024,
+            first: 1,
+            sequence: 1,
+            start: 1,
+            feature_incompat: INCOMPAT_64BIT | INCOMPAT_CSUM_V3,
+            num_fc_blocks: 0,
+        };
+        assert_eq!(sb.tag_bytes(), 16);
+
+        let mut buf = vec![0; 1024];
+        // the first tag is followed by a UUID
+        buf[12..16].copy_from_slice(&100u32.to_be_bytes());
+        buf[28 + 16..28 + 20].copy_from_slice(&101u32.to_be_bytes());
+        buf[28 + 20..28 + 24]
+            .copy_from_slice(&(TAG_FLAG_SAME_UUID | TAG_FLAG_LAST_TAG).to_be_bytes());
+        buf[28 + 24..28 + 28].copy_from_slice(&1u32.to_be_bytes());
+        // ignored after the last tag
+        buf[60..64].copy_from_slice(&102u32.to_be_bytes());
+
+        assert_eq!(sb.parse_tags(&buf), [100, (1 << 32) | 101]);
+    }
+}
@@ -1,23 +1,58 @@
+mod block_device;
+mod checksum;
 mod codec;
 #[allow(dead_code)]
 mod constants;
 mod descriptor;
+mod dir_hash;
 #[allow(dead_code)]
 mod entry;
 mod errors;
 #[allow(dead_code)]
 mod extent;
+#[cfg(unix)]
+mod extract;
 mod file;
+mod file_type;
 mod fs;
+mod handle;
+mod htree;
 mod inode;
+mod inode_cache;
+mod journal;
 mod metadata;
+mod mount_options;
+mod options;
+mod partition;
+mod read_at;
 mod read_dir;
+mod special_inode;
 mod superblock;
+#[cfg(any(test, feature = "test-util"))]
+pub mod testutil;
 mod utils;
+mod walk_dir;
+mod xattr;
 
-pub use entry::DirEntryEnum;
+pub use block_device::BlockDevice;
+pub use checksum::ChecksumKind;
+pub use descriptor::{BlockGroupInfo, FreeBlocksCheck, InodeUsage};
+pub use dir_hash::name_hash;
+pub use entry::{DirEntryEnum, DirEntryInfo};
 pub use errors::ExtfsError;
+pub use extent::{DataRun, Extent, FragStats};
 pub use file::File;
+pub use file_type::FileType;
 pub use fs::FileSystem;
+pub use handle::Entry;
+pub use inode::Inode;
+pub use journal::JournalTransaction;
 pub use metadata::Metadata;
-pub use read_dir::ReadDir;
+pub use mount_options::MountOptions;
+pub use options::FileSystemOptions;
+pub use partition::Partition;
+pub use read_at::{ReadAt, ReadAtCursor};
+pub use read_dir::{DirCookie, ReadDir};
+pub use special_inode::SpecialInode;
+pub use superblock::{BlkidInfo, CreatorOs, Provenance, SuperBlockView};
+pub use walk_dir::WalkDir;
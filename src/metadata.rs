@@ -3,55 +3,257 @@ use std::{
     time::{SystemTime, UNIX_EPOCH},
 };
 
-use super::inode::Inode;
+use super::{
+    constants::{
+        INODE_FLAG_CASEFOLD, INODE_FLAG_ENCRYPT, INODE_FLAG_EXTENTS, INODE_FLAG_HUGE_FILE,
+        INODE_FLAG_INDEX, INODE_FLAG_INLINE_DATA, INODE_FLAG_TOPDIR, INODE_FLAG_VERITY,
+    },
+    file_type::FileType,
+    inode::Inode,
+};
 
+/// The attributes of a file, copied out of its inode so that keeping many of them is cheap.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Metadata {
-    inode: Inode,
+    mode: u16,
+    uid: u32,
+    gid: u32,
+    size: u64,
+    file_acl: u64,
+    flags: u32,
+    atime: u32,
+    mtime: u32,
+    ctime: u32,
+    checksum_valid: Option<bool>,
+    extent_tree_depth: Option<u16>,
 }
 
 impl Metadata {
     pub fn new(inode: Inode) -> Self {
-        Self { inode }
+        Self::from(&inode)
+    }
+
+    /// Get the file type, `FileType::Unknown` if the type bits of the mode are invalid.
+    pub fn file_type(&self) -> FileType {
+        FileType::from_mode(self.mode)
     }
 
     pub fn is_dir(&self) -> bool {
-        self.inode.is_dir()
+        self.file_type() == FileType::Directory
     }
     pub fn is_file(&self) -> bool {
-        self.inode.is_regular()
+        self.file_type() == FileType::Regular
     }
     pub fn is_symlink(&self) -> bool {
-        self.inode.is_symlink()
+        self.file_type() == FileType::Symlink
     }
 
     pub fn len(&self) -> u64 {
-        self.inode.get_size()
+        self.size
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Get the lower 16 bits of the owner, see `uid32` for the full id.
     pub fn uid(&self) -> u16 {
-        self.inode.uid
+        self.uid as u16
     }
 
+    /// Get the lower 16 bits of the group, see `gid32` for the full id.
     pub fn gid(&self) -> u16 {
-        self.inode.gid
+        self.gid as u16
+    }
+
+    /// Get the owner with its upper bits from osd2 where the creator OS keeps them.
+    pub fn uid32(&self) -> u32 {
+        self.uid
+    }
+
+    /// Get the group with its upper bits from osd2 where the creator OS keeps them.
+    pub fn gid32(&self) -> u32 {
+        self.gid
+    }
+
+    /// Block number of the extended attribute block, 0 if there's none.
+    pub fn file_acl(&self) -> u64 {
+        self.file_acl
+    }
+
+    /// Get the raw inode flags, `EXT4_*_FL` as shown by `lsattr`.
+    pub fn flags(&self) -> u32 {
+        self.flags
+    }
+
+    /// Check whether the file is encrypted.
+    pub fn is_encrypted(&self) -> bool {
+        self.flags() & INODE_FLAG_ENCRYPT != 0
+    }
+
+    /// Check whether the directory has case-insensitive names.
+    pub fn is_casefold(&self) -> bool {
+        self.flags() & INODE_FLAG_CASEFOLD != 0
+    }
+
+    /// Check whether the file is protected by fs-verity.
+    pub fn is_verity(&self) -> bool {
+        self.flags() & INODE_FLAG_VERITY != 0
+    }
+
+    /// Check whether the block count is in units of file system blocks.
+    pub fn is_huge_file(&self) -> bool {
+        self.flags() & INODE_FLAG_HUGE_FILE != 0
+    }
+
+    /// Check whether the directory is the top of a hierarchy for the block allocator.
+    pub fn is_topdir(&self) -> bool {
+        self.flags() & INODE_FLAG_TOPDIR != 0
+    }
+
+    /// Check whether the directory is indexed by a hash tree, lookups in it don't need to scan
+    /// every entry.
+    pub fn is_indexed_dir(&self) -> bool {
+        self.is_dir() && self.flags() & INODE_FLAG_INDEX != 0
+    }
+
+    /// Check whether the data is mapped by extents.
+    pub fn uses_extents(&self) -> bool {
+        self.flags() & INODE_FLAG_EXTENTS != 0
+    }
+
+    /// Check whether the data is stored inline in the inode.
+    pub fn uses_inline_data(&self) -> bool {
+        self.flags() & INODE_FLAG_INLINE_DATA != 0
+    }
+
+    /// Check whether the checksum of the inode matched when it was read, `None` if the
+    /// metadata_csum feature is disabled.
+    ///
+    /// Unless the file system was opened in strict mode, a mismatch doesn't fail the read, so a
+    /// scan can report bad inodes and go on.
+    pub fn checksum_valid(&self) -> Option<bool> {
+        self.checksum_valid
+    }
+
+    /// Get the depth of the extent tree, 0 if all the extents fit in the inode and more for
+    /// files fragmented in many extents. `None` if the file doesn't use extents.
+    pub fn extent_tree_depth(&self) -> Option<u16> {
+        self.extent_tree_depth
     }
 
     pub fn permissions(&self) -> u16 {
-        self.inode.mode & 0o777
+        self.mode & 0o777
     }
 
     pub fn modified(&self) -> io::Result<SystemTime> {
-        let t = UNIX_EPOCH + std::time::Duration::from_secs(self.inode.mtime as u64);
+        let t = UNIX_EPOCH + std::time::Duration::from_secs(self.mtime as u64);
         Ok(t)
     }
 
     pub fn accessed(&self) -> io::Result<SystemTime> {
-        let t = UNIX_EPOCH + std::time::Duration::from_secs(self.inode.atime as u64);
+        let t = UNIX_EPOCH + std::time::Duration::from_secs(self.atime as u64);
         Ok(t)
     }
 
     pub fn created(&self) -> io::Result<SystemTime> {
-        let t = UNIX_EPOCH + std::time::Duration::from_secs(self.inode.ctime as u64);
+        let t = UNIX_EPOCH + std::time::Duration::from_secs(self.ctime as u64);
         Ok(t)
     }
 }
+
+impl From<&Inode> for Metadata {
+    fn from(inode: &Inode) -> Self {
+        Self {
+            mode: inode.mode,
+            uid: inode.get_uid(),
+            gid: inode.get_gid(),
+            size: inode.get_size(),
+            file_acl: inode.get_file_acl(),
+            flags: inode.flags,
+            atime: inode.atime,
+            mtime: inode.mtime,
+            ctime: inode.ctime,
+            checksum_valid: inode.checksum_valid,
+            extent_tree_depth: inode.extent_tree_depth(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Metadata;
+    use crate::{file_type::FileType, inode::Inode};
+
+    fn with_flags(flags: u32) -> Metadata {
+        let mut buf = vec![0; 128];
+        buf[0x20..0x24].copy_from_slice(&flags.to_le_bytes());
+        Metadata::new(Inode::from_reader(buf.as_slice(), 128).unwrap())
+    }
+
+    #[test]
+    fn test_flags() {
+        // encrypt | topdir | extents | casefold
+        let m = with_flags(0x4008_0800 | 0x2_0000);
+        assert_eq!(m.flags(), 0x400A_0800);
+        assert!(m.is_encrypted());
+        assert!(m.is_topdir());
+        assert!(m.uses_extents());
+        assert!(m.is_casefold());
+        assert!(!m.is_verity());
+        assert!(!m.is_huge_file());
+        assert!(!m.uses_inline_data());
+        // the flag is only meaningful on directories
+        assert!(!with_flags(0x1000).is_indexed_dir());
+
+        // verity | huge_file | inline_data
+        let m = with_flags(0x1014_0000);
+        assert!(m.is_verity());
+        assert!(m.is_huge_file());
+        assert!(m.uses_inline_data());
+        assert!(!m.uses_extents());
+        assert!(!m.is_encrypted());
+    }
+
+    #[test]
+    fn test_extent_tree_depth() {
+        let mut buf = vec![0; 128];
+        buf[0x20..0x24].copy_from_slice(&0x8_0000u32.to_le_bytes());
+        buf[0x28..0x2A].copy_from_slice(&0xF30Au16.to_le_bytes());
+        buf[0x2E..0x30].copy_from_slice(&2u16.to_le_bytes());
+        let m = Metadata::new(Inode::from_reader(buf.as_slice(), 128).unwrap());
+        assert_eq!(m.extent_tree_depth(), Some(2));
+
+        // a bad magic
+        buf[0x28] = 0;
+        let m = Metadata::new(Inode::from_reader(buf.as_slice(), 128).unwrap());
+        assert_eq!(m.extent_tree_depth(), None);
+        assert_eq!(with_flags(0).extent_tree_depth(), None);
+    }
+
+    #[test]
+    fn test_file_type() {
+        let with_mode = |mode: u16| {
+            let mut buf = vec![0; 128];
+            buf[0..2].copy_from_slice(&mode.to_le_bytes());
+            Metadata::new(Inode::from_reader(buf.as_slice(), 128).unwrap())
+        };
+        assert_eq!(with_mode(0o100644).file_type(), FileType::Regular);
+        assert_eq!(with_mode(0o040755).file_type(), FileType::Directory);
+        assert_eq!(with_mode(0o120777).file_type(), FileType::Symlink);
+        assert_eq!(with_mode(0o020600).file_type(), FileType::CharacterDevice);
+        assert_eq!(with_mode(0o060600).file_type(), FileType::BlockDevice);
+        assert_eq!(with_mode(0o010600).file_type(), FileType::Fifo);
+        assert_eq!(with_mode(0o140755).file_type(), FileType::Socket);
+
+        // a plain copy, without the inode
+        let m = with_mode(0o100644);
+        let copy = m;
+        assert_eq!(copy, m);
+        assert!(std::mem::size_of::<Metadata>() <= 48);
+
+        let m = with_mode(0x3000 | 0o644);
+        assert_eq!(m.file_type(), FileType::Unknown(0x3000));
+        assert!(!m.is_dir() && !m.is_file() && !m.is_symlink());
+    }
+}
@@ -0,0 +1,73 @@
+use super::constants::{
+    DEFM_ACL, DEFM_BLOCK_VALIDITY, DEFM_BSDGROUPS, DEFM_DEBUG, DEFM_DISCARD, DEFM_JMODE,
+    DEFM_JMODE_DATA, DEFM_JMODE_ORDERED, DEFM_JMODE_WBACK, DEFM_NOBARRIER, DEFM_NODELALLOC,
+    DEFM_UID16, DEFM_XATTR_USER,
+};
+
+const FLAG_NAMES: [(u32, &str); 9] = [
+    (DEFM_DEBUG, "debug"),
+    (DEFM_BSDGROUPS, "bsdgroups"),
+    (DEFM_XATTR_USER, "user_xattr"),
+    (DEFM_ACL, "acl"),
+    (DEFM_UID16, "uid16"),
+    (DEFM_NOBARRIER, "nobarrier"),
+    (DEFM_BLOCK_VALIDITY, "block_validity"),
+    (DEFM_DISCARD, "discard"),
+    (DEFM_NODELALLOC, "nodelalloc"),
+];
+
+/// Default mount options recorded in the super block (`s_default_mount_opts`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MountOptions(u32);
+
+impl MountOptions {
+    pub(crate) fn new(bits: u32) -> Self {
+        Self(bits)
+    }
+
+    /// Get the raw bitmask.
+    pub fn bits(&self) -> u32 {
+        self.0
+    }
+
+    /// Get the journal mode, one of `journal_data`, `journal_data_ordered` or `journal_data_writeback`.
+    pub fn journal_mode(&self) -> Option<&'static str> {
+        match self.0 & DEFM_JMODE {
+            DEFM_JMODE_DATA => Some("journal_data"),
+            DEFM_JMODE_ORDERED => Some("journal_data_ordered"),
+            DEFM_JMODE_WBACK => Some("journal_data_writeback"),
+            _ => None,
+        }
+    }
+
+    /// Get names of the options which are set, as printed by `dumpe2fs`.
+    pub fn names(&self) -> Vec<&'static str> {
+        let mut names = Vec::new();
+        for &(bit, name) in FLAG_NAMES.iter() {
+            if self.0 & bit != 0 {
+                names.push(name);
+            }
+            // dumpe2fs prints the journal mode right after uid16
+            if bit == DEFM_UID16 {
+                names.extend(self.journal_mode());
+            }
+        }
+        names
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MountOptions;
+
+    #[test]
+    fn test_mount_options() {
+        let opts = MountOptions::new(0x000C);
+        assert_eq!(opts.names(), ["user_xattr", "acl"]);
+        assert_eq!(opts.journal_mode(), None);
+
+        let opts = MountOptions::new(0x0468);
+        assert_eq!(opts.journal_mode(), Some("journal_data_writeback"));
+        assert_eq!(opts.names(), ["acl", "journal_data_writeback", "discard"]);
+    }
+}
@@ -0,0 +1,150 @@
+use std::io::{Read, Seek};
+
+use super::{errors::ExtfsError, fs::FileSystem, partition::Partition};
+
+/// Options to open a file system, the common cases also have `FileSystem::from_*` shortcuts.
+///
+/// Without `strict`, checksums aren't verified and a corrupted structure only fails the
+/// operation reading it.
+#[derive(Debug, Clone, Default)]
+pub struct FileSystemOptions {
+    pub(crate) strict: bool,
+    offset: u64,
+    pub(crate) inode_cache: usize,
+    pub(crate) tolerate_corruption: bool,
+}
+
+impl FileSystemOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Verify checksums of metadata as it's read, see `FileSystem::from_reader_strict`.
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Read the file system starting at byte `offset` of the reader, e.g. a partition of a whole
+    /// disk image, see `FileSystem::from_reader_at`. It's 0 by default.
+    pub fn offset(mut self, offset: u64) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    /// Keep the `n` most recently used inodes in memory, so repeated lookups of the same paths
+    /// don't read the inode table again. It's 0 by default, caching nothing.
+    pub fn inode_cache(mut self, n: usize) -> Self {
+        self.inode_cache = n;
+        self
+    }
+
+    /// Skip directory entries that fail to parse instead of failing on them, and entries whose
+    /// inode can't be read while walking, to salvage what's readable from a damaged image. It's
+    /// off by default.
+    pub fn tolerate_corruption(mut self, tolerate: bool) -> Self {
+        self.tolerate_corruption = tolerate;
+        self
+    }
+
+    /// Open the file system in `reader`, wrapped in a `Partition` at the offset.
+    pub fn open<R: Read + Seek>(&self, reader: R) -> Result<FileSystem<Partition<R>>, ExtfsError> {
+        self.open_unwrapped(Partition::new(reader, self.offset))
+    }
+
+    /// Open the file system starting at byte 0 of `reader`, ignoring the offset.
+    pub(crate) fn open_unwrapped<R: Read + Seek>(
+        &self,
+        reader: R,
+    ) -> Result<FileSystem<R>, ExtfsError> {
+        FileSystem::new(reader, self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::FileSystemOptions;
+    use crate::ExtfsError;
+
+    #[test]
+    fn test_options() {
+        let data = std::fs::read("testdata/test.ext4").unwrap();
+        let fs = FileSystemOptions::new().open(Cursor::new(&data)).unwrap();
+        assert_eq!(fs.read("/hello.txt").unwrap(), b"hello\n");
+
+        let mut disk = vec![0; 4096];
+        disk.extend(&data);
+        let fs = FileSystemOptions::new()
+            .strict(true)
+            .offset(4096)
+            .open(Cursor::new(&disk))
+            .unwrap();
+        assert_eq!(fs.read("/hello.txt").unwrap(), b"hello\n");
+
+        // corrupt the super block
+        let mut bad = data.clone();
+        bad[1024 + 0x200] ^= 0xFF;
+        assert!(FileSystemOptions::new().open(Cursor::new(&bad)).is_ok());
+        assert!(matches!(
+            FileSystemOptions::new()
+                .strict(true)
+                .open(Cursor::new(&bad)),
+            Err(ExtfsError::SuperBlockChecksumMismatch)
+        ));
+    }
+
+    #[test]
+    fn test_inode_cache() {
+        let data = std::fs::read("testdata/test.ext4").unwrap();
+        // i_size_lo of /hello.txt, inode 12 of the table at block 50
+        let size = 50 * 1024 + 11 * 128 + 4;
+
+        for (n, len) in [(0, 7), (8, 6)] {
+            let mut fs = FileSystemOptions::new()
+                .inode_cache(n)
+                .open_unwrapped(Cursor::new(data.clone()))
+                .unwrap();
+            assert_eq!(fs.metadata("/hello.txt").unwrap().len(), 6);
+            // a cached inode isn't read again
+            fs.reader_mut().get_mut()[size] = 7;
+            assert_eq!(fs.metadata("/hello.txt").unwrap().len(), len);
+        }
+    }
+
+    #[test]
+    fn test_tolerate_corruption() {
+        let mut data = std::fs::read("testdata/test.ext4").unwrap();
+        // /dir1 is block 1092 with `.`, `..`, dir11, dir12 and world.txt, break the rec_len of
+        // dir12 and point dir11 past the last inode
+        let block = 1092 * 1024;
+        data[block + 40 + 4..block + 40 + 6].copy_from_slice(&3u16.to_le_bytes());
+        data[block + 24..block + 28].copy_from_slice(&0xFF_FFFFu32.to_le_bytes());
+
+        let fs = FileSystemOptions::new().open(Cursor::new(&data)).unwrap();
+        assert!(matches!(
+            fs.list_dir("/dir1"),
+            Err(ExtfsError::CorruptDirEntry { .. })
+        ));
+        assert!(fs.walk_dir("/").unwrap().any(|x| x.is_err()));
+
+        let fs = FileSystemOptions::new()
+            .tolerate_corruption(true)
+            .open(Cursor::new(&data))
+            .unwrap();
+        let names: Vec<_> = fs
+            .list_dir("/dir1")
+            .unwrap()
+            .into_iter()
+            .map(|x| x.name)
+            .collect();
+        assert_eq!(names, ["dir11"]);
+        let paths: Vec<_> = fs.walk_dir("/").unwrap().map(|x| x.unwrap().0).collect();
+        assert!(paths.iter().any(|p| p.ends_with("hello.txt")));
+        assert!(paths.iter().any(|p| p.ends_with("dir1")));
+        assert!(!paths
+            .iter()
+            .any(|p| p.parent() == Some(std::path::Path::new("/dir1"))));
+    }
+}
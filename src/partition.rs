@@ -0,0 +1,136 @@
+use std::io::{self, Read, Seek, SeekFrom};
+
+/// A reader over a file system embedded at an offset of another reader, e.g. a partition of a
+/// whole disk image, created by `FileSystem::from_reader_at`.
+///
+/// Positions are relative to the start of the partition, so `SeekFrom::Start(0)` seeks to the
+/// partition offset of the inner reader.
+#[derive(Debug)]
+pub struct Partition<R> {
+    inner: R,
+    offset: u64,
+    len: Option<u64>,
+    /// Position in the partition.
+    pos: u64,
+    /// The inner reader is at `offset + pos`, it isn't after a failed seek.
+    synced: bool,
+}
+
+impl<R: Seek> Partition<R> {
+    pub fn new(inner: R, offset: u64) -> Self {
+        Self {
+            inner,
+            offset,
+            len: None,
+            pos: 0,
+            synced: false,
+        }
+    }
+
+    /// Limit the partition to `len` bytes, reads stop there and `SeekFrom::End` is relative to
+    /// it instead of the end of the inner reader.
+    pub fn with_len(mut self, len: u64) -> Self {
+        self.len = Some(len);
+        self
+    }
+
+    /// Get the offset of the partition in the inner reader.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Get the length of the partition, if it's limited.
+    pub fn get_len(&self) -> Option<u64> {
+        self.len
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /// Seek the inner reader to `pos` in the partition.
+    fn seek_inner(&mut self, pos: u64) -> io::Result<u64> {
+        let abs = self.offset.checked_add(pos).ok_or_else(invalid_seek)?;
+        // the inner position is unknown until a seek succeeds
+        self.synced = false;
+        self.inner.seek(SeekFrom::Start(abs))?;
+        self.pos = pos;
+        self.synced = true;
+        Ok(pos)
+    }
+}
+
+fn invalid_seek() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, "Invalid seek position")
+}
+
+impl<R: Read + Seek> Read for Partition<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if !self.synced {
+            self.seek_inner(self.pos)?;
+        }
+        let max = match self.len {
+            Some(len) => len.saturating_sub(self.pos).min(buf.len() as u64) as usize,
+            None => buf.len(),
+        };
+        let n = self.inner.read(&mut buf[..max])?;
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl<R: Seek> Seek for Partition<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let pos = match (pos, self.len) {
+            (SeekFrom::Start(offset), _) => Some(offset),
+            (SeekFrom::Current(offset), _) => self.pos.checked_add_signed(offset),
+            (SeekFrom::End(offset), Some(len)) => len.checked_add_signed(offset),
+            (SeekFrom::End(offset), None) => {
+                // the end is only known to the inner reader, the next read seeks back if the
+                // position turns out to be invalid
+                self.synced = false;
+                let end = self.inner.seek(SeekFrom::End(0))?;
+                end.checked_sub(self.offset)
+                    .and_then(|len| len.checked_add_signed(offset))
+            }
+        }
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Seek before the partition"))?;
+        self.seek_inner(pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Read, Seek, SeekFrom};
+
+    use super::Partition;
+
+    #[test]
+    fn test_seek() {
+        let mut p = Partition::new(Cursor::new((0..10).collect::<Vec<u8>>()), 4);
+        assert_eq!(p.seek(SeekFrom::Start(1)).unwrap(), 1);
+        let mut buf = [0; 2];
+        p.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, [5, 6]);
+        assert_eq!(p.stream_position().unwrap(), 3);
+        assert_eq!(p.seek(SeekFrom::End(-1)).unwrap(), 5);
+        assert!(p.seek(SeekFrom::Current(-6)).is_err());
+        // a failed seek doesn't move the position
+        assert!(p.seek(SeekFrom::End(-7)).is_err());
+        p.read_exact(&mut buf[..1]).unwrap();
+        assert_eq!(buf[0], 9);
+    }
+
+    #[test]
+    fn test_len() {
+        let mut p = Partition::new(Cursor::new((0..10).collect::<Vec<u8>>()), 2).with_len(4);
+        let mut buf = Vec::new();
+        p.read_to_end(&mut buf).unwrap();
+        assert_eq!(buf, [2, 3, 4, 5]);
+        assert_eq!(p.seek(SeekFrom::End(-1)).unwrap(), 3);
+        let mut buf = [0; 4];
+        assert_eq!(p.read(&mut buf).unwrap(), 1);
+        assert_eq!(buf[0], 5);
+        assert_eq!(p.read(&mut buf).unwrap(), 0);
+    }
+}
@@ -0,0 +1,144 @@
+use std::{
+    fs,
+    io::{self, Read, Seek, SeekFrom},
+    sync::{Arc, Mutex},
+};
+
+/// A source of positioned reads, like `std::os::unix::fs::FileExt`, e.g. an HTTP client
+/// issuing range requests.
+///
+/// Reads take `&self`, so a source can be shared by several file systems or threads. Use
+/// `FileSystem::from_read_at` to open a file system on it.
+///
+/// A file system reads everything through this trait from `&self`. Seeking needs exclusive
+/// access, so its `Read + Seek` reader is kept in a `Mutex` and read with the blanket impl
+/// below.
+pub trait ReadAt {
+    /// Read bytes at `offset` into `buf`, returning the number of bytes read, 0 at the end.
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> io::Result<usize>;
+}
+
+impl ReadAt for [u8] {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+        let start = usize::try_from(offset).map_or(self.len(), |x| x.min(self.len()));
+        let n = buf.len().min(self.len() - start);
+        buf[..n].copy_from_slice(&self[start..start + n]);
+        Ok(n)
+    }
+}
+
+impl ReadAt for Vec<u8> {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+        self.as_slice().read_at(buf, offset)
+    }
+}
+
+#[cfg(unix)]
+impl ReadAt for fs::File {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+        std::os::unix::fs::FileExt::read_at(self, buf, offset)
+    }
+}
+
+#[cfg(windows)]
+impl ReadAt for fs::File {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+        std::os::windows::fs::FileExt::seek_read(self, buf, offset)
+    }
+}
+
+/// Any `Read + Seek` reader behind a lock, the lock is held for the seek and the read.
+impl<R: Read + Seek> ReadAt for Mutex<R> {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+        let mut reader = self
+            .lock()
+            .map_err(|_| io::Error::other("Poisoned reader lock"))?;
+        reader.seek(SeekFrom::Start(offset))?;
+        reader.read(buf)
+    }
+}
+
+impl<T: ReadAt + ?Sized> ReadAt for &T {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+        (**self).read_at(buf, offset)
+    }
+}
+
+impl<T: ReadAt + ?Sized> ReadAt for Arc<T> {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+        (**self).read_at(buf, offset)
+    }
+}
+
+/// A `Read + Seek` reader over a `ReadAt` source, which only tracks the position.
+///
+/// `SeekFrom::End` isn't supported since the source has no length.
+#[derive(Debug)]
+pub struct ReadAtCursor<T> {
+    source: T,
+    pos: u64,
+}
+
+impl<T: ReadAt> ReadAtCursor<T> {
+    pub fn new(source: T) -> Self {
+        Self { source, pos: 0 }
+    }
+
+    /// Get a reference to the source.
+    pub fn get_ref(&self) -> &T {
+        &self.source
+    }
+
+    pub fn into_inner(self) -> T {
+        self.source
+    }
+}
+
+impl<T: ReadAt> Read for ReadAtCursor<T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.source.read_at(buf, self.pos)?;
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl<T: ReadAt> Seek for ReadAtCursor<T> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let pos = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::Current(offset) => self.pos.checked_add_signed(offset),
+            SeekFrom::End(_) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    "Seek from the end of a ReadAt source",
+                ))
+            }
+        }
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Invalid seek position"))?;
+        self.pos = pos;
+        Ok(pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Seek, SeekFrom};
+
+    use super::{ReadAt, ReadAtCursor};
+
+    #[test]
+    fn test_read_at() {
+        let data: Vec<u8> = (0..10).collect();
+        let mut buf = [0; 4];
+        assert_eq!(data.read_at(&mut buf, 8).unwrap(), 2);
+        assert_eq!(buf[..2], [8, 9]);
+        assert_eq!(data.read_at(&mut buf, 20).unwrap(), 0);
+
+        let mut cursor = ReadAtCursor::new(&data);
+        cursor.seek(SeekFrom::Start(3)).unwrap();
+        cursor.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, [3, 4, 5, 6]);
+        assert_eq!(cursor.seek(SeekFrom::Current(-2)).unwrap(), 5);
+        assert!(cursor.seek(SeekFrom::End(0)).is_err());
+    }
+}
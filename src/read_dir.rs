@@ -1,6 +1,36 @@
-use std::io::{Read, Seek};
+use std::io::{ErrorKind, Read, Seek};
 
-use super::{entry::DirEntryEnum, errors::ExtfsError, extent::Extent};
+use super::{
+    entry::{DirEntryEnum, DirEntryInfo},
+    errors::ExtfsError,
+    extent::Extent,
+    htree::DX_ROOT_INFO_OFFSET,
+    utils::{block_pos, read_exact_at},
+};
+
+/// An opaque position in a directory, to resume a `ReadDir` later.
+///
+/// It can be converted to and from an `u64`, e.g. as the offset of a FUSE `readdir`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct DirCookie {
+    idx: u32,
+    extent_offset: u32,
+}
+
+impl DirCookie {
+    /// Get the cookie as an `u64`.
+    pub fn as_u64(&self) -> u64 {
+        (self.idx as u64) << 32 | self.extent_offset as u64
+    }
+
+    /// Restore a cookie from `as_u64`.
+    pub fn from_u64(value: u64) -> Self {
+        Self {
+            idx: (value >> 32) as u32,
+            extent_offset: value as u32,
+        }
+    }
+}
 
 pub struct ReadDir<R> {
     reader: R,
@@ -10,6 +40,19 @@ pub struct ReadDir<R> {
 
     block_size: u64,
     feature_incompat_filetype: bool,
+    /// The directory is indexed by an htree, whose blocks hold no entries.
+    indexed: bool,
+    /// Return the `.` and `..` entries too.
+    dots: bool,
+    /// Skip entries that fail to parse instead of returning an error.
+    skip_corrupt: bool,
+    /// Entries of an inline directory with their offsets, `idx` is the next one then.
+    inline: Option<Vec<(DirEntryEnum, u64)>>,
+
+    /// The block being read, so a directory is streamed one block at a time.
+    block: Vec<u8>,
+    /// Extent index and offset in the extent of `block`.
+    loaded: Option<(usize, u64)>,
 }
 
 impl<R: Read + Seek> ReadDir<R> {
@@ -26,42 +69,209 @@ impl<R: Read + Seek> ReadDir<R> {
             extent_offset: 0,
             block_size,
             feature_incompat_filetype,
+            indexed: false,
+            dots: false,
+            skip_corrupt: false,
+            inline: None,
+            block: Vec::new(),
+            loaded: None,
+        }
+    }
+
+    pub(crate) fn with_inline(mut self, entries: Vec<(DirEntryEnum, u64)>) -> Self {
+        self.inline = Some(entries);
+        self
+    }
+
+    /// Start reading from a position returned by `position`.
+    pub(crate) fn with_position(mut self, cookie: DirCookie) -> Self {
+        self.idx = cookie.idx as usize;
+        self.extent_offset = cookie.extent_offset as u64;
+        self
+    }
+
+    /// Get the position after the last returned entry, iteration can be resumed from it with
+    /// `Inode::read_dir_from`.
+    pub fn position(&self) -> DirCookie {
+        DirCookie {
+            idx: self.idx as u32,
+            extent_offset: self.extent_offset as u32,
         }
     }
+
+    /// Return the `.` and `..` entries too, they are skipped by default.
+    pub fn with_dots(mut self, dots: bool) -> Self {
+        self.dots = dots;
+        self
+    }
+
+    pub(crate) fn with_index(mut self, indexed: bool) -> Self {
+        self.indexed = indexed;
+        self
+    }
+
+    pub(crate) fn with_skip_corrupt(mut self, skip_corrupt: bool) -> Self {
+        self.skip_corrupt = skip_corrupt;
+        self
+    }
+
+    /// Read the block at `block_start` of the extent unless it's already loaded.
+    fn load_block(&mut self, extent: &Extent, block_start: u64) -> Result<(), ExtfsError> {
+        if self.loaded == Some((self.idx, block_start)) {
+            return Ok(());
+        }
+        self.loaded = None;
+        self.block.resize(self.block_size as usize, 0);
+        let pos = block_pos(extent.get_block_loc(), self.block_size, block_start)?;
+        read_exact_at(&mut self.reader, pos, &mut self.block, "directory block")?;
+        self.loaded = Some((self.idx, block_start));
+        Ok(())
+    }
+
+    /// Check whether the loaded block is a dx_root or dx_node block.
+    ///
+    /// The dx_root is always the first block, a dx_node starts with a fake empty entry covering
+    /// the whole block.
+    fn is_index_block(&self, extent: &Extent, block_start: u64) -> bool {
+        if self.is_root_block(extent, block_start) {
+            return true;
+        }
+
+        let fake = &self.block;
+        let ino = u32::from_le_bytes([fake[0], fake[1], fake[2], fake[3]]);
+        let rec_len = u16::from_le_bytes([fake[4], fake[5]]) as u64;
+        // a rec_len of 0 means 65536
+        ino == 0 && (rec_len == self.block_size || rec_len == 0 && self.block_size == 65536)
+    }
+
+    /// Check whether the block at `block_start` of the extent is the first of the directory.
+    fn is_root_block(&self, extent: &Extent, block_start: u64) -> bool {
+        extent.get_logical_block() + block_start / self.block_size == 0
+    }
+
+    /// Move to the block after the current one.
+    fn skip_block(&mut self, extent_size: u64) {
+        self.extent_offset = (self.extent_offset / self.block_size + 1) * self.block_size;
+        if self.extent_offset >= extent_size {
+            self.extent_offset = 0;
+            self.idx += 1;
+        }
+    }
+
+    /// Convert into an iterator over name, inode number and type of each entry.
+    pub fn infos(self) -> impl Iterator<Item = Result<DirEntryInfo, ExtfsError>> {
+        self.filter_map(|x| match x {
+            Ok(e) => DirEntryInfo::from_entry(&e).map(Ok),
+            Err(e) => Some(Err(e)),
+        })
+    }
+
+    /// Convert into an iterator over the entries and their byte offset in the directory.
+    ///
+    /// The offset is the logical position of the entry in the directory file, or in the inline
+    /// data of an inline directory.
+    pub fn offsets(mut self) -> impl Iterator<Item = Result<(DirEntryEnum, u64), ExtfsError>> {
+        std::iter::from_fn(move || self.next_entry())
+    }
+
+    /// Unwraps this `ReadDir`, returning the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
 }
 
-impl<R: Read + Seek> Iterator for ReadDir<R> {
-    type Item = Result<DirEntryEnum, ExtfsError>;
+impl<R: Read + Seek> ReadDir<R> {
+    /// Read the next entry with its offset in the directory.
+    fn next_entry(&mut self) -> Option<Result<(DirEntryEnum, u64), ExtfsError>> {
+        if let Some(entries) = &self.inline {
+            loop {
+                let (e, offset) = entries.get(self.idx)?;
+                self.idx += 1;
+                if self.dots || (!e.is_dot() && !e.is_dotdot()) {
+                    return Some(Ok((e.clone(), *offset)));
+                }
+            }
+        }
 
-    fn next(&mut self) -> Option<Self::Item> {
         loop {
-            let extent = self.extents.get(self.idx)?;
-            match extent.read_entry(
-                self.block_size,
+            let extent = self.extents.get(self.idx)?.clone();
+            let extent_size = extent.get_len() as u64 * self.block_size;
+            if self.extent_offset >= extent_size {
+                self.extent_offset = 0;
+                self.idx += 1;
+                continue;
+            }
+
+            let in_block = self.extent_offset % self.block_size;
+            let block_start = self.extent_offset - in_block;
+            if let Err(e) = self.load_block(&extent, block_start) {
+                self.skip_block(extent_size);
+                return Some(Err(e));
+            }
+            let is_root = self.is_root_block(&extent, block_start);
+            if self.indexed
+                && (in_block == 0 || is_root)
+                && self.is_index_block(&extent, block_start)
+            {
+                // the dx_root starts with real `.` and `..` entries, the index follows them
+                // whatever the rec_len of `..` says
+                let dots_left = is_root && self.dots && in_block < DX_ROOT_INFO_OFFSET as u64;
+                if !dots_left {
+                    self.skip_block(extent_size);
+                    continue;
+                }
+            }
+
+            match DirEntryEnum::from_block(
+                &self.block,
+                in_block as usize,
                 self.feature_incompat_filetype,
-                &mut self.reader,
-                self.extent_offset,
             ) {
-                Ok(Some((e, offset))) => {
-                    if extent.len as u64 >= offset {
-                        self.extent_offset = 0;
-                        self.idx += 1;
-                    } else {
-                        self.extent_offset = offset;
+                Ok(e) => {
+                    let offset = match block_pos(
+                        extent.get_logical_block(),
+                        self.block_size,
+                        self.extent_offset,
+                    ) {
+                        Ok(offset) => offset,
+                        Err(e) => return Some(Err(e)),
+                    };
+                    self.extent_offset += e.get_rec_len() as u64;
+                    // ignore the tail and deleted entries, and dots unless asked for
+                    if matches!(e, DirEntryEnum::DirEntryTail(_))
+                        || e.is_unused()
+                        || (!self.dots && (e.is_dot() || e.is_dotdot()))
+                    {
+                        continue;
                     }
-
-                    return Some(Ok(e));
+                    return Some(Ok((e, offset)));
                 }
-                // reach the end of the extent
-                Ok(None) => {
-                    self.extent_offset = 0;
-                    self.idx += 1;
-                    continue;
+                // the rest of the block is too short for an entry
+                Err(e) if e.kind() == ErrorKind::UnexpectedEof => {
+                    self.skip_block(extent_size);
                 }
                 Err(e) => {
-                    return Some(Err(e));
+                    let offset = block_pos(extent.get_block_loc(), self.block_size, 0)
+                        .map_or(0, |pos| pos + self.extent_offset);
+                    // skip the rest of the block, retrying would fail forever
+                    self.skip_block(extent_size);
+                    if self.skip_corrupt {
+                        continue;
+                    }
+                    return Some(Err(ExtfsError::CorruptDirEntry {
+                        offset,
+                        reason: e.to_string(),
+                    }));
                 }
             }
         }
     }
 }
+
+impl<R: Read + Seek> Iterator for ReadDir<R> {
+    type Item = Result<DirEntryEnum, ExtfsError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_entry().map(|x| x.map(|(e, _)| e))
+    }
+}
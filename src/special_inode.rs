@@ -0,0 +1,38 @@
+use super::constants::{
+    GOOD_OLD_FIRST_INO, INO_BAD_BLOCKS, INO_BOOT_LOADER, INO_GRP_QUOTA, INO_JOURNAL, INO_RESIZE,
+    INO_ROOT, INO_UNDEL_DIR, INO_USR_QUOTA,
+};
+
+/// An inode with a fixed role, see `FileSystem::special_inode`.
+///
+/// Inodes below `first_ino` are reserved, `lost+found` is the first one after them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SpecialInode {
+    BadBlocks,
+    Root,
+    UserQuota,
+    GroupQuota,
+    BootLoader,
+    UndeleteDir,
+    ResizeInode,
+    Journal,
+    LostAndFound,
+}
+
+impl SpecialInode {
+    /// Get the conventional inode number, `FileSystem::special_ino` follows `first_ino` and
+    /// `s_journal_inum` of the super block instead.
+    pub fn default_ino(&self) -> u64 {
+        match self {
+            Self::BadBlocks => INO_BAD_BLOCKS,
+            Self::Root => INO_ROOT,
+            Self::UserQuota => INO_USR_QUOTA,
+            Self::GroupQuota => INO_GRP_QUOTA,
+            Self::BootLoader => INO_BOOT_LOADER,
+            Self::UndeleteDir => INO_UNDEL_DIR,
+            Self::ResizeInode => INO_RESIZE,
+            Self::Journal => INO_JOURNAL,
+            Self::LostAndFound => GOOD_OLD_FIRST_INO,
+        }
+    }
+}
@@ -1,16 +1,27 @@
-use std::io::Read;
+use std::{
+    cmp,
+    io::Read,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
 use serde::Deserialize;
 use serde_big_array::BigArray;
 
 use super::{
+    checksum::{self, ChecksumKind},
     codec::Decoder,
     constants::{
-        FEATURE_INCOMPAT_64BIT, FEATURE_INCOMPAT_EXTENTS, FEATURE_INCOMPAT_FILETYPE,
-        SUPER_BLOCK_MAGIC,
+        DESC_SIZE_32BIT, DX_HASH_TEA, FEATURE_COMPAT_HAS_JOURNAL, FEATURE_COMPAT_SPARSE_SUPER2,
+        FEATURE_INCOMPAT_64BIT, FEATURE_INCOMPAT_CSUM_SEED, FEATURE_INCOMPAT_EXTENTS,
+        FEATURE_INCOMPAT_FILETYPE, FEATURE_INCOMPAT_FLEX_BG, FEATURE_INCOMPAT_LARGEDIR,
+        FEATURE_INCOMPAT_META_BG, FEATURE_INCOMPAT_RECOVER, FEATURE_RO_COMPAT_BIGALLOC,
+        FEATURE_RO_COMPAT_BTREE_DIR, FEATURE_RO_COMPAT_GDT_CSUM, FEATURE_RO_COMPAT_LARGE_FILE,
+        FEATURE_RO_COMPAT_METADATA_CSUM, FEATURE_RO_COMPAT_SPARSE_SUPER, FLAGS_UNSIGNED_HASH,
+        GOOD_OLD_FIRST_INO, SUPER_BLOCK_MAGIC, ZERO_PADDING_SIZE,
     },
     errors::ExtfsError,
-    utils::compute_u64,
+    mount_options::MountOptions,
+    utils::{block_pos, c_string, compute_u64},
 };
 
 /// https://www.kernel.org/doc/html/latest/filesystems/ext4/globals.html#super-block
@@ -25,7 +36,7 @@ pub struct SuperBlock {
     first_data_block: u32,
     log_block_size: u32,
     log_cluster_size: u32,
-    blocks_per_group: u32,
+    pub(crate) blocks_per_group: u32,
     clusters_per_group: u32,
     pub(crate) inodes_per_group: u32,
     mtime: u32,
@@ -79,8 +90,8 @@ pub struct SuperBlock {
     blocks_count_hi: u32,
     r_blocks_count_hi: u32,
     free_blocks_count_hi: u32,
-    min_extra_isize: u16,
-    want_extra_isize: u16,
+    pub(crate) min_extra_isize: u16,
+    pub(crate) want_extra_isize: u16,
     flags: u32,
     raid_stride: u16,
     mmp_interval: u16,
@@ -89,7 +100,7 @@ pub struct SuperBlock {
     log_groups_per_flex: u8,
     checksum_type: u8,
     reserved_pad: u16,
-    kbytes_written: u64,
+    pub(crate) kbytes_written: u64,
     snapshot_inum: u32,
     snapshot_id: u32,
     snapshot_r_blocks_count: u64,
@@ -131,12 +142,180 @@ pub struct SuperBlock {
     checksum: u32,
 }
 
+/// The OS that created the file system, `s_creator_os`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CreatorOs {
+    Linux,
+    Hurd,
+    Masix,
+    FreeBsd,
+    Lites,
+    Unknown(u32),
+}
+
+impl CreatorOs {
+    /// Convert the raw `s_creator_os` value.
+    pub fn from_raw(value: u32) -> Self {
+        match value {
+            0 => Self::Linux,
+            1 => Self::Hurd,
+            2 => Self::Masix,
+            3 => Self::FreeBsd,
+            4 => Self::Lites,
+            x => Self::Unknown(x),
+        }
+    }
+}
+
+/// Where a file system comes from, see `FileSystem::provenance`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Provenance {
+    pub created_at: SystemTime,
+    pub creator_os: CreatorOs,
+    pub rev_level: u32,
+    pub minor_rev_level: u16,
+}
+
+/// The identification of a file system as reported by `blkid`, see `FileSystem::blkid_info`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlkidInfo {
+    /// Lowercase and hyphenated, e.g. `5a3ae39f-1ede-42ce-babd-2b817db3ad51`.
+    pub uuid: String,
+    /// The volume name without trailing whitespace, `None` if it's empty.
+    pub label: Option<String>,
+    /// `ext2`, `ext3` or `ext4`.
+    pub fs_type: &'static str,
+}
+
+/// Features `blkid` accepts for ext2 and ext3, anything else makes it ext4.
+const EXT3_RO_COMPAT_SUPP: u32 =
+    FEATURE_RO_COMPAT_SPARSE_SUPER | FEATURE_RO_COMPAT_LARGE_FILE | FEATURE_RO_COMPAT_BTREE_DIR;
+const EXT3_INCOMPAT_SUPP: u32 =
+    FEATURE_INCOMPAT_FILETYPE | FEATURE_INCOMPAT_RECOVER | FEATURE_INCOMPAT_META_BG;
+
+/// Decide the type like the ext probes of libblkid: ext4 as soon as a feature isn't known to
+/// ext3, otherwise ext3 with a journal and ext2 without.
+fn blkid_type(feature_compat: u32, feature_incompat: u32, feature_ro_compat: u32) -> &'static str {
+    if feature_ro_compat & !EXT3_RO_COMPAT_SUPP != 0 || feature_incompat & !EXT3_INCOMPAT_SUPP != 0
+    {
+        "ext4"
+    } else if feature_compat & FEATURE_COMPAT_HAS_JOURNAL != 0 {
+        "ext3"
+    } else {
+        "ext2"
+    }
+}
+
+/// A read-only copy of the common fields of the super block, see `FileSystem::superblock`.
+///
+/// Times are in seconds since the epoch.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct SuperBlockView {
+    pub inodes_count: u32,
+    pub blocks_count: u64,
+    pub reserved_blocks_count: u64,
+    pub free_blocks_count: u64,
+    pub free_inodes_count: u32,
+    pub first_data_block: u32,
+    pub block_size: u64,
+    pub blocks_per_group: u32,
+    pub inodes_per_group: u32,
+    pub block_group_count: u32,
+    pub inode_size: u16,
+    pub desc_size: u16,
+    pub first_ino: u32,
+    pub rev_level: u32,
+    pub minor_rev_level: u16,
+    pub creator_os: CreatorOs,
+    pub state: u16,
+    pub mount_count: u16,
+    pub max_mount_count: u16,
+    pub mkfs_time: u64,
+    pub mount_time: u64,
+    pub write_time: u64,
+    pub last_check_time: u64,
+    pub uuid: [u8; 16],
+    pub volume_name: String,
+    pub last_mounted: String,
+    pub feature_compat: u32,
+    pub feature_incompat: u32,
+    pub feature_ro_compat: u32,
+}
+
+/// Combine the low 32 bits of a time with its high 8 bits.
+fn time(lo: u32, hi: u8) -> u64 {
+    compute_u64(lo, hi as u32)
+}
+
 impl SuperBlock {
+    pub fn view(&self) -> SuperBlockView {
+        SuperBlockView {
+            inodes_count: self.inodes_count,
+            blocks_count: self.get_block_count(),
+            reserved_blocks_count: compute_u64(
+                self.r_blocks_count_lo,
+                self.high_bits(self.r_blocks_count_hi),
+            ),
+            free_blocks_count: self.get_free_blocks_count(),
+            free_inodes_count: self.free_inodes_count,
+            first_data_block: self.first_data_block,
+            block_size: self.get_block_size(),
+            blocks_per_group: self.blocks_per_group,
+            inodes_per_group: self.inodes_per_group,
+            block_group_count: self.get_block_group_count(),
+            inode_size: self.inode_size,
+            desc_size: self.get_desc_size(),
+            first_ino: self.first_ino,
+            rev_level: self.rev_level,
+            minor_rev_level: self.minor_rev_level,
+            creator_os: self.creator_os(),
+            state: self.state,
+            mount_count: self.mnt_count,
+            max_mount_count: self.max_mnt_count,
+            mkfs_time: self.get_mkfs_time(),
+            mount_time: time(self.mtime, self.mtime_hi),
+            write_time: time(self.wtime, self.wtime_hi),
+            last_check_time: time(self.lastcheck, self.lastcheck_hi),
+            uuid: self.uuid,
+            volume_name: c_string(&self.volume_name),
+            last_mounted: c_string(&self.last_mounted),
+            feature_compat: self.feature_compat,
+            feature_incompat: self.feature_incompat,
+            feature_ro_compat: self.feature_ro_compat,
+        }
+    }
+
+    /// Check whether the filesystem has a journal.
+    pub fn feature_compat_has_journal(&self) -> bool {
+        (self.feature_compat & FEATURE_COMPAT_HAS_JOURNAL) != 0
+    }
+
+    /// Get the first inode that isn't reserved, revision 0 has no `first_ino` field.
+    pub fn get_first_ino(&self) -> u64 {
+        if self.rev_level == 0 {
+            GOOD_OLD_FIRST_INO
+        } else {
+            self.first_ino as u64
+        }
+    }
+
+    /// Get the inode number of the journal, 0 if the journal is on an external device.
+    pub fn get_journal_inum(&self) -> u32 {
+        self.journal_inum
+    }
+
     /// Check whether it supports 64bit.
     pub fn feature_incompat_64bit(&self) -> bool {
         (self.feature_incompat & FEATURE_INCOMPAT_64BIT) != 0
     }
 
+    /// Check whether directories may be larger than 4 GiB, with the upper size bits in
+    /// `i_size_high`.
+    pub fn feature_incompat_largedir(&self) -> bool {
+        (self.feature_incompat & FEATURE_INCOMPAT_LARGEDIR) != 0
+    }
+
     /// Check whether dir entry supports filetype.
     pub fn feature_incompat_filetype(&self) -> bool {
         (self.feature_incompat & FEATURE_INCOMPAT_FILETYPE) != 0
@@ -147,9 +326,166 @@ impl SuperBlock {
         (self.feature_incompat & FEATURE_INCOMPAT_EXTENTS) != 0
     }
 
+    /// Check whether metadata is checksummed with crc32c.
+    pub fn feature_ro_compat_metadata_csum(&self) -> bool {
+        (self.feature_ro_compat & FEATURE_RO_COMPAT_METADATA_CSUM) != 0
+    }
+
+    /// Get the number of free blocks, as of the last unmount.
+    pub fn get_free_blocks_count(&self) -> u64 {
+        compute_u64(
+            self.free_blocks_count_lo,
+            self.high_bits(self.free_blocks_count_hi),
+        )
+    }
+
+    /// Get the total number of inodes.
+    pub fn get_inodes_count(&self) -> u64 {
+        self.inodes_count as u64
+    }
+
+    /// Get the number of free inodes, as of the last unmount.
+    pub fn get_free_inodes_count(&self) -> u64 {
+        self.free_inodes_count as u64
+    }
+
+    /// Get the OS that created the file system, it decides how `osd1` and `osd2` of inodes are
+    /// interpreted.
+    pub fn creator_os(&self) -> CreatorOs {
+        CreatorOs::from_raw(self.creator_os)
+    }
+
+    /// Get the revision level and the minor revision level.
+    pub fn revision(&self) -> (u32, u16) {
+        (self.rev_level, self.minor_rev_level)
+    }
+
+    /// Get the time the file system was created, 0 if `mkfs` didn't record it.
+    pub fn get_mkfs_time(&self) -> u64 {
+        time(self.mkfs_time, self.mkfs_time_hi)
+    }
+
+    pub fn blkid_info(&self) -> BlkidInfo {
+        let u = &self.uuid;
+        let hex = |range: std::ops::Range<usize>| {
+            u[range]
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect::<String>()
+        };
+        let label = c_string(&self.volume_name).trim_end().to_string();
+        BlkidInfo {
+            uuid: format!(
+                "{}-{}-{}-{}-{}",
+                hex(0..4),
+                hex(4..6),
+                hex(6..8),
+                hex(8..10),
+                hex(10..16)
+            ),
+            label: (!label.is_empty()).then_some(label),
+            fs_type: blkid_type(
+                self.feature_compat,
+                self.feature_incompat,
+                self.feature_ro_compat,
+            ),
+        }
+    }
+
+    pub fn provenance(&self) -> Provenance {
+        let (rev_level, minor_rev_level) = self.revision();
+        Provenance {
+            created_at: UNIX_EPOCH + Duration::from_secs(self.get_mkfs_time()),
+            creator_os: self.creator_os(),
+            rev_level,
+            minor_rev_level,
+        }
+    }
+
+    /// Get the checksum algorithm, metadata_csum supersedes gdt_csum if both are set.
+    pub fn checksum_kind(&self) -> ChecksumKind {
+        if self.feature_ro_compat_metadata_csum() {
+            ChecksumKind::Crc32c
+        } else if (self.feature_ro_compat & FEATURE_RO_COMPAT_GDT_CSUM) != 0 {
+            ChecksumKind::Crc16
+        } else {
+            ChecksumKind::None
+        }
+    }
+
+    /// Get `s_checksum_type`, 1 for crc32c is the only valid value with metadata_csum.
+    pub fn checksum_type(&self) -> u8 {
+        self.checksum_type
+    }
+
+    /// Get the uuid of the file system.
+    pub(crate) fn uuid(&self) -> &[u8] {
+        &self.uuid
+    }
+
+    /// Get the seed of metadata checksums, `None` if metadata_csum is disabled.
+    pub fn get_checksum_seed(&self) -> Option<u32> {
+        if !self.feature_ro_compat_metadata_csum() {
+            return None;
+        }
+        if (self.feature_incompat & FEATURE_INCOMPAT_CSUM_SEED) != 0 {
+            Some(self.checksum_seed)
+        } else {
+            Some(checksum::fs_seed(&self.uuid))
+        }
+    }
+
+    /// Get the seed of htree directory hashes.
+    pub fn get_hash_seed(&self) -> [u32; 4] {
+        self.hash_seed
+    }
+
+    /// Get the default hash algorithm of htree directories.
+    pub fn get_def_hash_version(&self) -> u8 {
+        self.def_hash_version
+    }
+
+    /// Get the hash version used for an htree whose root records `version`, the signed
+    /// versions are replaced by their unsigned variant if the super block asks for it.
+    pub fn effective_hash_version(&self, version: u8) -> u8 {
+        if self.flags & FLAGS_UNSIGNED_HASH != 0 && version <= DX_HASH_TEA {
+            version + 3
+        } else {
+            version
+        }
+    }
+
+    /// Get the default mount options.
+    pub fn default_mount_options(&self) -> MountOptions {
+        MountOptions::new(self.default_mount_opts)
+    }
+
+    /// Get the mount options string, with trailing NULs trimmed.
+    pub fn mount_opts_string(&self) -> String {
+        c_string(&self.mount_opts)
+    }
+
     /// Get total block count.
     pub fn get_block_count(&self) -> u64 {
-        compute_u64(self.blocks_count_lo, self.blocks_count_hi)
+        compute_u64(self.blocks_count_lo, self.high_bits(self.blocks_count_hi))
+    }
+
+    /// The upper 32 bits of block counts are only valid with the 64bit feature.
+    fn high_bits(&self, hi: u32) -> u32 {
+        if self.feature_incompat_64bit() {
+            hi
+        } else {
+            0
+        }
+    }
+
+    /// Get size of block group descriptor.
+    pub fn get_desc_size(&self) -> u16 {
+        if self.feature_incompat_64bit() {
+            cmp::max(self.desc_size, DESC_SIZE_32BIT as u16)
+        } else {
+            DESC_SIZE_32BIT as u16
+        }
     }
 
     // Get size of single block.
@@ -157,9 +493,105 @@ impl SuperBlock {
         1024 << self.log_block_size
     }
 
+    /// Get the block where data starts, 1 with 1 KiB blocks as block 0 holds the boot sector
+    /// and the super block, 0 otherwise.
+    pub fn get_first_data_block(&self) -> u64 {
+        self.first_data_block as u64
+    }
+
+    /// Get the first block of a group.
+    pub fn group_first_block(&self, group: u64) -> u64 {
+        group * self.blocks_per_group as u64 + self.first_data_block as u64
+    }
+
+    /// Get the size of an allocation cluster, the block size without bigalloc.
+    pub fn get_cluster_size(&self) -> u64 {
+        if (self.feature_ro_compat & FEATURE_RO_COMPAT_BIGALLOC) == 0 {
+            return self.get_block_size();
+        }
+        cmp::max(1024 << self.log_cluster_size, self.get_block_size())
+    }
+
+    /// Get the number of blocks in a cluster, bitmaps and free counts of groups are in clusters.
+    pub fn get_cluster_ratio(&self) -> u64 {
+        self.get_cluster_size() / self.get_block_size()
+    }
+
     // Get block group count.
     pub fn get_block_group_count(&self) -> u32 {
-        self.get_block_count() as u32 / self.blocks_per_group + 1
+        // blocks before first_data_block don't belong to any group
+        let blocks = self
+            .get_block_count()
+            .saturating_sub(self.first_data_block as u64);
+        blocks.div_ceil(cmp::max(self.blocks_per_group, 1) as u64) as u32
+    }
+
+    /// Check whether block group descriptors are stored with the meta_bg layout.
+    pub fn feature_incompat_meta_bg(&self) -> bool {
+        (self.feature_incompat & FEATURE_INCOMPAT_META_BG) != 0
+    }
+
+    /// Get the number of groups packed in a flex group, `None` without flex_bg.
+    pub fn flex_bg_size(&self) -> Option<u32> {
+        if (self.feature_incompat & FEATURE_INCOMPAT_FLEX_BG) == 0 {
+            return None;
+        }
+        1u32.checked_shl(self.log_groups_per_flex as u32)
+    }
+
+    /// Check whether a group holds a copy of the super block, see `ext4_bg_has_super`.
+    pub fn group_has_super(&self, group: u64) -> bool {
+        if group == 0 {
+            return true;
+        }
+        if (self.feature_compat & FEATURE_COMPAT_SPARSE_SUPER2) != 0 {
+            return self.backup_bgs.iter().any(|&g| g as u64 == group);
+        }
+        if group <= 1 || (self.feature_ro_compat & FEATURE_RO_COMPAT_SPARSE_SUPER) == 0 {
+            return true;
+        }
+        let is_power_of = |base: u64| {
+            let mut n = group;
+            while n.is_multiple_of(base) {
+                n /= base;
+            }
+            n == 1
+        };
+        group & 1 == 1 && (is_power_of(3) || is_power_of(5) || is_power_of(7))
+    }
+
+    /// Get the byte position of the descriptor of `group`, see `descriptor_loc` in the kernel.
+    ///
+    /// Without meta_bg, the descriptors follow the super block. With meta_bg, the descriptors
+    /// of each meta group, as many groups as descriptors fit in a block, are stored in its
+    /// first group, after the backup super block if there's one. Meta groups before
+    /// `s_first_meta_bg` keep the old layout.
+    pub fn descriptor_pos(&self, group: u64) -> Result<u64, ExtfsError> {
+        let block_size = self.get_block_size();
+        let desc_size = self.get_desc_size() as u64;
+        let per_block = cmp::max(block_size / desc_size, 1);
+        let meta_group = group / per_block;
+        let offset = group % per_block * desc_size;
+
+        // the super block is in block 1 with 1 KiB blocks, in block 0 otherwise
+        let sb_block = ZERO_PADDING_SIZE / block_size;
+        if !self.feature_incompat_meta_bg() || meta_group < self.first_meta_bg as u64 {
+            return block_pos(sb_block + 1 + meta_group, block_size, offset);
+        }
+
+        let first = meta_group * per_block;
+        let mut block = first
+            .checked_mul(self.blocks_per_group as u64)
+            .and_then(|x| x.checked_add(self.first_data_block as u64))
+            .ok_or(ExtfsError::ArithmeticOverflow("descriptor position"))?;
+        if self.group_has_super(first) {
+            block += 1;
+        }
+        // 1 KiB blocks with bigalloc start the groups at block 0
+        if meta_group == 0 && self.first_data_block == 0 && block_size == 1024 {
+            block += 1;
+        }
+        block_pos(block, block_size, offset)
     }
 
     pub fn from_reader(mut reader: impl Read) -> Result<Self, ExtfsError> {
@@ -171,10 +603,8 @@ impl SuperBlock {
         }
 
         // validate block group count
-        let bg_count_from_block = (sb.get_block_count() + (sb.blocks_per_group as u64) - 1)
-            / (sb.blocks_per_group as u64);
-        let bg_count_from_inode =
-            ((sb.inodes_count + sb.inodes_per_group - 1) / sb.inodes_per_group) as u64;
+        let bg_count_from_block = sb.get_block_group_count() as u64;
+        let bg_count_from_inode = sb.inodes_count.div_ceil(sb.inodes_per_group) as u64;
         if bg_count_from_block != bg_count_from_inode {
             return Err(ExtfsError::BlockGroupCountMismatch {
                 blocks: bg_count_from_block,
@@ -191,7 +621,7 @@ impl SuperBlock {
 mod tests {
     use std::{fs::File, io::Seek};
 
-    use super::SuperBlock;
+    use super::{blkid_type, CreatorOs, SuperBlock};
 
     #[test]
     fn test_super_block() {
@@ -207,4 +637,23 @@ mod tests {
             super_block.get_block_group_count(),
         );
     }
+
+    #[test]
+    fn test_blkid_type() {
+        assert_eq!(blkid_type(0, 0x2, 0x1), "ext2");
+        // has_journal, needs_recovery
+        assert_eq!(blkid_type(0x4, 0x6, 0x3), "ext3");
+        // extents
+        assert_eq!(blkid_type(0x4, 0x42, 0x1), "ext4");
+        // metadata_csum without a journal
+        assert_eq!(blkid_type(0, 0x2, 0x401), "ext4");
+    }
+
+    #[test]
+    fn test_creator_os() {
+        assert_eq!(CreatorOs::from_raw(0), CreatorOs::Linux);
+        assert_eq!(CreatorOs::from_raw(1), CreatorOs::Hurd);
+        assert_eq!(CreatorOs::from_raw(3), CreatorOs::FreeBsd);
+        assert_eq!(CreatorOs::from_raw(9), CreatorOs::Unknown(9));
+    }
 }
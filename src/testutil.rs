@@ -0,0 +1,246 @@
+// Helpers to build tiny ext4 images in memory, for tests that don't want to ship image files.
+
+use std::io::Cursor;
+
+use super::{errors::ExtfsError, fs::FileSystem};
+
+const BLOCK_SIZE: usize = 1024;
+const INODE_SIZE: usize = 128;
+const INODES_PER_GROUP: usize = 32;
+const BLOCKS_PER_GROUP: usize = 8192;
+
+const BLOCK_BITMAP: usize = 3;
+const INODE_BITMAP: usize = 4;
+const INODE_TABLE: usize = 5;
+const INODE_TABLE_BLOCKS: usize = INODES_PER_GROUP * INODE_SIZE / BLOCK_SIZE;
+const ROOT_DIR_BLOCK: usize = INODE_TABLE + INODE_TABLE_BLOCKS;
+const FIRST_FILE_INO: usize = 12;
+
+/// Open an image held in a byte vector.
+pub fn from_vec(data: Vec<u8>) -> Result<FileSystem<Cursor<Vec<u8>>>, ExtfsError> {
+    FileSystem::from_reader(Cursor::new(data))
+}
+
+/// Encode the directory entry of a regular file, padded with zeros to `rec_len`.
+pub fn dir_entry(ino: u32, rec_len: u16, name: &[u8]) -> Vec<u8> {
+    let mut b = Vec::new();
+    b.extend(ino.to_le_bytes());
+    b.extend(rec_len.to_le_bytes());
+    b.push(name.len() as u8);
+    b.push(1);
+    b.extend(name);
+    b.resize(rec_len as usize, 0);
+    b
+}
+
+/// Build a minimal ext4 image with 1 KiB blocks and a single block group, whose root directory
+/// holds regular files, each stored in one extent.
+///
+/// Files get inode numbers from 12 in the order they are added. There's no journal and no
+/// checksums, the image is only meant to be read by this crate.
+#[derive(Debug, Default)]
+pub struct ImageBuilder {
+    files: Vec<(String, Vec<u8>)>,
+}
+
+impl ImageBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a regular file to the root directory.
+    pub fn file(mut self, name: &str, contents: &[u8]) -> Self {
+        self.files.push((name.to_string(), contents.to_vec()));
+        self
+    }
+
+    /// Write the image.
+    ///
+    /// Panics if the files don't fit in the first group or the root directory block.
+    pub fn build(&self) -> Vec<u8> {
+        assert!(FIRST_FILE_INO + self.files.len() <= INODES_PER_GROUP + 1);
+
+        let data_blocks: Vec<usize> = self
+            .files
+            .iter()
+            .map(|(_, contents)| contents.len().div_ceil(BLOCK_SIZE))
+            .collect();
+        let used_blocks = ROOT_DIR_BLOCK + 1 + data_blocks.iter().sum::<usize>();
+        let blocks_count = used_blocks.max(64);
+        assert!(blocks_count <= BLOCKS_PER_GROUP);
+
+        let mut image = vec![0; blocks_count * BLOCK_SIZE];
+        let used_inodes = FIRST_FILE_INO - 1 + self.files.len();
+        write_super_block(
+            &mut image[1024..2048],
+            blocks_count,
+            used_blocks,
+            used_inodes,
+        );
+        write_descriptor(
+            &mut image[2 * BLOCK_SIZE..],
+            blocks_count,
+            used_blocks,
+            used_inodes,
+        );
+        set_bits(&mut image[BLOCK_BITMAP * BLOCK_SIZE..], used_blocks - 1);
+        set_bits(&mut image[INODE_BITMAP * BLOCK_SIZE..], used_inodes);
+
+        // the root directory
+        let mut dir = vec![(2, ".".to_string(), 2), (2, "..".to_string(), 2)];
+        write_inode(&mut image, 2, 0o040755, BLOCK_SIZE, ROOT_DIR_BLOCK, 1);
+
+        let mut block = ROOT_DIR_BLOCK + 1;
+        for (i, (name, contents)) in self.files.iter().enumerate() {
+            let ino = FIRST_FILE_INO + i;
+            write_inode(
+                &mut image,
+                ino,
+                0o100644,
+                contents.len(),
+                block,
+                data_blocks[i],
+            );
+            image[block * BLOCK_SIZE..block * BLOCK_SIZE + contents.len()]
+                .copy_from_slice(contents);
+            dir.push((ino, name.clone(), 1));
+            block += data_blocks[i];
+        }
+        write_dir_block(&mut image[ROOT_DIR_BLOCK * BLOCK_SIZE..], &dir);
+
+        image
+    }
+}
+
+fn put(buf: &mut [u8], offset: usize, bytes: &[u8]) {
+    buf[offset..offset + bytes.len()].copy_from_slice(bytes);
+}
+
+fn set_bits(bitmap: &mut [u8], count: usize) {
+    for i in 0..count {
+        bitmap[i / 8] |= 1 << (i % 8);
+    }
+}
+
+fn write_super_block(sb: &mut [u8], blocks_count: usize, used_blocks: usize, used_inodes: usize) {
+    put(sb, 0x0, &(INODES_PER_GROUP as u32).to_le_bytes());
+    put(sb, 0x4, &(blocks_count as u32).to_le_bytes());
+    put(
+        sb,
+        0xC,
+        &((blocks_count - used_blocks) as u32).to_le_bytes(),
+    );
+    put(
+        sb,
+        0x10,
+        &((INODES_PER_GROUP - used_inodes) as u32).to_le_bytes(),
+    );
+    // s_first_data_block
+    put(sb, 0x14, &1u32.to_le_bytes());
+    put(sb, 0x20, &(BLOCKS_PER_GROUP as u32).to_le_bytes());
+    put(sb, 0x24, &(BLOCKS_PER_GROUP as u32).to_le_bytes());
+    put(sb, 0x28, &(INODES_PER_GROUP as u32).to_le_bytes());
+    // s_magic and s_state
+    put(sb, 0x38, &0xEF53u16.to_le_bytes());
+    put(sb, 0x3A, &1u16.to_le_bytes());
+    // s_rev_level, s_first_ino and s_inode_size
+    put(sb, 0x4C, &1u32.to_le_bytes());
+    put(sb, 0x54, &11u32.to_le_bytes());
+    put(sb, 0x58, &(INODE_SIZE as u16).to_le_bytes());
+    // filetype and extents
+    put(sb, 0x60, &0x42u32.to_le_bytes());
+}
+
+fn write_descriptor(desc: &mut [u8], blocks_count: usize, used_blocks: usize, used_inodes: usize) {
+    put(desc, 0x0, &(BLOCK_BITMAP as u32).to_le_bytes());
+    put(desc, 0x4, &(INODE_BITMAP as u32).to_le_bytes());
+    put(desc, 0x8, &(INODE_TABLE as u32).to_le_bytes());
+    // the only group holds all the blocks, so its count matches the super block
+    put(
+        desc,
+        0xC,
+        &((blocks_count - used_blocks) as u16).to_le_bytes(),
+    );
+    put(
+        desc,
+        0xE,
+        &((INODES_PER_GROUP - used_inodes) as u16).to_le_bytes(),
+    );
+    put(desc, 0x10, &1u16.to_le_bytes());
+}
+
+fn write_inode(image: &mut [u8], ino: usize, mode: u16, size: usize, block: usize, len: usize) {
+    let pos = INODE_TABLE * BLOCK_SIZE + (ino - 1) * INODE_SIZE;
+    let inode = &mut image[pos..pos + INODE_SIZE];
+    put(inode, 0x0, &mode.to_le_bytes());
+    put(inode, 0x4, &(size as u32).to_le_bytes());
+    let links: u16 = if mode & 0o040000 != 0 { 2 } else { 1 };
+    put(inode, 0x1A, &links.to_le_bytes());
+    // i_blocks in 512 bytes sectors
+    put(
+        inode,
+        0x1C,
+        &((len * BLOCK_SIZE / 512) as u32).to_le_bytes(),
+    );
+    // EXT4_EXTENTS_FL
+    put(inode, 0x20, &0x8_0000u32.to_le_bytes());
+
+    // extent header, then one extent if there's data
+    let entries: u16 = if len > 0 { 1 } else { 0 };
+    put(inode, 0x28, &0xF30Au16.to_le_bytes());
+    put(inode, 0x2A, &entries.to_le_bytes());
+    put(inode, 0x2C, &4u16.to_le_bytes());
+    if len > 0 {
+        put(inode, 0x38, &(len as u16).to_le_bytes());
+        put(inode, 0x3C, &(block as u32).to_le_bytes());
+    }
+}
+
+fn write_dir_block(block: &mut [u8], entries: &[(usize, String, u8)]) {
+    let mut pos = 0;
+    for (i, (ino, name, file_type)) in entries.iter().enumerate() {
+        let mut rec_len = (8 + name.len()).div_ceil(4) * 4;
+        if i == entries.len() - 1 {
+            rec_len = BLOCK_SIZE - pos;
+        }
+        assert!(pos + rec_len <= BLOCK_SIZE);
+
+        put(block, pos, &(*ino as u32).to_le_bytes());
+        put(block, pos + 4, &(rec_len as u16).to_le_bytes());
+        block[pos + 6] = name.len() as u8;
+        block[pos + 7] = *file_type;
+        put(block, pos + 8, name.as_bytes());
+        pos += rec_len;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{from_vec, ImageBuilder};
+
+    #[test]
+    fn test_image_builder() {
+        let big: Vec<u8> = (0..3000).map(|i| i as u8).collect();
+        let image = ImageBuilder::new()
+            .file("hello.txt", b"hello\n")
+            .file("big.bin", &big)
+            .file("empty", b"")
+            .build();
+        let fs = from_vec(image).unwrap();
+
+        let mut names: Vec<String> = fs
+            .list_dir("/")
+            .unwrap()
+            .into_iter()
+            .map(|x| x.name)
+            .collect();
+        names.sort();
+        assert_eq!(names, ["big.bin", "empty", "hello.txt"]);
+
+        assert_eq!(fs.read("/hello.txt").unwrap(), b"hello\n");
+        assert_eq!(fs.read("/big.bin").unwrap(), big);
+        assert!(fs.read("/empty").unwrap().is_empty());
+        assert_eq!(fs.path_to_ino("/empty").unwrap(), 14);
+        assert!(fs.free_blocks_verified().consistent);
+    }
+}
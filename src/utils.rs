@@ -1,12 +1,88 @@
+use std::{
+    io::{ErrorKind, Read, Seek, SeekFrom},
+    path::PathBuf,
+};
+
+use super::errors::ExtfsError;
+
 // compute complete u64 with lower address and high address
 #[inline]
 pub fn compute_u64(lower: u32, high: u32) -> u64 {
     ((high as u64) << 32) | (lower as u64)
 }
 
+// decode a NUL-padded byte string
+pub fn c_string(bytes: &[u8]) -> String {
+    let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..end]).to_string()
+}
+
+/// Convert bytes read from the image to a path, only lossy where paths must be UTF-8.
+pub fn path_from_bytes(bytes: Vec<u8>) -> PathBuf {
+    #[cfg(unix)]
+    {
+        use std::{ffi::OsString, os::unix::ffi::OsStringExt};
+        PathBuf::from(OsString::from_vec(bytes))
+    }
+    #[cfg(not(unix))]
+    {
+        PathBuf::from(String::from_utf8_lossy(&bytes).to_string())
+    }
+}
+
+/// Compute the byte position of `offset` in `block`, a crafted image must not wrap it around.
+pub fn block_pos(block: u64, block_size: u64, offset: u64) -> Result<u64, ExtfsError> {
+    block
+        .checked_mul(block_size)
+        .and_then(|x| x.checked_add(offset))
+        .ok_or(ExtfsError::ArithmeticOverflow("block position"))
+}
+
+/// Convert a length read from the image to `usize` before allocating a buffer for it, which
+/// may not fit on 32-bit platforms.
+pub fn to_usize(len: u64) -> Result<usize, ExtfsError> {
+    usize::try_from(len).map_err(|_| ExtfsError::FileTooLargeForPlatform(len))
+}
+
+/// Seek to `offset`, an error carries the offset and what was about to be read.
+pub fn seek_at(
+    mut reader: impl Seek,
+    offset: u64,
+    context: &'static str,
+) -> Result<(), ExtfsError> {
+    reader
+        .seek(SeekFrom::Start(offset))
+        .map_err(|source| ExtfsError::IoAt {
+            source,
+            offset,
+            context,
+        })?;
+    Ok(())
+}
+
+/// Seek to `offset` and fill `buf`, an unexpected EOF means the image is shorter than the
+/// file system claims and is reported as `TruncatedImage`.
+pub fn read_exact_at(
+    mut reader: impl Read + Seek,
+    offset: u64,
+    buf: &mut [u8],
+    reading: &'static str,
+) -> Result<(), ExtfsError> {
+    seek_at(&mut reader, offset, reading)?;
+    reader.read_exact(buf).map_err(|e| match e.kind() {
+        ErrorKind::UnexpectedEof => ExtfsError::TruncatedImage { reading, offset },
+        _ => ExtfsError::IoAt {
+            source: e,
+            offset,
+            context: reading,
+        },
+    })
+}
+
 #[cfg(test)]
 mod tests {
-    use super::compute_u64;
+    use super::{block_pos, c_string, compute_u64, to_usize};
+    use crate::ExtfsError;
 
     #[test]
     fn test_compute_u64() {
@@ -14,4 +90,31 @@ mod tests {
         assert_eq!(compute_u64(0x00, 0x01), 0x0000_0001_0000_0000);
         assert_eq!(compute_u64(0x01, 0x00), 0x0000_0000_0000_0001);
     }
+
+    #[test]
+    fn test_c_string() {
+        assert_eq!(c_string(b"acl\0\0\0"), "acl");
+        assert_eq!(c_string(b"acl"), "acl");
+        assert_eq!(c_string(b"\0acl"), "");
+    }
+
+    #[test]
+    fn test_block_pos() {
+        assert_eq!(block_pos(2, 1024, 24).unwrap(), 2072);
+        assert!(matches!(
+            block_pos(1 << 48, 1 << 16, 0),
+            Err(ExtfsError::ArithmeticOverflow(_))
+        ));
+        assert!(block_pos(u64::MAX / 1024, 1024, 1024).is_err());
+    }
+
+    #[test]
+    fn test_to_usize() {
+        assert_eq!(to_usize(4096).unwrap(), 4096);
+        #[cfg(target_pointer_width = "32")]
+        assert!(matches!(
+            to_usize(1 << 32),
+            Err(ExtfsError::FileTooLargeForPlatform(_))
+        ));
+    }
 }
@@ -0,0 +1,128 @@
+use std::{
+    io::{Read, Seek},
+    path::PathBuf,
+    sync::Mutex,
+};
+
+use super::{
+    entry::DirEntryInfo, errors::ExtfsError, fs::FileSystem, inode::Inode, metadata::Metadata,
+    read_at::ReadAtCursor, read_dir::ReadDir,
+};
+
+/// An open directory of the walk, reading with a cursor of its own.
+struct Frame<'a, R> {
+    path: PathBuf,
+    ino: u64,
+    dir: ReadDir<ReadAtCursor<&'a Mutex<R>>>,
+}
+
+/// A lazy recursive iterator over the entries below a directory, created by
+/// `FileSystem::walk_dir`.
+///
+/// Only the directories being walked are kept open, so the memory stays bounded by the depth of
+/// the tree. Symlinks aren't followed.
+pub struct WalkDir<'a, R> {
+    fs: &'a FileSystem<R>,
+    stack: Vec<Frame<'a, R>>,
+    max_depth: usize,
+}
+
+impl<'a, R: Read + Seek> WalkDir<'a, R> {
+    pub(crate) fn new(
+        fs: &'a FileSystem<R>,
+        path: PathBuf,
+        ino: u64,
+        inode: &Inode,
+    ) -> Result<Self, ExtfsError> {
+        let mut walk = Self {
+            fs,
+            stack: Vec::new(),
+            max_depth: usize::MAX,
+        };
+        walk.push(path, ino, inode)?;
+        Ok(walk)
+    }
+
+    /// Set the maximum depth to descend, 1 only yields the entries of the directory itself.
+    pub fn max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = depth;
+        self
+    }
+
+    fn push(&mut self, path: PathBuf, ino: u64, inode: &Inode) -> Result<(), ExtfsError> {
+        let block_size = self.fs.block_size();
+        let feature_incompat_filetype = self.fs.feature_incompat_filetype();
+        let (extents, inline) = if inode.uses_inline_data() {
+            (
+                Vec::new(),
+                Some(inode.inline_dir_entries(feature_incompat_filetype)?),
+            )
+        } else {
+            (self.fs.extents_of_inode(inode)?, None)
+        };
+        let mut dir = ReadDir::new(
+            self.fs.cursor(),
+            extents,
+            block_size,
+            feature_incompat_filetype,
+        )
+        .with_index(inode.is_indexed())
+        .with_skip_corrupt(self.fs.tolerates_corruption());
+        if let Some(entries) = inline {
+            dir = dir.with_inline(entries);
+        }
+        self.stack.push(Frame { path, ino, dir });
+        Ok(())
+    }
+
+    /// Read the next entry of the innermost open directory.
+    fn next_info(&mut self) -> Option<Result<(PathBuf, DirEntryInfo), ExtfsError>> {
+        loop {
+            let frame = self.stack.last_mut()?;
+            match frame.dir.next() {
+                Some(Ok(e)) => {
+                    if let Some(info) = DirEntryInfo::from_entry(&e) {
+                        return Some(Ok((frame.path.join(&info.name), info)));
+                    }
+                }
+                Some(Err(e)) => {
+                    // don't retry a directory that fails to read
+                    self.stack.pop();
+                    return Some(Err(e));
+                }
+                None => {
+                    self.stack.pop();
+                }
+            }
+        }
+    }
+}
+
+impl<R: Read + Seek> Iterator for WalkDir<'_, R> {
+    type Item = Result<(PathBuf, Metadata), ExtfsError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (path, info, inode) = loop {
+            let (path, info) = match self.next_info()? {
+                Ok(x) => x,
+                Err(e) => return Some(Err(e)),
+            };
+            match self.fs.inode(info.ino) {
+                Ok(inode) => break (path, info, inode),
+                Err(_) if self.fs.tolerates_corruption() => {}
+                Err(e) => return Some(Err(e)),
+            }
+        };
+        let depth = self.stack.len();
+
+        // a corrupted entry pointing to an ancestor would loop forever
+        let is_ancestor = self.stack.iter().any(|f| f.ino == info.ino);
+        if inode.is_dir() && depth < self.max_depth && !is_ancestor {
+            if let Err(e) = self.push(path.clone(), info.ino, &inode) {
+                return Some(Err(e));
+            }
+        }
+
+        Some(Ok((path, Metadata::new(inode))))
+    }
+}
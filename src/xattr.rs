@@ -0,0 +1,222 @@
+// https://www.kernel.org/doc/html/latest/filesystems/ext4/dynamic.html#extended-attributes
+
+/// Magic number of the extended attribute area in an inode or a block.
+pub const XATTR_MAGIC: u32 = 0xEA02_0000;
+/// Size of `ext4_xattr_entry` without the name.
+const XATTR_ENTRY_SIZE: usize = 16;
+/// Size of `ext4_xattr_header` at the start of an attribute block.
+const XATTR_BLOCK_HEADER_SIZE: usize = 32;
+
+/// Name index of `system.` attributes, e.g. `system.data` holding inline data.
+pub const XATTR_INDEX_SYSTEM: u8 = 7;
+
+/// Name prefixes by index, see `ext4_xattr_handler_map` in fs/ext4/xattr.c.
+///
+/// The POSIX ACL attributes store an empty name, the prefix is the whole name.
+const XATTR_PREFIXES: [(u8, &str); 7] = [
+    (1, "user."),
+    (2, "system.posix_acl_access"),
+    (3, "system.posix_acl_default"),
+    (4, "trusted."),
+    (6, "security."),
+    (XATTR_INDEX_SYSTEM, "system."),
+    (8, "system.richacl"),
+];
+
+/// Split a full attribute name into its name index and the name stored on disk.
+///
+/// Returns `None` if no known prefix matches.
+pub fn split_name(full_name: &str) -> Option<(u8, &str)> {
+    XATTR_PREFIXES
+        .iter()
+        .filter(|(_, prefix)| full_name.starts_with(prefix))
+        // "system.posix_acl_access" must win over "system."
+        .max_by_key(|(_, prefix)| prefix.len())
+        .map(|(index, prefix)| (*index, &full_name[prefix.len()..]))
+}
+
+/// An extended attribute with its value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct XattrEntry {
+    /// Index of the name prefix, e.g. 1 for `user.`.
+    pub name_index: u8,
+    /// Name without the prefix.
+    pub name: Vec<u8>,
+    pub value: Vec<u8>,
+}
+
+impl XattrEntry {
+    /// Get the name with its prefix, e.g. `security.selinux`.
+    pub fn full_name(&self) -> String {
+        let prefix = XATTR_PREFIXES
+            .iter()
+            .find(|(index, _)| *index == self.name_index)
+            .map_or("", |(_, prefix)| prefix);
+        format!("{}{}", prefix, String::from_utf8_lossy(&self.name))
+    }
+
+    /// Check whether the entry has the given full name.
+    pub fn matches(&self, full_name: &str) -> bool {
+        split_name(full_name)
+            .is_some_and(|(index, name)| index == self.name_index && name.as_bytes() == self.name)
+    }
+}
+
+fn read_u16(buf: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(
+        buf.get(offset..offset + 2)?.try_into().ok()?,
+    ))
+}
+
+fn read_u32(buf: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(
+        buf.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+/// Parse the entries starting at `start` of `buf`, whose value offsets are relative to
+/// `value_base`. Parsing stops at the end marker or at the first malformed entry.
+fn parse_entries(buf: &[u8], start: usize, value_base: usize) -> Vec<XattrEntry> {
+    parse_located_entries(buf, start, value_base)
+        .into_iter()
+        .map(|(entry, _)| entry)
+        .collect()
+}
+
+/// Parse the entries like `parse_entries`, each with the offset of its value in `buf`, `None`
+/// for a value stored in a separate inode.
+fn parse_located_entries(
+    buf: &[u8],
+    start: usize,
+    value_base: usize,
+) -> Vec<(XattrEntry, Option<usize>)> {
+    let mut entries = Vec::new();
+    let mut pos = start;
+    while let Some(first) = read_u32(buf, pos) {
+        if first == 0 {
+            break;
+        }
+        let name_len = buf[pos] as usize;
+        let name_index = buf[pos + 1];
+        let (Some(value_offs), Some(value_inum), Some(value_size)) = (
+            read_u16(buf, pos + 2),
+            read_u32(buf, pos + 4),
+            read_u32(buf, pos + 8),
+        ) else {
+            break;
+        };
+        let Some(name) = buf.get(pos + XATTR_ENTRY_SIZE..pos + XATTR_ENTRY_SIZE + name_len) else {
+            break;
+        };
+
+        // values stored in a separate inode (ea_inode) aren't read
+        let (value, location) = if value_inum == 0 {
+            let offset = value_base + value_offs as usize;
+            match buf.get(offset..offset + value_size as usize) {
+                Some(v) => (v.to_vec(), Some(offset)),
+                None => break,
+            }
+        } else {
+            (Vec::new(), None)
+        };
+
+        entries.push((
+            XattrEntry {
+                name_index,
+                name: name.to_vec(),
+                value,
+            },
+            location,
+        ));
+        pos += (XATTR_ENTRY_SIZE + name_len).div_ceil(4) * 4;
+    }
+    entries
+}
+
+/// Parse the extended attributes stored in an inode after its `extra_isize` fields.
+pub fn parse_inode_xattrs(area: &[u8]) -> Vec<XattrEntry> {
+    if read_u32(area, 0) != Some(XATTR_MAGIC) {
+        return Vec::new();
+    }
+    // value offsets are relative to the first entry
+    parse_entries(area, 4, 4)
+}
+
+/// Parse the extended attributes stored in an inode like `parse_inode_xattrs`, each with the
+/// offset of its value in `area`.
+pub(crate) fn parse_located_inode_xattrs(area: &[u8]) -> Vec<(XattrEntry, Option<usize>)> {
+    if read_u32(area, 0) != Some(XATTR_MAGIC) {
+        return Vec::new();
+    }
+    parse_located_entries(area, 4, 4)
+}
+
+/// Parse the extended attributes stored in the block pointed to by `i_file_acl`.
+pub fn parse_block_xattrs(block: &[u8]) -> Vec<XattrEntry> {
+    if read_u32(block, 0) != Some(XATTR_MAGIC) {
+        return Vec::new();
+    }
+    // value offsets are relative to the start of the block
+    parse_entries(block, XATTR_BLOCK_HEADER_SIZE, 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        parse_inode_xattrs, parse_located_inode_xattrs, split_name, XattrEntry, XATTR_MAGIC,
+    };
+
+    #[test]
+    fn test_parse_inode_xattrs() {
+        let mut area = vec![0; 96];
+        area[0..4].copy_from_slice(&XATTR_MAGIC.to_le_bytes());
+        // system.data = "0123456789", value at the end of the area
+        let entry = &mut area[4..24];
+        entry[0] = 4;
+        entry[1] = 7;
+        entry[2..4].copy_from_slice(&80u16.to_le_bytes());
+        entry[8..12].copy_from_slice(&10u32.to_le_bytes());
+        entry[16..20].copy_from_slice(b"data");
+        area[84..94].copy_from_slice(b"0123456789");
+
+        let xattrs = parse_inode_xattrs(&area);
+        assert_eq!(xattrs.len(), 1);
+        assert_eq!(xattrs[0].name_index, 7);
+        assert_eq!(xattrs[0].name, b"data");
+        assert_eq!(xattrs[0].value, b"0123456789");
+        assert_eq!(parse_located_inode_xattrs(&area)[0].1, Some(84));
+
+        assert!(parse_inode_xattrs(&area[4..]).is_empty());
+        // an entry running past the end is ignored
+        area[4..6].copy_from_slice(&[200, 7]);
+        assert!(parse_inode_xattrs(&area).is_empty());
+    }
+
+    #[test]
+    fn test_names() {
+        assert_eq!(split_name("user.foo"), Some((1, "foo")));
+        assert_eq!(split_name("security.selinux"), Some((6, "selinux")));
+        assert_eq!(split_name("system.data"), Some((7, "data")));
+        assert_eq!(split_name("system.posix_acl_access"), Some((2, "")));
+        assert_eq!(split_name("system.posix_acl_default"), Some((3, "")));
+        assert_eq!(split_name("foo"), None);
+
+        let acl = XattrEntry {
+            name_index: 2,
+            name: Vec::new(),
+            value: Vec::new(),
+        };
+        assert_eq!(acl.full_name(), "system.posix_acl_access");
+        assert!(acl.matches("system.posix_acl_access"));
+        assert!(!acl.matches("system."));
+
+        let selinux = XattrEntry {
+            name_index: 6,
+            name: b"selinux".to_vec(),
+            value: Vec::new(),
+        };
+        assert_eq!(selinux.full_name(), "security.selinux");
+        assert!(selinux.matches("security.selinux"));
+        assert!(!selinux.matches("user.selinux"));
+    }
+}